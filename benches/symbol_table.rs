@@ -0,0 +1,40 @@
+// Looks names up from deep inside a program where every name is declared in many scopes, so each
+// scope walked through has to be checked in constant time. Run with `cargo bench`
+use oil::{
+    symbol_table::{Symbol, SymbolTable},
+    types::DataType,
+};
+use std::{hint::black_box, time::Instant};
+
+const NAMES: usize = 100;
+const SCOPES: usize = 1000;
+const LOOKUPS: usize = 1_000_000;
+
+fn main() {
+    let names: Vec<String> = (0..NAMES).map(|n| format!("name_{n}")).collect();
+    let mut symbol_table = SymbolTable::new();
+
+    // Every name is declared in every other scope, so lookups have to walk back through some
+    for depth in 0..SCOPES {
+        symbol_table.add_scope();
+
+        if depth % 2 == 0 {
+            for name in &names {
+                symbol_table.add_symbol(name, Symbol::Variable(DataType::Bool));
+            }
+        }
+    }
+
+    let start = Instant::now();
+
+    for n in 0..LOOKUPS {
+        black_box(symbol_table.get_symbol_id(&names[n % NAMES]));
+    }
+
+    let elapsed = start.elapsed();
+
+    println!(
+        "get_symbol_id: {:.1} ns per lookup, {SCOPES} scopes and {NAMES} names",
+        elapsed.as_nanos() as f64 / LOOKUPS as f64
+    );
+}
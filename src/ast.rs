@@ -1,19 +1,174 @@
 use crate::{
-    parser::{Token, TokenKind},
+    parser::{Position, Token, TokenKind},
     symbol_table::SymbolTable,
-    types::DataType,
+    types::{DataType, InferredType},
     CompilerResult,
 };
-use std::cmp::Eq;
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct VariableDeclaration<'src> {
     pub name: &'src str,
     pub data_type: DataType<'src>,
     pub value: Option<Box<Ast<'src>>>,
+    // From `@align(n)` in front of a `let`
+    pub alignment: Option<usize>,
 }
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+// Runs the body when the value that is matched on equals the pattern
+#[derive(PartialEq, Debug, Clone)]
+pub struct MatchArm<'src> {
+    pub pattern: Ast<'src>,
+    pub body: Ast<'src>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Intrinsic {
+    Breakpoint,
+    Trap,
+    SizeOfVal,
+    // The number of elements of an array, known at compile time like its size
+    CountOf,
+    AlignUp,
+    AlignDown,
+    IsAligned,
+    Alloc,
+    Free,
+    Memcpy,
+    Null,
+    IsNull,
+    Assume,
+    WrappingNeg,
+    // A byte as the character it encodes and back, both are a single byte
+    ToChar,
+    ToByte,
+    Offset,
+    ByteAt,
+    SetByteAt,
+    // Replaced by a string literal of where it is called while parsing
+    SourceLocation,
+    // Reads a cycle counter, the time stamp counter on x86. Every backend has one, C falls back
+    // to processor time and the interpreter counts nanoseconds
+    Rdtsc,
+    // `print` and `println` with any number of values, which `write` the text of each of them.
+    // Recognized by name while parsing
+    Print,
+    PrintLine,
+    // `len` of a string, which counts the bytes before the null terminator.
+    // Also recognized by name, and a literal's length is known without counting
+    Length,
+}
+
+impl Intrinsic {
+    // Whether calling it does anything besides producing a value
+    pub fn has_effects(&self) -> bool {
+        matches!(
+            self,
+            Self::Breakpoint
+                | Self::Trap
+                | Self::Alloc
+                | Self::Free
+                | Self::Memcpy
+                | Self::SetByteAt
+                | Self::Rdtsc
+                | Self::Print
+                | Self::PrintLine
+        )
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "breakpoint" => Some(Self::Breakpoint),
+            "trap" => Some(Self::Trap),
+            "size_of_val" => Some(Self::SizeOfVal),
+            "countof" => Some(Self::CountOf),
+            "align_up" => Some(Self::AlignUp),
+            "align_down" => Some(Self::AlignDown),
+            "is_aligned" => Some(Self::IsAligned),
+            "alloc" => Some(Self::Alloc),
+            "free" => Some(Self::Free),
+            "memcpy" => Some(Self::Memcpy),
+            "null" => Some(Self::Null),
+            "is_null" => Some(Self::IsNull),
+            "assume" => Some(Self::Assume),
+            "wrapping_neg" => Some(Self::WrappingNeg),
+            "to_char" => Some(Self::ToChar),
+            "to_byte" => Some(Self::ToByte),
+            "source_location" => Some(Self::SourceLocation),
+            "offset" => Some(Self::Offset),
+            "byte_at" => Some(Self::ByteAt),
+            "set_byte_at" => Some(Self::SetByteAt),
+            "rdtsc" => Some(Self::Rdtsc),
+            _ => None,
+        }
+    }
+
+    // What it's called in the source, without the `@` of the ones that have one
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Breakpoint => "breakpoint",
+            Self::Trap => "trap",
+            Self::SizeOfVal => "size_of_val",
+            Self::CountOf => "countof",
+            Self::AlignUp => "align_up",
+            Self::AlignDown => "align_down",
+            Self::IsAligned => "is_aligned",
+            Self::Alloc => "alloc",
+            Self::Free => "free",
+            Self::Memcpy => "memcpy",
+            Self::Null => "null",
+            Self::IsNull => "is_null",
+            Self::Assume => "assume",
+            Self::WrappingNeg => "wrapping_neg",
+            Self::ToChar => "to_char",
+            Self::ToByte => "to_byte",
+            Self::SourceLocation => "source_location",
+            Self::Offset => "offset",
+            Self::ByteAt => "byte_at",
+            Self::SetByteAt => "set_byte_at",
+            Self::Rdtsc => "rdtsc",
+            Self::Print => "print",
+            Self::PrintLine => "println",
+            Self::Length => "len",
+        }
+    }
+
+    // `print`, `println` and `len` are called like functions
+    pub fn is_named(&self) -> bool {
+        matches!(self, Self::Print | Self::PrintLine | Self::Length)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum InlineHint {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct FunctionAttributes {
+    pub inline: InlineHint,
+    // Rarely called, so it is kept away from the rest of the code
+    pub cold: bool,
+}
+
+impl FunctionAttributes {
+    pub fn is_attribute(name: &str) -> bool {
+        matches!(name, "inline" | "noinline" | "cold")
+    }
+
+    pub fn add(&mut self, name: &str) {
+        match name {
+            "inline" => self.inline = InlineHint::Always,
+            "noinline" => self.inline = InlineHint::Never,
+            "cold" => self.cold = true,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum AstKind<'src> {
     Node {
         token: Token<'src>,
@@ -31,6 +186,13 @@ pub enum AstKind<'src> {
         lhs: Box<Ast<'src>>,
         index: Box<Ast<'src>>,
     },
+    ArrayLiteral {
+        elements: Vec<Ast<'src>>,
+    },
+    // `"x = {x}"`, string literals for the text between the values they format
+    Interpolation {
+        pieces: Vec<Ast<'src>>,
+    },
     Assign {
         lhs: Box<Ast<'src>>,
         rhs: Box<Ast<'src>>,
@@ -42,10 +204,14 @@ pub enum AstKind<'src> {
     Block {
         scope_id: usize,
         statements: Vec<Ast<'src>>,
+        // Where each statement starts, so the generated code can be commented with its source
+        positions: Vec<Position<'src>>,
     },
     VariableDeclaration(VariableDeclaration<'src>),
     FunctionDeclaration {
         name: &'src str,
+        attributes: FunctionAttributes,
+        type_arguments: Vec<DataType<'src>>,
         scope_id: usize,
         return_type: DataType<'src>,
         arguments: Vec<VariableDeclaration<'src>>,
@@ -55,6 +221,33 @@ pub enum AstKind<'src> {
         name: &'src str,
         fields: Vec<VariableDeclaration<'src>>,
     },
+    EnumDeclaration {
+        name: &'src str,
+    },
+    ConstantDeclaration {
+        name: &'src str,
+    },
+    StaticDeclaration {
+        name: &'src str,
+        data_type: DataType<'src>,
+        value: Option<u64>,
+    },
+    // `name::variant`
+    EnumVariant {
+        name: &'src str,
+        variant: &'src str,
+    },
+    GenericFunctionDeclaration {
+        name: &'src str,
+    },
+    ExternDeclaration {
+        name: &'src str,
+    },
+    // A generic function with its type parameters substituted
+    Instance {
+        name: &'src str,
+        type_arguments: Vec<DataType<'src>>,
+    },
     Call {
         lhs: Box<Ast<'src>>,
         arguments: Vec<Ast<'src>>,
@@ -68,9 +261,66 @@ pub enum AstKind<'src> {
         condition: Box<Ast<'src>>,
         body: Box<Ast<'src>>,
     },
+    // The arms are compared in order, `default` runs when none of them are equal
+    Match {
+        scrutinee: Box<Ast<'src>>,
+        arms: Vec<MatchArm<'src>>,
+        default: Box<Ast<'src>>,
+    },
+    Intrinsic {
+        intrinsic: Intrinsic,
+        arguments: Vec<Ast<'src>>,
+    },
+    Return {
+        value: Option<Box<Ast<'src>>>,
+        return_type: DataType<'src>,
+    },
+    // A statement that didn't parse, kept so that the block around it still checks
+    Error,
 }
 
 impl<'src> AstKind<'src> {
+    // The children whose results are used, unlike statements, bodies and branches,
+    // which are allowed to be `Void`
+    pub fn values(&self) -> Vec<&Ast<'src>> {
+        match self {
+            Self::Prefix { node, .. } => vec![node],
+            Self::GetField { lhs, .. } => vec![lhs],
+            Self::Infix { lhs, rhs, .. } => vec![lhs, rhs],
+            Self::Index { lhs, index } => vec![lhs, index],
+            Self::Assign { rhs, .. } => vec![rhs],
+            Self::ArrayLiteral { elements } | Self::Interpolation { pieces: elements } => {
+                elements.iter().collect()
+            }
+            Self::VariableDeclaration(VariableDeclaration { value, .. })
+            | Self::Return { value, .. } => value.iter().map(Box::as_ref).collect(),
+            Self::Call { lhs, arguments } => {
+                std::iter::once(lhs.as_ref()).chain(arguments).collect()
+            }
+            Self::IfStatement { condition, .. } | Self::WhileLoop { condition, .. } => {
+                vec![condition]
+            }
+            Self::Match {
+                scrutinee, arms, ..
+            } => std::iter::once(scrutinee.as_ref())
+                .chain(arms.iter().map(|arm| &arm.pattern))
+                .collect(),
+            Self::Intrinsic { arguments, .. } => arguments.iter().collect(),
+            Self::Node { .. }
+            | Self::Block { .. }
+            | Self::FunctionDeclaration { .. }
+            | Self::StructDeclaration { .. }
+            | Self::EnumDeclaration { .. }
+            | Self::ConstantDeclaration { .. }
+            | Self::StaticDeclaration { .. }
+            | Self::EnumVariant { .. }
+            | Self::GenericFunctionDeclaration { .. }
+            | Self::ExternDeclaration { .. }
+            | Self::Instance { .. }
+            | Self::Error => Vec::new(),
+        }
+    }
+
     pub fn assignable(&self) -> bool {
         matches!(
             self,
@@ -91,7 +341,7 @@ impl<'src> AstKind<'src> {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Ast<'src> {
     pub data_type: DataType<'src>,
     pub kind: AstKind<'src>,
@@ -106,4 +356,223 @@ impl<'src> Ast<'src> {
 
         Ok(Self { kind, data_type })
     }
+
+    // The value of an expression made of nothing but literals, worked out at compile time.
+    // Booleans are 0 or 1, and integers are wrapped to their type at every step
+    pub fn fold(&self) -> Option<i128> {
+        let value = match &self.kind {
+            AstKind::Node { token } => match token.kind {
+                TokenKind::Number(value, _) => value,
+                TokenKind::Char(value) => value.into(),
+                TokenKind::True => 1,
+                TokenKind::False => 0,
+                _ => return None,
+            },
+            AstKind::Prefix { oper, node } => {
+                let value = node.fold()?;
+
+                match oper.kind {
+                    TokenKind::Sub => -value,
+                    TokenKind::BitNot => !value,
+                    TokenKind::Not => i128::from(value == 0),
+                    _ => return None,
+                }
+            }
+            AstKind::Infix { oper, lhs, rhs } => {
+                let (lhs, rhs) = (lhs.fold()?, rhs.fold()?);
+
+                match oper.kind {
+                    TokenKind::Add => lhs + rhs,
+                    TokenKind::Sub => lhs - rhs,
+                    TokenKind::Mul => lhs.checked_mul(rhs)?,
+                    TokenKind::Div => lhs.checked_div(rhs)?,
+                    TokenKind::Mod => lhs.checked_rem(rhs)?,
+                    TokenKind::BitAnd => lhs & rhs,
+                    TokenKind::BitOr => lhs | rhs,
+                    TokenKind::BitXor => lhs ^ rhs,
+                    TokenKind::And => i128::from(lhs != 0 && rhs != 0),
+                    TokenKind::Or => i128::from(lhs != 0 || rhs != 0),
+                    TokenKind::Equals => i128::from(lhs == rhs),
+                    TokenKind::NotEquals => i128::from(lhs != rhs),
+                    TokenKind::Greater => i128::from(lhs > rhs),
+                    TokenKind::Less => i128::from(lhs < rhs),
+                    TokenKind::GreaterOrEqual => i128::from(lhs >= rhs),
+                    TokenKind::LessOrEqual => i128::from(lhs <= rhs),
+                    _ => return None,
+                }
+            }
+            AstKind::Intrinsic {
+                intrinsic: Intrinsic::Length,
+                arguments,
+            } => arguments[0].string_literal_length()? as i128,
+            AstKind::Intrinsic {
+                intrinsic: Intrinsic::CountOf,
+                arguments,
+            } => match arguments[0].data_type {
+                DataType::Array { length, .. } => length as i128,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        // Integer literals that aren't typed yet can't be wrapped
+        match self.data_type {
+            DataType::Int(int_type) => Some(int_type.wrap(value)),
+            DataType::Bool | DataType::Char | DataType::Inferred(InferredType::Int) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn string_literal(&self) -> Option<&[u8]> {
+        match self.kind {
+            AstKind::Node {
+                token:
+                    Token {
+                        kind: TokenKind::Str(ref text),
+                        ..
+                    },
+            } => Some(text),
+            _ => None,
+        }
+    }
+
+    // Only the bytes before the first null are counted, like `len` does at runtime
+    pub fn string_literal_length(&self) -> Option<usize> {
+        let text = self.string_literal()?;
+
+        let end = text.iter().position(|&byte| byte == 0);
+
+        Some(end.unwrap_or(text.len()))
+    }
+
+    pub fn children(&self) -> Vec<&Self> {
+        match &self.kind {
+            AstKind::Node { .. }
+            | AstKind::StructDeclaration { .. }
+            | AstKind::EnumDeclaration { .. }
+            | AstKind::ConstantDeclaration { .. }
+            | AstKind::StaticDeclaration { .. }
+            | AstKind::EnumVariant { .. }
+            | AstKind::GenericFunctionDeclaration { .. }
+            | AstKind::ExternDeclaration { .. }
+            | AstKind::Instance { .. }
+            | AstKind::Error => Vec::new(),
+            AstKind::Prefix { node, .. } => vec![node],
+            AstKind::GetField { lhs, .. } => vec![lhs],
+            AstKind::Infix { lhs, rhs, .. } | AstKind::Assign { lhs, rhs } => vec![lhs, rhs],
+            AstKind::Index { lhs, index } => vec![lhs, index],
+            AstKind::ArrayLiteral { elements } | AstKind::Interpolation { pieces: elements } => {
+                elements.iter().collect()
+            }
+            AstKind::Block { statements, .. } => statements.iter().collect(),
+            AstKind::VariableDeclaration(VariableDeclaration { value, .. }) => {
+                value.iter().map(Box::as_ref).collect()
+            }
+            AstKind::FunctionDeclaration { body, .. } => vec![body],
+            AstKind::Call { lhs, arguments } => {
+                std::iter::once(lhs.as_ref()).chain(arguments).collect()
+            }
+            AstKind::IfStatement {
+                condition,
+                if_block,
+                else_block,
+            } => [condition, if_block]
+                .into_iter()
+                .chain(else_block)
+                .map(Box::as_ref)
+                .collect(),
+            AstKind::WhileLoop { condition, body } => vec![condition, body],
+            AstKind::Match {
+                scrutinee,
+                arms,
+                default,
+            } => std::iter::once(scrutinee.as_ref())
+                .chain(arms.iter().flat_map(|arm| [&arm.pattern, &arm.body]))
+                .chain(std::iter::once(default.as_ref()))
+                .collect(),
+            AstKind::Intrinsic { arguments, .. } => arguments.iter().collect(),
+            AstKind::Return { value, .. } => value.iter().map(Box::as_ref).collect(),
+        }
+    }
+
+    // Calls and stores anywhere inside of it, declarations don't count as they can't be observed
+    pub fn has_effects(&self) -> bool {
+        match self.kind {
+            AstKind::Call { .. } | AstKind::Assign { .. } | AstKind::Return { .. } => true,
+            // It allocates the string it builds
+            AstKind::Interpolation { .. } => true,
+            AstKind::Intrinsic { intrinsic, .. } if intrinsic.has_effects() => true,
+            // Its body only runs when it is called
+            AstKind::FunctionDeclaration { .. } => false,
+            _ => self.children().into_iter().any(Self::has_effects),
+        }
+    }
+
+    // Whether it's built on a name whose declaration didn't parse, which could stand for anything
+    pub fn is_recovered(&self) -> bool {
+        self.data_type == DataType::Inferred(InferredType::Any)
+            && match &self.kind {
+                AstKind::Node { token } => token.kind == TokenKind::Ident,
+                kind => kind.values().into_iter().any(Self::is_recovered),
+            }
+    }
+
+    // Whether this calls the function `name` directly anywhere inside of it
+    pub fn calls(&self, name: &str) -> bool {
+        if let AstKind::Call { lhs, .. } = &self.kind {
+            match lhs.kind {
+                AstKind::Node {
+                    token:
+                        Token {
+                            kind: TokenKind::Ident,
+                            text,
+                            ..
+                        },
+                }
+                | AstKind::Instance { name: text, .. }
+                    if text == name =>
+                {
+                    return true;
+                }
+                _ => {}
+            }
+        }
+
+        self.children().into_iter().any(|child| child.calls(name))
+    }
+
+    // Whether the variable `name` is assigned anywhere inside of it, or has its address taken so
+    // it could be written through that
+    pub fn may_change(&self, name: &str) -> bool {
+        let target = match &self.kind {
+            AstKind::Assign { lhs, .. } => Some(&lhs.kind),
+            AstKind::Prefix {
+                oper:
+                    Token {
+                        kind: TokenKind::Hash,
+                        ..
+                    },
+                node,
+            } => Some(&node.kind),
+            _ => None,
+        };
+
+        if let Some(AstKind::Node {
+            token:
+                Token {
+                    kind: TokenKind::Ident,
+                    text,
+                    ..
+                },
+        }) = target
+        {
+            if *text == name {
+                return true;
+            }
+        }
+
+        self.children()
+            .into_iter()
+            .any(|child| child.may_change(name))
+    }
 }
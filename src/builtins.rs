@@ -0,0 +1,146 @@
+use crate::{
+    c::{FREE_CODE_C, MALLOC_CODE_C, PRINT_INTEGER_CODE_C, READ_CODE_C, WRITE_CODE_C},
+    llvm::{
+        FREE_CODE_LLVM, MALLOC_CODE_LLVM, PRINT_INTEGER_CODE_LLVM, READ_CODE_LLVM, WRITE_CODE_LLVM,
+    },
+    nasm::{
+        FREE_CODE, FREE_CODE_GAS, MALLOC_CODE, MALLOC_CODE_GAS, PRINT_INTEGER_CODE,
+        PRINT_INTEGER_CODE_GAS, READ_CODE, READ_CODE_GAS, WRITE_CODE, WRITE_CODE_GAS,
+    },
+    types::{DataType, IntType},
+};
+use std::fmt;
+
+// A function provided by the runtime instead of the program
+#[derive(Clone)]
+pub struct Builtin<'src> {
+    pub name: &'src str,
+    pub data_type: DataType<'src>,
+    // Assembly of the whole function, starting with its label, for each syntax
+    pub nasm: &'static str,
+    pub gas: &'static str,
+    // The same function in C, for the C backend
+    pub c: &'static str,
+    // And in LLVM IR, for the LLVM backend
+    pub llvm: &'static str,
+}
+
+impl<'src> fmt::Debug for Builtin<'src> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.name, self.data_type)
+    }
+}
+
+// A function declared with `extern fn`, resolved by the linker under the same name
+#[derive(Debug, Clone)]
+pub struct Extern<'src> {
+    pub name: &'src str,
+    pub data_type: DataType<'src>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Builtins<'src> {
+    pub functions: Vec<Builtin<'src>>,
+}
+
+impl<'src> Builtins<'src> {
+    // No builtins at all, for when the runtime is provided some other way
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, builtin: Builtin<'src>) {
+        self.functions.push(builtin);
+    }
+
+    // Everything a program running on linux gets by default
+    pub fn hosted() -> Self {
+        let string = || DataType::Ref(Box::new(DataType::Int(IntType::U8)));
+
+        let mut builtins = Self::new();
+
+        builtins.add(Builtin {
+            name: "malloc",
+            data_type: DataType::Function {
+                return_type: Box::new(string()),
+                argument_types: vec![DataType::Int(IntType::U64)],
+            },
+            nasm: MALLOC_CODE,
+            gas: MALLOC_CODE_GAS,
+            c: MALLOC_CODE_C,
+            llvm: MALLOC_CODE_LLVM,
+        });
+
+        builtins.add(Builtin {
+            name: "free",
+            data_type: DataType::Function {
+                return_type: Box::new(DataType::Void),
+                argument_types: vec![string(), DataType::Int(IntType::U64)],
+            },
+            nasm: FREE_CODE,
+            gas: FREE_CODE_GAS,
+            c: FREE_CODE_C,
+            llvm: FREE_CODE_LLVM,
+        });
+
+        builtins.add(Builtin {
+            name: "read",
+            data_type: DataType::Function {
+                return_type: Box::new(DataType::Void),
+                argument_types: vec![string(), DataType::Int(IntType::U64)],
+            },
+            nasm: READ_CODE,
+            gas: READ_CODE_GAS,
+            c: READ_CODE_C,
+            llvm: READ_CODE_LLVM,
+        });
+
+        builtins.add(Builtin {
+            name: "write",
+            data_type: DataType::Function {
+                return_type: Box::new(DataType::Void),
+                argument_types: vec![string(), DataType::Int(IntType::U64)],
+            },
+            nasm: WRITE_CODE,
+            gas: WRITE_CODE_GAS,
+            c: WRITE_CODE_C,
+            llvm: WRITE_CODE_LLVM,
+        });
+
+        // What `print` of an integer calls, after widening it to 64 bits
+        builtins.add(Builtin {
+            name: "print_integer",
+            data_type: DataType::Function {
+                return_type: Box::new(DataType::Void),
+                argument_types: vec![DataType::Int(IntType::U64), DataType::Bool],
+            },
+            nasm: PRINT_INTEGER_CODE,
+            gas: PRINT_INTEGER_CODE_GAS,
+            c: PRINT_INTEGER_CODE_C,
+            llvm: PRINT_INTEGER_CODE_LLVM,
+        });
+
+        builtins
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, symbol_table::SymbolTable};
+
+    #[test]
+    fn printing_needs_write() {
+        let mut builtins = Builtins::hosted();
+
+        builtins.functions.retain(|builtin| builtin.name != "write");
+
+        let mut symbol_table = SymbolTable::with_builtins(builtins);
+
+        let source = "{\n    print(\"hi\");\n    0;\n}";
+
+        let errors = Parser::parse(source, &mut symbol_table).unwrap_err();
+
+        assert_eq!(errors[0].to_string(), "variable `write` was not defined");
+    }
+}
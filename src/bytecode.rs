@@ -1,5 +1,13 @@
-use crate::types::DataType;
-use std::fmt;
+use crate::{
+    ast::InlineHint,
+    builtins::{Builtin, Extern},
+    types::{DataType, FloatType},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Write},
+    mem,
+};
 
 pub type RegisterID = usize;
 pub type ArgumentID = usize;
@@ -26,6 +34,17 @@ pub enum Argument<'src> {
     VoidRegister,
 }
 
+impl Argument<'_> {
+    // The register holding the data, or the pointer to it
+    pub fn register(&self) -> Option<RegisterID> {
+        match self {
+            Self::Register(register_id) => Some(*register_id),
+            Self::Deref(data) | Self::StructField { data, .. } => data.register(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum OpCode<'src> {
     Mov {
@@ -52,9 +71,24 @@ pub enum OpCode<'src> {
         dst: Argument<'src>,
         src: Argument<'src>,
     },
+    And {
+        dst: Argument<'src>,
+        src: Argument<'src>,
+    },
+    Or {
+        dst: Argument<'src>,
+        src: Argument<'src>,
+    },
+    Xor {
+        dst: Argument<'src>,
+        src: Argument<'src>,
+    },
     Not {
         dst: Argument<'src>,
     },
+    BitNot {
+        dst: Argument<'src>,
+    },
     Ref {
         dst: Argument<'src>,
         src: Argument<'src>,
@@ -74,6 +108,11 @@ pub enum OpCode<'src> {
         src: Argument<'src>,
         offset: usize,
     },
+    Copy {
+        dst: Argument<'src>,
+        src: Argument<'src>,
+        size: Argument<'src>,
+    },
     SetIfEqual {
         dst: Argument<'src>,
         lhs: Argument<'src>,
@@ -104,6 +143,7 @@ pub enum OpCode<'src> {
         lhs: Argument<'src>,
         rhs: Argument<'src>,
     },
+    // Two's complement, so the smallest signed value stays the same
     Negate {
         dst: Argument<'src>,
     },
@@ -126,23 +166,299 @@ pub enum OpCode<'src> {
         lhs: Argument<'src>,
         arguments: Vec<Argument<'src>>,
     },
+    // Jumps to the single epilogue of the function, no matter how deep in nested blocks it is.
+    // The return value has to be set before
+    Return,
+    Breakpoint,
+    Trap,
+    // `rdtsc` in assembly, `@llvm.readcyclecounter` in LLVM IR, and the closest C and the
+    // interpreter have elsewhere
+    ReadCycleCounter {
+        dst: Argument<'src>,
+    },
+    // Where the code of a statement starts, nothing is generated for it but a comment
+    SourceLine {
+        line: usize,
+        text: &'src str,
+    },
+}
+
+impl<'src> OpCode<'src> {
+    pub fn arguments(&self) -> Vec<&Argument<'src>> {
+        match self {
+            Self::Mov { dst, src }
+            | Self::Add { dst, src }
+            | Self::Sub { dst, src }
+            | Self::Mul { dst, src }
+            | Self::Div { dst, src }
+            | Self::Mod { dst, src }
+            | Self::And { dst, src }
+            | Self::Or { dst, src }
+            | Self::Xor { dst, src }
+            | Self::Ref { dst, src }
+            | Self::SetField { dst, src, .. } => vec![dst, src],
+            Self::Index { dst, src, index } | Self::SetIndex { dst, src, index } => {
+                vec![dst, src, index]
+            }
+            Self::Copy { dst, src, size } => vec![dst, src, size],
+            Self::SetIfEqual { dst, lhs, rhs }
+            | Self::SetIfNotEqual { dst, lhs, rhs }
+            | Self::SetIfGreater { dst, lhs, rhs }
+            | Self::SetIfLess { dst, lhs, rhs }
+            | Self::SetIfGreaterOrEqual { dst, lhs, rhs }
+            | Self::SetIfLessOrEqual { dst, lhs, rhs } => vec![dst, lhs, rhs],
+            Self::Not { dst }
+            | Self::BitNot { dst }
+            | Self::Negate { dst }
+            | Self::ReadCycleCounter { dst } => vec![dst],
+            Self::GotoIfZero { condition, .. } | Self::GotoIfNotZero { condition, .. } => {
+                vec![condition]
+            }
+            Self::Call {
+                dst,
+                lhs,
+                arguments,
+            } => [dst, lhs].into_iter().chain(arguments).collect(),
+            Self::Label { .. }
+            | Self::Goto { .. }
+            | Self::Return
+            | Self::Breakpoint
+            | Self::Trap
+            | Self::SourceLine { .. } => Vec::new(),
+        }
+    }
+
+    pub fn arguments_mut(&mut self) -> Vec<&mut Argument<'src>> {
+        match self {
+            Self::Mov { dst, src }
+            | Self::Add { dst, src }
+            | Self::Sub { dst, src }
+            | Self::Mul { dst, src }
+            | Self::Div { dst, src }
+            | Self::Mod { dst, src }
+            | Self::And { dst, src }
+            | Self::Or { dst, src }
+            | Self::Xor { dst, src }
+            | Self::Ref { dst, src }
+            | Self::SetField { dst, src, .. } => vec![dst, src],
+            Self::Index { dst, src, index } | Self::SetIndex { dst, src, index } => {
+                vec![dst, src, index]
+            }
+            Self::Copy { dst, src, size } => vec![dst, src, size],
+            Self::SetIfEqual { dst, lhs, rhs }
+            | Self::SetIfNotEqual { dst, lhs, rhs }
+            | Self::SetIfGreater { dst, lhs, rhs }
+            | Self::SetIfLess { dst, lhs, rhs }
+            | Self::SetIfGreaterOrEqual { dst, lhs, rhs }
+            | Self::SetIfLessOrEqual { dst, lhs, rhs } => vec![dst, lhs, rhs],
+            Self::Not { dst }
+            | Self::BitNot { dst }
+            | Self::Negate { dst }
+            | Self::ReadCycleCounter { dst } => vec![dst],
+            Self::GotoIfZero { condition, .. } | Self::GotoIfNotZero { condition, .. } => {
+                vec![condition]
+            }
+            Self::Call {
+                dst,
+                lhs,
+                arguments,
+            } => [dst, lhs].into_iter().chain(arguments).collect(),
+            Self::Label { .. }
+            | Self::Goto { .. }
+            | Self::Return
+            | Self::Breakpoint
+            | Self::Trap
+            | Self::SourceLine { .. } => Vec::new(),
+        }
+    }
+
+    pub fn jump_target(&self) -> Option<LabelID> {
+        match self {
+            Self::Goto { label_id }
+            | Self::GotoIfZero { label_id, .. }
+            | Self::GotoIfNotZero { label_id, .. } => Some(*label_id),
+            _ => None,
+        }
+    }
+
+    // Rough relative weight of an opcode, used by passes that need to estimate code size or speed.
+    // Keep all of the tuning numbers in here.
+    pub fn cost(&self) -> usize {
+        match self {
+            Self::Label { .. } | Self::SourceLine { .. } => 0,
+            Self::Mov { .. }
+            | Self::Goto { .. }
+            | Self::Return
+            | Self::Breakpoint
+            | Self::Trap => 1,
+            Self::Add { .. }
+            | Self::Sub { .. }
+            | Self::And { .. }
+            | Self::Or { .. }
+            | Self::Xor { .. }
+            | Self::Not { .. }
+            | Self::BitNot { .. }
+            | Self::Negate { .. }
+            | Self::Ref { .. }
+            | Self::ReadCycleCounter { .. } => 2,
+            Self::Index { .. }
+            | Self::SetIndex { .. }
+            | Self::SetField { .. }
+            | Self::GotoIfZero { .. }
+            | Self::GotoIfNotZero { .. }
+            | Self::SetIfEqual { .. }
+            | Self::SetIfNotEqual { .. }
+            | Self::SetIfGreater { .. }
+            | Self::SetIfLess { .. }
+            | Self::SetIfGreaterOrEqual { .. }
+            | Self::SetIfLessOrEqual { .. } => 3,
+            Self::Mul { .. } => 4,
+            Self::Copy { .. } => 10,
+            Self::Call { arguments, .. } => 8 + 2 * arguments.len(),
+            Self::Div { .. } | Self::Mod { .. } => 20,
+        }
+    }
+
+    // Functions costing at most this much are inlined without being asked to
+    pub const INLINE_THRESHOLD: usize = 16;
+
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Mov { .. } => "mov",
+            Self::Add { .. } => "add",
+            Self::Sub { .. } => "sub",
+            Self::Mul { .. } => "mul",
+            Self::Div { .. } => "div",
+            Self::Mod { .. } => "mod",
+            Self::And { .. } => "and",
+            Self::Or { .. } => "or",
+            Self::Xor { .. } => "xor",
+            Self::Not { .. } => "not",
+            Self::BitNot { .. } => "bit_not",
+            Self::Ref { .. } => "ref",
+            Self::Index { .. } => "index",
+            Self::SetIndex { .. } => "set_index",
+            Self::SetField { .. } => "set_field",
+            Self::Copy { .. } => "copy",
+            Self::SetIfEqual { .. } => "set_if_equal",
+            Self::SetIfNotEqual { .. } => "set_if_not_equal",
+            Self::SetIfGreater { .. } => "set_if_greater",
+            Self::SetIfLess { .. } => "set_if_less",
+            Self::SetIfGreaterOrEqual { .. } => "set_if_greater_or_equal",
+            Self::SetIfLessOrEqual { .. } => "set_if_less_or_equal",
+            Self::Negate { .. } => "negate",
+            Self::Label { .. } => "label",
+            Self::Goto { .. } => "goto",
+            Self::GotoIfZero { .. } => "goto_if_zero",
+            Self::GotoIfNotZero { .. } => "goto_if_not_zero",
+            Self::Call { .. } => "call",
+            Self::Return => "return",
+            Self::Breakpoint => "breakpoint",
+            Self::Trap => "trap",
+            Self::ReadCycleCounter { .. } => "read_cycle_counter",
+            Self::SourceLine { .. } => "source_line",
+        }
+    }
+}
+
+// Registers are `r`, arguments `a`, and constants are suffixed with their type
+impl fmt::Display for Argument<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ReturnValue => write!(f, "ret"),
+            Self::Register(register_id) => write!(f, "r{register_id}"),
+            Self::Argument(argument_id) => write!(f, "a{argument_id}"),
+            Self::Deref(data) => write!(f, "[{data}]"),
+            Self::StructField { data, name } => write!(f, "{data}.{name}"),
+            Self::Constant {
+                value,
+                data_type: DataType::Int(int_type),
+            } if int_type.is_signed() => {
+                let shift = 64 - int_type.size() * 8;
+
+                write!(f, "{}:{int_type:?}", ((value << shift) as i64) >> shift)
+            }
+            Self::Constant {
+                value,
+                data_type: DataType::Float(FloatType::F32),
+            } => write!(f, "{:?}:F32", f32::from_bits(*value as u32)),
+            Self::Constant {
+                value,
+                data_type: DataType::Float(FloatType::F64),
+            } => write!(f, "{:?}:F64", f64::from_bits(*value)),
+            Self::Constant { value, data_type } => write!(f, "{value}:{data_type:?}"),
+            Self::Symbol { name, .. } => write!(f, "${name}"),
+            Self::VoidRegister => write!(f, "_"),
+        }
+    }
+}
+
+// One line of pseudo-assembly, destination first
+impl fmt::Display for OpCode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Label { label_id } => write!(f, "L{label_id}:"),
+            Self::SourceLine { line, text } => write!(f, "line {line}: {text}"),
+            Self::Goto { label_id } => write!(f, "goto L{label_id}"),
+            Self::GotoIfZero {
+                condition,
+                label_id,
+            } => write!(f, "goto_if_zero {condition}, L{label_id}"),
+            Self::GotoIfNotZero {
+                condition,
+                label_id,
+            } => write!(f, "goto_if_not_zero {condition}, L{label_id}"),
+            Self::SetField { dst, src, offset } => write!(f, "set_field {dst} + {offset}, {src}"),
+            // Arguments are stored last to first
+            Self::Call {
+                dst,
+                lhs,
+                arguments,
+            } => {
+                write!(f, "call {dst}, {lhs}(")?;
+
+                for (n, argument) in arguments.iter().rev().enumerate() {
+                    if n > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{argument}")?;
+                }
+
+                write!(f, ")")
+            }
+            _ => {
+                write!(f, "{}", self.mnemonic())?;
+
+                for (n, argument) in self.arguments().into_iter().enumerate() {
+                    write!(f, "{}{argument}", if n == 0 { " " } else { ", " })?;
+                }
+
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Function<'src> {
-    pub name: &'src str,
+    pub name: String,
+    pub inline: InlineHint,
+    pub cold: bool,
     pub labels: LabelID,
     pub return_type: DataType<'src>,
     pub argument_types: Vec<DataType<'src>>,
     pub arguments_size: usize,
     pub register_types: Vec<DataType<'src>>,
+    // Registers that have to be aligned to more than the 8 bytes every one of them is
+    pub register_alignments: HashMap<RegisterID, usize>,
     pub registers_size: usize,
     pub opcodes: Vec<OpCode<'src>>,
 }
 
 impl<'src> Function<'src> {
     pub fn new(
-        name: &'src str,
+        name: String,
         return_type: DataType<'src>,
         argument_types: Vec<DataType<'src>>,
     ) -> Self {
@@ -153,16 +469,291 @@ impl<'src> Function<'src> {
 
         Self {
             name,
+            inline: InlineHint::Auto,
+            cold: false,
             labels: 0,
             return_type,
             argument_types,
             arguments_size,
             register_types: Vec::new(),
+            register_alignments: HashMap::new(),
             registers_size: 0,
             opcodes: Vec::new(),
         }
     }
 
+    pub fn estimated_size(&self) -> usize {
+        self.opcodes.iter().map(OpCode::cost).sum()
+    }
+
+    // Cold functions are left alone, so they don't take up space where they are called
+    fn should_inline(&self) -> bool {
+        let calls_itself = self.opcodes.iter().any(|opcode| {
+            matches!(opcode, OpCode::Call { lhs: Argument::Symbol { name, .. }, .. } if *name == self.name)
+        });
+
+        let wanted = match self.inline {
+            InlineHint::Always => true,
+            InlineHint::Never => false,
+            InlineHint::Auto => !self.cold && self.estimated_size() <= OpCode::INLINE_THRESHOLD,
+        };
+
+        wanted && !calls_itself
+    }
+
+    // The body of `callee` in place of a call to it. Its registers, arguments, return value
+    // and labels become new ones of this function, and returning jumps past the end of the body
+    fn inline_call(
+        &mut self,
+        callee: &Function<'src>,
+        dst: Argument<'src>,
+        arguments: Vec<Argument<'src>>,
+    ) {
+        let registers: Vec<RegisterID> = callee
+            .register_types
+            .iter()
+            .enumerate()
+            .map(|(register_id, data_type)| {
+                self.add_aligned_register(data_type.clone(), callee.register_alignment(register_id))
+            })
+            .collect();
+
+        let argument_registers: Vec<RegisterID> = callee
+            .argument_types
+            .iter()
+            .map(|data_type| self.add_register(data_type.clone()))
+            .collect();
+
+        let return_register = (callee.return_type != DataType::Void)
+            .then(|| self.add_register(callee.return_type.clone()));
+
+        let labels: Vec<LabelID> = (0..callee.labels).map(|_| self.add_label()).collect();
+        let end = self.add_label();
+
+        // Stored last to first
+        for (register_id, argument) in argument_registers.iter().zip(arguments.into_iter().rev()) {
+            self.add_opcode(OpCode::Mov {
+                dst: Argument::Register(*register_id),
+                src: argument,
+            });
+        }
+
+        fn rename<'src>(
+            argument: &mut Argument<'src>,
+            registers: &[RegisterID],
+            argument_registers: &[RegisterID],
+            return_register: Option<RegisterID>,
+        ) {
+            match argument {
+                Argument::ReturnValue => {
+                    *argument = Argument::Register(
+                        return_register.expect("Only functions returning something use it"),
+                    )
+                }
+                Argument::Register(register_id) => *register_id = registers[*register_id],
+                Argument::Argument(argument_id) => {
+                    *argument = Argument::Register(argument_registers[*argument_id])
+                }
+                Argument::Deref(data) | Argument::StructField { data, .. } => {
+                    rename(data, registers, argument_registers, return_register)
+                }
+                _ => {}
+            }
+        }
+
+        for opcode in &callee.opcodes {
+            let mut opcode = opcode.clone();
+
+            for argument in opcode.arguments_mut() {
+                rename(argument, &registers, &argument_registers, return_register);
+            }
+
+            if let OpCode::Label { label_id }
+            | OpCode::Goto { label_id }
+            | OpCode::GotoIfZero { label_id, .. }
+            | OpCode::GotoIfNotZero { label_id, .. } = &mut opcode
+            {
+                *label_id = labels[*label_id];
+            }
+
+            if let OpCode::Return = opcode {
+                opcode = OpCode::Goto { label_id: end };
+            }
+
+            self.add_opcode(opcode);
+        }
+
+        self.add_opcode(OpCode::Label { label_id: end });
+
+        if let (Some(return_register), false) = (return_register, dst == Argument::VoidRegister) {
+            self.add_opcode(OpCode::Mov {
+                dst,
+                src: Argument::Register(return_register),
+            });
+        }
+    }
+
+    // Drops what comes after a jump, return or trap, up to the next label that could be jumped to
+    pub fn remove_unreachable(&mut self) {
+        let mut reachable = true;
+
+        self.opcodes.retain(|opcode| {
+            if let OpCode::Label { .. } = opcode {
+                reachable = true;
+            }
+
+            let keep = reachable;
+
+            if let OpCode::Goto { .. } | OpCode::Return | OpCode::Trap = opcode {
+                reachable = false;
+            }
+
+            keep
+        });
+    }
+
+    // Small rewrites of neighbouring opcodes, repeated until none of them applies anymore
+    pub fn peephole(&mut self) {
+        while self.peephole_once() {}
+    }
+
+    // Drops moves into the same place, labels nothing jumps to, and moves into registers that
+    // are never read. A register moved into and straight back out of is skipped
+    fn peephole_once(&mut self) -> bool {
+        let mut uses = vec![0; self.register_types.len()];
+
+        for opcode in &self.opcodes {
+            for register_id in opcode.arguments().iter().filter_map(|argument| argument.register()) {
+                uses[register_id] += 1;
+            }
+        }
+
+        let jump_targets: HashSet<LabelID> = self
+            .opcodes
+            .iter()
+            .filter_map(OpCode::jump_target)
+            .collect();
+
+        let before = self.opcodes.len();
+        let mut opcodes: Vec<OpCode<'src>> = Vec::with_capacity(before);
+
+        for opcode in mem::take(&mut self.opcodes) {
+            match opcode {
+                OpCode::Mov { ref dst, ref src } if dst == src => continue,
+                OpCode::Label { label_id } if !jump_targets.contains(&label_id) => continue,
+                OpCode::Mov {
+                    dst: Argument::Register(register_id),
+                    ..
+                } if uses[register_id] == 1 => continue,
+                _ => {}
+            }
+
+            // Source lines in between don't keep the moves apart, so comments don't change the code
+            let previous = opcodes
+                .iter()
+                .rposition(|opcode| !matches!(opcode, OpCode::SourceLine { .. }));
+
+            if let (
+                Some((
+                    previous,
+                    OpCode::Mov {
+                        dst: Argument::Register(register_id),
+                        src,
+                    },
+                )),
+                OpCode::Mov {
+                    dst,
+                    src: Argument::Register(read_register_id),
+                },
+            ) = (previous.map(|n| (n, &opcodes[n])), &opcode)
+            {
+                let data_type = &self.register_types[*register_id];
+
+                if register_id == read_register_id
+                    && uses[*register_id] == 2
+                    && self.argument_data_type(src) == data_type
+                    && self.argument_data_type(dst) == data_type
+                {
+                    let forwarded = OpCode::Mov {
+                        dst: dst.clone(),
+                        src: src.clone(),
+                    };
+
+                    opcodes[previous] = forwarded;
+
+                    continue;
+                }
+            }
+
+            opcodes.push(opcode);
+        }
+
+        self.opcodes = opcodes;
+
+        self.opcodes.len() < before
+    }
+
+    // The first and last opcode each register is used by. A register used inside of a loop
+    // stays alive for all of it, as the next time around might still need it
+    pub fn live_ranges(&self) -> Vec<Option<(usize, usize)>> {
+        let mut live_ranges: Vec<Option<(usize, usize)>> = vec![None; self.register_types.len()];
+
+        for (n, opcode) in self.opcodes.iter().enumerate() {
+            for register_id in opcode.arguments().iter().filter_map(|argument| argument.register()) {
+                live_ranges[register_id] = Some(match live_ranges[register_id] {
+                    Some((start, _)) => (start, n),
+                    None => (n, n),
+                });
+            }
+        }
+
+        let labels: Vec<(LabelID, usize)> = self
+            .opcodes
+            .iter()
+            .enumerate()
+            .filter_map(|(n, opcode)| match opcode {
+                OpCode::Label { label_id } => Some((*label_id, n)),
+                _ => None,
+            })
+            .collect();
+
+        let loops: Vec<(usize, usize)> = self
+            .opcodes
+            .iter()
+            .enumerate()
+            .filter_map(|(n, opcode)| {
+                let label_id = opcode.jump_target()?;
+                let (_, label) = labels.iter().find(|(id, _)| *id == label_id)?;
+
+                (*label < n).then_some((*label, n))
+            })
+            .collect();
+
+        // Stretching over one loop can make a range overlap another one
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for live_range in live_ranges.iter_mut().flatten() {
+                for &(loop_start, loop_end) in &loops {
+                    let (start, end) = *live_range;
+
+                    if start <= loop_end && end >= loop_start {
+                        let stretched = (start.min(loop_start), end.max(loop_end));
+
+                        if stretched != *live_range {
+                            *live_range = stretched;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        live_ranges
+    }
+
     pub fn stack_size(&self) -> usize {
         self.arguments_size + self.registers_size
     }
@@ -175,6 +766,35 @@ impl<'src> Function<'src> {
         self.register_types.len() - 1
     }
 
+    // The frame is only 8 byte aligned, so the register gets enough room to be moved up to an
+    // address that is aligned, which is where it is used from
+    pub fn add_aligned_register(
+        &mut self,
+        data_type: DataType<'src>,
+        alignment: usize,
+    ) -> RegisterID {
+        let register_id = self.add_register(data_type);
+
+        if alignment > 8 {
+            self.register_alignments.insert(register_id, alignment);
+            self.registers_size += alignment - 8;
+        }
+
+        register_id
+    }
+
+    pub fn register_alignment(&self, register_id: RegisterID) -> usize {
+        self.register_alignments
+            .get(&register_id)
+            .copied()
+            .unwrap_or(8)
+    }
+
+    // Including the room to align it
+    pub fn register_size(&self, register_id: RegisterID) -> usize {
+        self.register_types[register_id].size_aligned() + self.register_alignment(register_id) - 8
+    }
+
     pub fn add_label(&mut self) -> LabelID {
         let prev_labels = self.labels;
 
@@ -188,10 +808,8 @@ impl<'src> Function<'src> {
     }
 
     pub fn register_position(&self, register_id: RegisterID) -> usize {
-        self.register_types
-            .iter()
-            .take(register_id)
-            .map(|data_type| data_type.size_aligned())
+        (0..register_id)
+            .map(|register_id| self.register_size(register_id))
             .sum()
     }
 
@@ -203,7 +821,7 @@ impl<'src> Function<'src> {
             .sum::<usize>()
     }
 
-    pub fn argument_data_type(&self, argument: &'src Argument<'src>) -> &DataType<'src> {
+    pub fn argument_data_type<'a>(&'a self, argument: &'a Argument<'src>) -> &'a DataType<'src> {
         match argument {
             Argument::ReturnValue => &self.return_type,
             Argument::Register(register_id) => &self.register_types[*register_id],
@@ -214,12 +832,9 @@ impl<'src> Function<'src> {
                 deref
             }
             Argument::StructField { data, name } => {
-                let DataType::Struct(ref fields) = self.argument_data_type(data) else { panic!() };
+                let DataType::Struct(ref struct_type) = self.argument_data_type(data) else { panic!() };
 
-                fields
-                    .iter()
-                    .find(|(find_name, _)| find_name == name)
-                    .map_or_else(|| panic!(), |(_, data_type)| data_type)
+                struct_type.field(name).unwrap_or_else(|| panic!())
             }
             Argument::Constant { data_type, .. } | Argument::Symbol { data_type, .. } => data_type,
             Argument::VoidRegister => unreachable!(),
@@ -227,9 +842,27 @@ impl<'src> Function<'src> {
     }
 }
 
+// A variable with a fixed address, which is zeroed when it has no initial value
+#[derive(Debug, Clone)]
+pub struct Global<'src> {
+    pub data_type: DataType<'src>,
+    pub value: Option<u64>,
+}
+
+impl Global<'_> {
+    // Little endian, the size of the type
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        self.value
+            .map(|value| value.to_le_bytes()[..self.data_type.size()].to_vec())
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ByteCode<'src> {
-    pub strings: Vec<&'src str>,
+    pub builtins: Vec<Builtin<'src>>,
+    pub externs: Vec<Extern<'src>>,
+    pub strings: Vec<&'src [u8]>,
+    pub globals: Vec<Global<'src>>,
     pub functions: Vec<Function<'src>>,
 }
 
@@ -242,16 +875,328 @@ impl<'src> ByteCode<'src> {
         self.functions.push(function);
     }
 
-    pub fn add_string(&mut self, string: &'src str) -> usize {
+    pub fn add_string(&mut self, string: &'src [u8]) -> usize {
         self.strings.push(string);
         self.strings.len() - 1
     }
 
+    pub fn add_global(&mut self, global: Global<'src>) -> usize {
+        self.globals.push(global);
+        self.globals.len() - 1
+    }
+
+    // Earlier functions are done first, so a chain of small functions is inlined all the way
+    pub fn inline_functions(&mut self) {
+        for index in 0..self.functions.len() {
+            let mut function = mem::replace(
+                &mut self.functions[index],
+                Function::new(String::new(), DataType::Void, Vec::new()),
+            );
+
+            for opcode in mem::take(&mut function.opcodes) {
+                let callee = match opcode {
+                    OpCode::Call {
+                        lhs: Argument::Symbol { ref name, .. },
+                        ..
+                    } => self
+                        .functions
+                        .iter()
+                        .find(|callee| callee.name == *name && callee.should_inline()),
+                    _ => None,
+                };
+
+                match (callee, opcode) {
+                    (Some(callee), OpCode::Call { dst, arguments, .. }) => {
+                        function.inline_call(callee, dst, arguments)
+                    }
+                    (_, opcode) => function.add_opcode(opcode),
+                }
+            }
+
+            self.functions[index] = function;
+        }
+    }
+
     pub fn string_symbol_name(id: usize) -> String {
         format!("str_{id}")
     }
+
+    pub fn global_symbol_name(id: usize) -> String {
+        format!("global_{id}")
+    }
+
+    // Lists every symbol with its section and size, functions are sized by their stack frame
+    pub fn generate_map(&self) -> Result<String, fmt::Error> {
+        let mut map = String::new();
+
+        writeln!(map, "{:<8} {:>8}  symbol", "section", "size")?;
+
+        // Written by hand, so there is no frame size to go by
+        for builtin in &self.builtins {
+            writeln!(map, "{:<8} {:>8}  {}", ".text", "-", builtin.name)?;
+        }
+
+        for function in &self.functions {
+            let section = if function.cold {
+                ".text.unlikely"
+            } else {
+                ".text"
+            };
+
+            writeln!(
+                map,
+                "{:<8} {:>8}  {}",
+                section,
+                function.stack_size(),
+                function.name
+            )?;
+        }
+
+        for (id, string) in self.strings.iter().enumerate() {
+            writeln!(
+                map,
+                "{:<8} {:>8}  {}",
+                ".data",
+                string.len() + 1, // Null terminated
+                Self::string_symbol_name(id)
+            )?;
+        }
+
+        for (id, global) in self.globals.iter().enumerate() {
+            let section = if global.value.is_some() {
+                ".data"
+            } else {
+                ".bss"
+            };
+
+            writeln!(
+                map,
+                "{:<8} {:>8}  {}",
+                section,
+                global.data_type.size_aligned(),
+                Self::global_symbol_name(id)
+            )?;
+        }
+
+        Ok(map)
+    }
+}
+
+// The signature, then the type of every register, then the opcodes with labels outdented
+impl fmt::Display for Function<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "function {}(", self.name)?;
+
+        for (argument_id, data_type) in self.argument_types.iter().enumerate() {
+            if argument_id > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "a{argument_id}: {data_type:?}")?;
+        }
+
+        write!(f, ") -> {:?}", self.return_type)?;
+
+        if self.inline != InlineHint::Auto {
+            write!(f, " inline({:?})", self.inline)?;
+        }
+
+        if self.cold {
+            write!(f, " cold")?;
+        }
+
+        writeln!(f)?;
+
+        for (register_id, data_type) in self.register_types.iter().enumerate() {
+            match self.register_alignments.get(&register_id) {
+                Some(alignment) => {
+                    writeln!(f, "    r{register_id}: {data_type:?} align({alignment})")?
+                }
+                None => writeln!(f, "    r{register_id}: {data_type:?}")?,
+            }
+        }
+
+        writeln!(f)?;
+
+        for opcode in &self.opcodes {
+            match opcode {
+                OpCode::Label { .. } => writeln!(f, "{opcode}")?,
+                _ => writeln!(f, "    {opcode}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ByteCode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for builtin in &self.builtins {
+            writeln!(f, "builtin {}", builtin.name)?;
+        }
+
+        for extern_function in &self.externs {
+            writeln!(f, "extern {}", extern_function.name)?;
+        }
+
+        for (id, string) in self.strings.iter().enumerate() {
+            writeln!(
+                f,
+                "string {} \"{}\"",
+                Self::string_symbol_name(id),
+                string.escape_ascii()
+            )?;
+        }
+
+        for function in &self.functions {
+            writeln!(f)?;
+            write!(f, "{function}")?;
+        }
+
+        Ok(())
+    }
 }
 
 pub trait CodeGenerator<'src> {
     fn generate(bytecode: &ByteCode<'src>) -> Result<String, fmt::Error>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::Compiler, parser::Parser, symbol_table::SymbolTable, types::IntType};
+
+    #[test]
+    fn small_functions_are_inlined() {
+        let source = "{
+            fn add(let a: U64, let b: U64): U64 {
+                a + b;
+            };
+
+            add(1, 2);
+        }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+
+        let main = bytecode
+            .functions
+            .iter()
+            .find(|function| function.name == "@main")
+            .unwrap();
+
+        assert!(!main
+            .opcodes
+            .iter()
+            .any(|opcode| matches!(opcode, OpCode::Call { .. })));
+    }
+
+    #[test]
+    fn peephole_forwards_moves_through_registers() {
+        let data_type = DataType::Int(IntType::U64);
+
+        let mut function = Function::new("test".to_string(), data_type.clone(), Vec::new());
+
+        let first = Argument::Register(function.add_register(data_type.clone()));
+        let second = Argument::Register(function.add_register(data_type.clone()));
+
+        function.add_opcode(OpCode::Mov {
+            dst: first.clone(),
+            src: Argument::Constant {
+                value: 5,
+                data_type: data_type.clone(),
+            },
+        });
+
+        function.add_opcode(OpCode::Mov {
+            dst: second.clone(),
+            src: first,
+        });
+
+        function.add_opcode(OpCode::Mov {
+            dst: second.clone(),
+            src: second.clone(),
+        });
+
+        function.add_opcode(OpCode::Mov {
+            dst: Argument::ReturnValue,
+            src: second,
+        });
+
+        assert_eq!(function.opcodes.len(), 4);
+
+        function.peephole();
+
+        assert_eq!(function.opcodes.len(), 1);
+    }
+
+    #[test]
+    fn division_costs_more_than_multiplication_and_addition() {
+        let dst = Argument::Register(0);
+        let src = Argument::Register(1);
+
+        let div = OpCode::Div {
+            dst: dst.clone(),
+            src: src.clone(),
+        };
+        let mul = OpCode::Mul {
+            dst: dst.clone(),
+            src: src.clone(),
+        };
+        let add = OpCode::Add {
+            dst: dst.clone(),
+            src: src.clone(),
+        };
+        let mov = OpCode::Mov { dst, src };
+
+        assert!(div.cost() > mul.cost());
+        assert!(mul.cost() > add.cost());
+        assert!(add.cost() > mov.cost());
+    }
+
+    #[test]
+    fn nothing_is_generated_after_returns_and_traps() {
+        let source = "{
+            @noinline fn early(let value: U64): U64 {
+                return value * 2;
+
+                write(\"unreachable\\n\", 12);
+
+                value;
+            };
+
+            @noinline fn crash(): U64 {
+                @trap();
+
+                write(\"unreachable\\n\", 12);
+
+                1;
+            };
+
+            early(21) + crash();
+        }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+
+        for name in ["early", "crash"] {
+            let function = bytecode
+                .functions
+                .iter()
+                .find(|function| function.name == name)
+                .unwrap();
+
+            let end = function
+                .opcodes
+                .iter()
+                .position(|opcode| matches!(opcode, OpCode::Return | OpCode::Trap))
+                .unwrap();
+
+            assert!(function.opcodes[end + 1..]
+                .iter()
+                .all(|opcode| matches!(opcode, OpCode::Label { .. })));
+        }
+    }
+}
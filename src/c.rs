@@ -0,0 +1,745 @@
+use crate::{
+    builtins::Extern,
+    bytecode::{Argument, ByteCode, CodeGenerator, Function, OpCode},
+    types::{DataType, FloatType, IntType},
+};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Write},
+};
+
+// Everything the generated functions rely on. Aggregates are plain bytes, one type per size,
+// and floats are constructed from their bit pattern
+pub const PROLOGUE_C: &str = "\
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+static inline float oil_f32(uint32_t bits)
+{
+    float value;
+    memcpy(&value, &bits, sizeof(value));
+    return value;
+}
+
+static inline double oil_f64(uint64_t bits)
+{
+    double value;
+    memcpy(&value, &bits, sizeof(value));
+    return value;
+}
+
+static inline uint64_t oil_cycles(void)
+{
+#if defined(__x86_64__) || defined(__i386__)
+    return __builtin_ia32_rdtsc();
+#else
+    return (uint64_t)clock();
+#endif
+}
+";
+
+pub const READ_CODE_C: &str = "\
+static inline void oil_read(unsigned char *text, uint64_t length)
+{
+    fread(text, 1, length, stdin);
+}
+";
+
+// Flushed right away, like the `write` syscall of the assembly version
+pub const WRITE_CODE_C: &str = "\
+static inline void oil_write(unsigned char *text, uint64_t length)
+{
+    fwrite(text, 1, length, stdout);
+    fflush(stdout);
+}
+";
+
+pub const PRINT_INTEGER_CODE_C: &str = "\
+static inline void oil_print__integer(uint64_t value, uint8_t is_signed)
+{
+    if (is_signed) {
+        printf(\"%lld\", (long long)value);
+    } else {
+        printf(\"%llu\", (unsigned long long)value);
+    }
+
+    fflush(stdout);
+}
+";
+
+// Whole pages that start out zeroed, like the `mmap` of the assembly version
+pub const MALLOC_CODE_C: &str = "\
+static inline unsigned char *oil_malloc(uint64_t length)
+{
+    uint64_t size = (length + 4095) / 4096 * 4096;
+    unsigned char *data = aligned_alloc(4096, size);
+
+    if (data) {
+        memset(data, 0, size);
+    }
+
+    return data;
+}
+";
+
+pub const FREE_CODE_C: &str = "\
+static inline void oil_free(unsigned char *data, uint64_t length)
+{
+    (void)length;
+    free(data);
+}
+";
+
+// Bytecode names like `@main` or `name.0` aren't C identifiers, and shouldn't clash with libc either
+fn mangle(name: &str) -> String {
+    let mut mangled = String::from("oil_");
+
+    for byte in name.bytes() {
+        match byte {
+            b'_' => mangled.push_str("__"),
+            byte if byte.is_ascii_alphanumeric() => mangled.push(byte as char),
+            byte => mangled.push_str(&format!("_{byte:02x}")),
+        }
+    }
+
+    mangled
+}
+
+// Pointers are written `T *name`
+fn declaration(c_type: &str, name: &str) -> String {
+    if c_type.ends_with('*') {
+        format!("{c_type}{name}")
+    } else {
+        format!("{c_type} {name}")
+    }
+}
+
+fn int_type(int_type: &IntType) -> &'static str {
+    match int_type {
+        IntType::S8 => "int8_t",
+        IntType::S16 => "int16_t",
+        IntType::S32 => "int32_t",
+        IntType::S64 => "int64_t",
+        IntType::U8 => "uint8_t",
+        IntType::U16 => "uint16_t",
+        IntType::U32 => "uint32_t",
+        IntType::U64 => "uint64_t",
+    }
+}
+
+// Portable C, with each register as a local variable and each opcode as a statement
+pub struct CBackend {
+    text: String,
+    // Of every aggregate used, each gets its own type
+    aggregate_sizes: BTreeSet<usize>,
+}
+
+impl CBackend {
+    fn c_type(&mut self, data_type: &DataType) -> String {
+        match data_type {
+            DataType::Void => "void".to_string(),
+            DataType::Bool | DataType::Char => "uint8_t".to_string(),
+            DataType::Int(int) => int_type(int).to_string(),
+            DataType::Enum(enum_type) => int_type(&enum_type.int_type()).to_string(),
+            DataType::Float(FloatType::F32) => "float".to_string(),
+            DataType::Float(FloatType::F64) => "double".to_string(),
+            DataType::Ref(_) => "unsigned char *".to_string(),
+            DataType::Function { .. } => "void *".to_string(),
+            DataType::Struct(_) | DataType::Array { .. } => {
+                self.aggregate_sizes.insert(data_type.size());
+
+                format!("oil_bytes_{}", data_type.size())
+            }
+            DataType::Inferred(_) => unreachable!(),
+        }
+    }
+
+    // What a value of the function type has to be cast to before it is called
+    fn function_pointer_type(&mut self, data_type: &DataType) -> String {
+        let DataType::Function {
+            return_type,
+            argument_types,
+        } = data_type
+        else {
+            unreachable!("Only functions can be called")
+        };
+
+        let return_type = self.c_type(return_type);
+
+        let argument_types = if argument_types.is_empty() {
+            "void".to_string()
+        } else {
+            argument_types
+                .iter()
+                .map(|data_type| self.c_type(data_type))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        format!("{return_type} (*)({argument_types})")
+    }
+
+    // The argument as something that can be assigned to
+    fn generate_place<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> String {
+        match argument {
+            Argument::ReturnValue => "ret".to_string(),
+            Argument::Register(register_id) => format!("r{register_id}"),
+            Argument::Argument(argument_id) => format!("a{argument_id}"),
+            Argument::Deref(deref) => {
+                let data_type = self.c_type(function.argument_data_type(argument));
+                let pointer = self.generate_value(function, deref);
+
+                format!("(*({data_type} *){pointer})")
+            }
+            Argument::StructField { data, name } => {
+                let DataType::Struct(ref struct_type) = function.argument_data_type(data) else {
+                    panic!();
+                };
+
+                let data_type = self.c_type(function.argument_data_type(argument));
+                let address = self.generate_address(function, data);
+
+                format!(
+                    "(*({data_type} *)({address} + {}))",
+                    struct_type.field_offset(name)
+                )
+            }
+            Argument::Constant { .. } | Argument::Symbol { .. } | Argument::VoidRegister => {
+                unreachable!()
+            }
+        }
+    }
+
+    fn generate_address<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> String {
+        match argument {
+            Argument::Symbol { name, .. } => format!("(unsigned char *){}", mangle(name)),
+            _ => format!(
+                "(unsigned char *)&{}",
+                self.generate_place(function, argument)
+            ),
+        }
+    }
+
+    fn generate_value<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> String {
+        match argument {
+            Argument::Constant {
+                value,
+                data_type: DataType::Float(FloatType::F32),
+            } => format!("oil_f32({value}u)"),
+            Argument::Constant {
+                value,
+                data_type: DataType::Float(FloatType::F64),
+            } => format!("oil_f64({value}ull)"),
+            Argument::Constant { value, data_type } => {
+                format!("(({}){value}ull)", self.c_type(data_type))
+            }
+            Argument::Symbol {
+                name,
+                data_type: DataType::Function { .. },
+            } => format!("(void *){}", mangle(name)),
+            Argument::Symbol { .. } => self.generate_address(function, argument),
+            _ => self.generate_place(function, argument),
+        }
+    }
+
+    fn statement(&mut self, statement: &str) -> fmt::Result {
+        writeln!(self.text, "    {statement};")
+    }
+
+    // Integer arithmetic goes through `uint64_t`, so overflow wraps around like it does in assembly
+    fn generate_infix<'src>(
+        &mut self,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        src: &Argument<'src>,
+        operation: &str,
+    ) -> fmt::Result {
+        let data_type = function.argument_data_type(dst);
+
+        let dst_compiled = self.generate_place(function, dst);
+
+        if data_type.is_float() {
+            let src_compiled = self.generate_value(function, src);
+
+            return self.statement(&format!("{dst_compiled} {operation}= {src_compiled}"));
+        }
+
+        let dst_type = self.c_type(data_type);
+        let dst_wide = self.generate_u64(function, dst);
+        let src_wide = self.generate_u64(function, src);
+
+        let result = format!("{dst_wide} {operation} {src_wide}");
+
+        if dst_type == "uint64_t" {
+            self.statement(&format!("{dst_compiled} = {result}"))
+        } else {
+            self.statement(&format!("{dst_compiled} = ({dst_type})({result})"))
+        }
+    }
+
+    // An integer, reference or boolean widened to `uint64_t`
+    fn generate_u64<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> String {
+        if let Argument::Constant { value, .. } = argument {
+            return format!("{value}ull");
+        }
+
+        let data_type = self.c_type(function.argument_data_type(argument));
+        let argument_compiled = self.generate_value(function, argument);
+
+        if data_type == "uint64_t" {
+            argument_compiled
+        } else {
+            format!("(uint64_t){argument_compiled}")
+        }
+    }
+
+    fn generate_comparison<'src>(
+        &mut self,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        lhs: &Argument<'src>,
+        rhs: &Argument<'src>,
+        operation: &str,
+    ) -> fmt::Result {
+        let dst_compiled = self.generate_place(function, dst);
+        let lhs_compiled = self.generate_value(function, lhs);
+        let rhs_compiled = self.generate_value(function, rhs);
+
+        self.statement(&format!(
+            "{dst_compiled} = {lhs_compiled} {operation} {rhs_compiled}"
+        ))
+    }
+
+    // `*(T *)(pointer + offset)`, the type being that of `data`
+    fn generate_offset<'src>(
+        &mut self,
+        function: &Function<'src>,
+        pointer: &Argument<'src>,
+        offset: String,
+        data: &Argument<'src>,
+    ) -> String {
+        let data_type = self.c_type(function.argument_data_type(data));
+        let pointer_compiled = self.generate_value(function, pointer);
+
+        format!("(*({data_type} *)({pointer_compiled} + {offset}))")
+    }
+
+    fn generate_opcode<'src>(
+        &mut self,
+        function: &Function<'src>,
+        opcode: &OpCode<'src>,
+    ) -> fmt::Result {
+        match opcode {
+            // Empty aggregates still take up a byte in C, which mustn't be written
+            OpCode::Mov { dst, .. } if function.argument_data_type(dst).size() == 0 => {}
+            OpCode::Mov { dst, src } => {
+                let dst_compiled = self.generate_place(function, dst);
+                let src_compiled = self.generate_value(function, src);
+
+                let dst_type = self.c_type(function.argument_data_type(dst));
+                let src_type = self.c_type(function.argument_data_type(src));
+
+                // Aggregates always have the same size, so they never need a cast
+                if dst_type == src_type {
+                    self.statement(&format!("{dst_compiled} = {src_compiled}"))?;
+                } else {
+                    self.statement(&format!("{dst_compiled} = ({dst_type}){src_compiled}"))?;
+                }
+            }
+            OpCode::Add { dst, src } => self.generate_infix(function, dst, src, "+")?,
+            OpCode::Sub { dst, src } => self.generate_infix(function, dst, src, "-")?,
+            OpCode::Mul { dst, src } => self.generate_infix(function, dst, src, "*")?,
+            OpCode::And { dst, src } => self.generate_infix(function, dst, src, "&")?,
+            OpCode::Or { dst, src } => self.generate_infix(function, dst, src, "|")?,
+            OpCode::Xor { dst, src } => self.generate_infix(function, dst, src, "^")?,
+            // Signed division has to stay signed, and it can't overflow besides the one case that traps
+            OpCode::Div { dst, src } | OpCode::Mod { dst, src } => {
+                let operation = match opcode {
+                    OpCode::Div { .. } => "/",
+                    _ => "%",
+                };
+
+                let dst_compiled = self.generate_place(function, dst);
+                let src_compiled = self.generate_value(function, src);
+
+                self.statement(&format!(
+                    "{dst_compiled} = {dst_compiled} {operation} {src_compiled}"
+                ))?;
+            }
+            OpCode::Not { dst } => {
+                let dst_compiled = self.generate_place(function, dst);
+
+                self.statement(&format!("{dst_compiled} = ({dst_compiled} & 1) ^ 1"))?;
+            }
+            OpCode::BitNot { dst } => {
+                let data_type = self.c_type(function.argument_data_type(dst));
+                let dst_compiled = self.generate_place(function, dst);
+
+                self.statement(&format!("{dst_compiled} = ({data_type})~{dst_compiled}"))?;
+            }
+            OpCode::Negate { dst } if function.argument_data_type(dst).is_float() => {
+                let dst_compiled = self.generate_place(function, dst);
+
+                self.statement(&format!("{dst_compiled} = -{dst_compiled}"))?;
+            }
+            OpCode::Negate { dst } => {
+                let data_type = self.c_type(function.argument_data_type(dst));
+                let dst_compiled = self.generate_place(function, dst);
+                let dst_wide = self.generate_u64(function, dst);
+
+                self.statement(&format!("{dst_compiled} = ({data_type})(0 - {dst_wide})"))?;
+            }
+            OpCode::Ref { dst, src } => {
+                let dst_compiled = self.generate_place(function, dst);
+                let src_address = self.generate_address(function, src);
+
+                self.statement(&format!("{dst_compiled} = {src_address}"))?;
+            }
+            // The index is in bytes
+            OpCode::Index { dst, src, index } => {
+                let offset = match index {
+                    Argument::VoidRegister => "0".to_string(),
+                    _ => self.generate_value(function, index),
+                };
+
+                let dst_compiled = self.generate_place(function, dst);
+                let src_compiled = self.generate_offset(function, src, offset, dst);
+
+                self.statement(&format!("{dst_compiled} = {src_compiled}"))?;
+            }
+            OpCode::SetIndex { dst, src, index } => {
+                let offset = match index {
+                    Argument::VoidRegister => "0".to_string(),
+                    _ => self.generate_value(function, index),
+                };
+
+                let dst_compiled = self.generate_offset(function, dst, offset, src);
+                let src_compiled = self.generate_value(function, src);
+
+                self.statement(&format!("{dst_compiled} = {src_compiled}"))?;
+            }
+            OpCode::SetField { dst, src, offset } => {
+                let dst_compiled = self.generate_offset(function, dst, offset.to_string(), src);
+                let src_compiled = self.generate_value(function, src);
+
+                self.statement(&format!("{dst_compiled} = {src_compiled}"))?;
+            }
+            OpCode::Copy { dst, src, size } => {
+                let dst_compiled = self.generate_value(function, dst);
+                let src_compiled = self.generate_value(function, src);
+                let size_compiled = self.generate_value(function, size);
+
+                self.statement(&format!(
+                    "memcpy({dst_compiled}, {src_compiled}, {size_compiled})"
+                ))?;
+            }
+            OpCode::SetIfEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "==")?
+            }
+            OpCode::SetIfNotEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "!=")?
+            }
+            OpCode::SetIfGreater { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, ">")?
+            }
+            OpCode::SetIfLess { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "<")?
+            }
+            OpCode::SetIfGreaterOrEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, ">=")?
+            }
+            OpCode::SetIfLessOrEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "<=")?
+            }
+            // The semicolon lets a label come last in the function
+            OpCode::Label { label_id } => writeln!(self.text, "L{label_id}:;")?,
+            OpCode::Goto { label_id } => self.statement(&format!("goto L{label_id}"))?,
+            OpCode::GotoIfZero {
+                condition,
+                label_id,
+            } => {
+                let condition_compiled = self.generate_value(function, condition);
+
+                self.statement(&format!("if (!{condition_compiled}) goto L{label_id}"))?;
+            }
+            OpCode::GotoIfNotZero {
+                condition,
+                label_id,
+            } => {
+                let condition_compiled = self.generate_value(function, condition);
+
+                self.statement(&format!("if ({condition_compiled}) goto L{label_id}"))?;
+            }
+            OpCode::Return if function.return_type == DataType::Void => self.statement("return")?,
+            OpCode::Return => self.statement("return ret")?,
+            // Arguments are stored last to first
+            OpCode::Call {
+                dst,
+                lhs,
+                arguments,
+            } => {
+                let lhs_compiled = match lhs {
+                    Argument::Symbol { name, .. } => mangle(name),
+                    _ => {
+                        let function_type =
+                            self.function_pointer_type(function.argument_data_type(lhs));
+                        let pointer = self.generate_value(function, lhs);
+
+                        format!("(({function_type}){pointer})")
+                    }
+                };
+
+                let arguments_compiled = arguments
+                    .iter()
+                    .rev()
+                    .map(|argument| self.generate_value(function, argument))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                let call = format!("{lhs_compiled}({arguments_compiled})");
+
+                if *dst == Argument::VoidRegister {
+                    self.statement(&call)?;
+                } else {
+                    let dst_compiled = self.generate_place(function, dst);
+
+                    self.statement(&format!("{dst_compiled} = {call}"))?;
+                }
+            }
+            OpCode::SourceLine { .. } => {}
+            OpCode::Breakpoint => self.statement("raise(SIGTRAP)")?,
+            OpCode::Trap => self.statement("abort()")?,
+            // The time stamp counter on x86, and processor time from `clock` anywhere else
+            OpCode::ReadCycleCounter { dst } => {
+                let dst_compiled = self.generate_place(function, dst);
+
+                self.statement(&format!("{dst_compiled} = oil_cycles()"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn generate_signature<'src>(&mut self, function: &Function<'src>) -> String {
+        let return_type = self.c_type(&function.return_type);
+
+        let arguments = if function.argument_types.is_empty() {
+            "void".to_string()
+        } else {
+            function
+                .argument_types
+                .iter()
+                .enumerate()
+                .map(|(argument_id, data_type)| {
+                    declaration(&self.c_type(data_type), &format!("a{argument_id}"))
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        format!(
+            "static {}({arguments})",
+            declaration(&return_type, &mangle(&function.name))
+        )
+    }
+
+    // Declared under the mangled name like every other symbol, the asm label links it to the real one
+    fn generate_extern_signature<'src>(&mut self, extern_function: &Extern<'src>) -> String {
+        let DataType::Function {
+            return_type,
+            argument_types,
+        } = &extern_function.data_type
+        else {
+            unreachable!("Extern declarations are always functions");
+        };
+
+        let return_type = self.c_type(return_type);
+
+        let arguments = if argument_types.is_empty() {
+            "void".to_string()
+        } else {
+            argument_types
+                .iter()
+                .map(|data_type| self.c_type(data_type))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        format!(
+            "extern {}({arguments}) __asm__(\"{}\")",
+            declaration(&return_type, &mangle(extern_function.name)),
+            extern_function.name
+        )
+    }
+
+    // Registers start out zeroed, so nothing is read uninitialized
+    fn generate_function<'src>(&mut self, function: &Function<'src>) -> fmt::Result {
+        let signature = self.generate_signature(function);
+
+        writeln!(self.text, "{signature}\n{{")?;
+
+        if function.return_type != DataType::Void {
+            let return_type = self.c_type(&function.return_type);
+
+            self.statement(&format!("{} = {{0}}", declaration(&return_type, "ret")))?;
+        }
+
+        for (register_id, data_type) in function.register_types.iter().enumerate() {
+            if *data_type == DataType::Void {
+                continue;
+            }
+
+            let c_type = self.c_type(data_type);
+
+            let alignment = if data_type.is_aggregate() {
+                format!("_Alignas({}) ", function.register_alignment(register_id))
+            } else {
+                String::new()
+            };
+
+            self.statement(&format!(
+                "{alignment}{} = {{0}}",
+                declaration(&c_type, &format!("r{register_id}"))
+            ))?;
+        }
+
+        for opcode in &function.opcodes {
+            self.generate_opcode(function, opcode)?;
+        }
+
+        if function.return_type != DataType::Void {
+            self.statement("return ret")?;
+        }
+
+        writeln!(self.text, "}}\n")
+    }
+}
+
+impl<'src> CodeGenerator<'src> for CBackend {
+    fn generate(bytecode: &ByteCode<'src>) -> Result<String, fmt::Error> {
+        let mut c = Self {
+            text: String::new(),
+            aggregate_sizes: BTreeSet::new(),
+        };
+
+        for extern_function in &bytecode.externs {
+            let signature = c.generate_extern_signature(extern_function);
+
+            writeln!(c.text, "{signature};")?;
+        }
+
+        for function in &bytecode.functions {
+            let signature = c.generate_signature(function);
+
+            writeln!(c.text, "{signature};")?;
+        }
+
+        writeln!(c.text)?;
+
+        for function in &bytecode.functions {
+            c.generate_function(function)?;
+        }
+
+        // `_start` calls `@main` and exits with its result
+        let main = bytecode
+            .functions
+            .iter()
+            .find(|function| function.name == "@main")
+            .expect("There is always a main function");
+
+        writeln!(c.text, "int main(void)\n{{")?;
+
+        match main.return_type {
+            DataType::Bool | DataType::Int(_) | DataType::Ref(_) => {
+                c.statement("return (int)(uint64_t)oil__40main()")?
+            }
+            _ => {
+                c.statement("oil__40main()")?;
+                c.statement("return 0")?;
+            }
+        }
+
+        writeln!(c.text, "}}")?;
+
+        // Now that every aggregate type used is known
+        let mut code = String::from(PROLOGUE_C);
+
+        // C doesn't have empty arrays
+        for size in &c.aggregate_sizes {
+            writeln!(
+                code,
+                "\ntypedef struct {{\n    unsigned char bytes[{}];\n}} oil_bytes_{size};",
+                size.max(&1)
+            )?;
+        }
+
+        for builtin in &bytecode.builtins {
+            write!(code, "\n{}", builtin.c)?;
+        }
+
+        writeln!(code)?;
+
+        for (id, string) in bytecode.strings.iter().enumerate() {
+            let bytes = string
+                .iter()
+                .chain(std::iter::once(&0))
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            writeln!(
+                code,
+                "static unsigned char {}[] = {{{bytes}}};",
+                mangle(&ByteCode::string_symbol_name(id))
+            )?;
+        }
+
+        // Bytes, like strings, so they can be read as any type through a cast
+        for (id, global) in bytecode.globals.iter().enumerate() {
+            let name = mangle(&ByteCode::global_symbol_name(id));
+            let size = global.data_type.size().max(1);
+
+            match global.bytes() {
+                Some(bytes) => {
+                    let bytes = bytes
+                        .iter()
+                        .map(|byte| byte.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ");
+
+                    writeln!(
+                        code,
+                        "static _Alignas(8) unsigned char {name}[{size}] = {{{bytes}}};"
+                    )?;
+                }
+                None => writeln!(code, "static _Alignas(8) unsigned char {name}[{size}];")?,
+            }
+        }
+
+        writeln!(code)?;
+
+        code.push_str(&c.text);
+
+        Ok(code)
+    }
+}
@@ -1,38 +1,1422 @@
 use crate::{
-    ast::{Ast, AstKind, VariableDeclaration},
-    bytecode::{Argument, ByteCode, Function, OpCode},
-    parser::{Token, TokenKind},
-    symbol_table::{SymbolID, SymbolTable},
-    types::DataType,
+    ast::{Ast, AstKind, Intrinsic, VariableDeclaration},
+    bytecode::{Argument, ByteCode, Function, Global, OpCode},
+    parser::{GenericFunction, Token, TokenKind},
+    symbol_table::{Symbol, SymbolID, SymbolTable},
+    types::{DataType, FloatType, IntType},
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+// What `compile` does, unless told otherwise
+#[derive(Clone, Copy, Default)]
+pub struct CompilerOptions {
+    // Traps when an array is indexed past its length, instead of reading or writing whatever is there
+    pub bounds_checks: bool,
+    // Traps when an integer is divided by zero, instead of leaving it to the target
+    pub division_checks: bool,
+    // Marks where each statement starts, so the generated code can be commented with its source
+    pub source_lines: bool,
+}
+
+pub struct Compiler<'src> {
+    symbol_table: SymbolTable<'src>,
+    variable_registers: HashMap<SymbolID, Argument<'src>>,
+    // What `@assume(x < n)` said about the variables for the rest of the block it's in
+    assumed_bounds: HashMap<SymbolID, u64>,
+    // And what `@assume(x != 0)` did
+    assumed_nonzero: HashSet<SymbolID>,
+    options: CompilerOptions,
+}
+
+impl<'src> Compiler<'src> {
+    pub fn compile(ast: &'src Ast<'src>, symbol_table: SymbolTable<'src>) -> ByteCode<'src> {
+        Self::compile_with(ast, symbol_table, CompilerOptions::default())
+    }
+
+    pub fn compile_with(
+        ast: &'src Ast<'src>,
+        symbol_table: SymbolTable<'src>,
+        options: CompilerOptions,
+    ) -> ByteCode<'src> {
+        let mut bytecode = ByteCode::new();
+
+        bytecode.builtins = symbol_table.builtins.clone();
+        bytecode.externs = symbol_table.externs.clone();
+
+        let mut compiler = Self {
+            symbol_table,
+            variable_registers: HashMap::new(),
+            assumed_bounds: HashMap::new(),
+            assumed_nonzero: HashSet::new(),
+            options,
+        };
+
+        let mut main = Function::new("@main".to_string(), ast.data_type.clone(), Vec::new());
+
+        let data = compiler.compile_ast(ast, &mut bytecode, &mut main);
+
+        main.add_opcode(OpCode::Mov {
+            dst: Argument::ReturnValue,
+            src: data,
+        });
+
+        bytecode.add_function(main);
+
+        bytecode.inline_functions();
+
+        for function in &mut bytecode.functions {
+            function.peephole();
+            function.remove_unreachable();
+        }
+
+        bytecode
+    }
+
+    fn instance_name(&self, name: &'src str, type_arguments: &[DataType<'src>]) -> String {
+        let Some(Symbol::Generic(generic)) = self.symbol_table.get_symbol(name) else {
+            unreachable!("Instances always refer to a generic function");
+        };
+
+        GenericFunction::instance_name(
+            name,
+            generic
+                .instance(type_arguments)
+                .expect("Instances are created while parsing"),
+        )
+    }
+
+    // Aggregates are copied byte by byte between their addresses, instead of through a register
+    fn compile_copy(function: &mut Function<'src>, dst: Argument<'src>, src: Argument<'src>) {
+        let data_type = function.argument_data_type(&dst).clone();
+
+        let dst_pointer =
+            Argument::Register(function.add_register(DataType::Ref(Box::new(data_type.clone()))));
+        let src_pointer =
+            Argument::Register(function.add_register(DataType::Ref(Box::new(data_type.clone()))));
+
+        function.add_opcode(OpCode::Ref {
+            dst: dst_pointer.clone(),
+            src: dst,
+        });
+
+        function.add_opcode(OpCode::Ref {
+            dst: src_pointer.clone(),
+            src,
+        });
+
+        function.add_opcode(OpCode::Copy {
+            dst: dst_pointer,
+            src: src_pointer,
+            // Not aligned, an array inside of another one is followed right away by the next element
+            size: Argument::Constant {
+                value: data_type.size() as u64,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+    }
+
+    // Compiles into an argument that refers to where the value lives, so writes go through to it
+    // instead of into a loaded copy
+    fn compile_place(
+        &mut self,
+        ast: &'src Ast<'src>,
+        bytecode: &mut ByteCode<'src>,
+        function: &mut Function<'src>,
+    ) -> Argument<'src> {
+        match ast.kind {
+            // `array + index * size`, the array itself is used in place
+            AstKind::Index { ref lhs, ref index } if matches!(lhs.data_type, DataType::Array { .. }) => {
+                let address = Argument::Register(
+                    function.add_register(DataType::Ref(Box::new(ast.data_type.clone()))),
+                );
+
+                let DataType::Array { length, .. } = lhs.data_type else {
+                    unreachable!();
+                };
+
+                let in_bounds = self.is_assumed_below(index, length);
+                let index = self.compile_ast(index, bytecode, function);
+
+                if self.options.bounds_checks && !in_bounds {
+                    Self::compile_bounds_check(function, index.clone(), length);
+                }
+
+                let array = self.compile_place(lhs, bytecode, function);
+
+                function.add_opcode(OpCode::Ref {
+                    dst: address.clone(),
+                    src: array,
+                });
+
+                let offset = Self::compile_scaled_index(function, index, ast.data_type.size());
+
+                function.add_opcode(OpCode::Add {
+                    dst: address.clone(),
+                    src: offset,
+                });
+
+                Argument::Deref(Box::new(address))
+            }
+            // `pointer + index * size`, like the pointer arithmetic
+            AstKind::Index { ref lhs, ref index } => {
+                let address = Argument::Register(
+                    function.add_register(DataType::Ref(Box::new(ast.data_type.clone()))),
+                );
+
+                let index = self.compile_ast(index, bytecode, function);
+                let lhs = self.compile_ast(lhs, bytecode, function);
+
+                function.add_opcode(OpCode::Mov {
+                    dst: address.clone(),
+                    src: lhs,
+                });
+
+                let offset = Self::compile_scaled_index(function, index, ast.data_type.size());
+
+                function.add_opcode(OpCode::Add {
+                    dst: address.clone(),
+                    src: offset,
+                });
+
+                Argument::Deref(Box::new(address))
+            }
+            AstKind::GetField { ref lhs, name } => Argument::StructField {
+                data: Box::new(self.compile_place(lhs, bytecode, function)),
+                name,
+            },
+            _ => self.compile_ast(ast, bytecode, function),
+        }
+    }
+
+    // Turns an index into a byte offset, which is what `Index` and `SetIndex` take
+    fn compile_scaled_index(
+        function: &mut Function<'src>,
+        index: Argument<'src>,
+        size: usize,
+    ) -> Argument<'src> {
+        if size == 1 {
+            return index;
+        }
+
+        let offset = Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+
+        function.add_opcode(OpCode::Mov {
+            dst: offset.clone(),
+            src: index,
+        });
+
+        function.add_opcode(OpCode::Mul {
+            dst: offset.clone(),
+            src: Argument::Constant {
+                value: size as u64,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+
+        offset
+    }
+
+    // The variable a node refers to, if it's just a name
+    fn variable_id(&self, ast: &Ast<'src>) -> Option<SymbolID> {
+        let AstKind::Node {
+            token:
+                Token {
+                    kind: TokenKind::Ident,
+                    text: name,
+                    ..
+                },
+        } = ast.kind
+        else {
+            return None;
+        };
+
+        self.symbol_table.get_symbol_id(name)
+    }
+
+    // Whether an earlier `@assume` keeps the index below `length`
+    fn is_assumed_below(&self, index: &Ast<'src>, length: usize) -> bool {
+        self.variable_id(index)
+            .and_then(|symbol_id| self.assumed_bounds.get(&symbol_id))
+            .is_some_and(|&bound| bound <= length as u64)
+    }
+
+    // Whether the divisor is a constant other than zero, or an earlier `@assume` says it isn't zero
+    fn is_assumed_nonzero(&self, divisor: &Ast<'src>) -> bool {
+        divisor.fold().is_some_and(|value| value != 0)
+            || self
+                .variable_id(divisor)
+                .is_some_and(|symbol_id| self.assumed_nonzero.contains(&symbol_id))
+    }
+
+    // Remembers `@assume(x < n)`, `@assume(x <= n)` or `@assume(x != 0)` on a local variable that
+    // none of the statements after it in the block change. One whose address was taken could be
+    // written through a pointer anywhere, so nothing is assumed about it
+    fn assume(&mut self, statement: &Ast<'src>, rest: &[Ast<'src>], function: &Function<'src>) {
+        let AstKind::Intrinsic {
+            intrinsic: Intrinsic::Assume,
+            ref arguments,
+        } = statement.kind
+        else {
+            return;
+        };
+
+        let AstKind::Infix {
+            ref oper,
+            ref lhs,
+            ref rhs,
+        } = arguments[0].kind
+        else {
+            return;
+        };
+
+        let AstKind::Node {
+            token:
+                Token {
+                    kind: TokenKind::Ident,
+                    text: name,
+                    ..
+                },
+        } = lhs.kind
+        else {
+            return;
+        };
+
+        let Some(symbol_id) = self.symbol_table.get_symbol_id(name) else {
+            return;
+        };
+
+        let Some(variable) = self.variable_registers.get(&symbol_id) else {
+            return;
+        };
+
+        let address_taken = function
+            .opcodes
+            .iter()
+            .any(|opcode| matches!(opcode, OpCode::Ref { src, .. } if src == variable));
+
+        if address_taken || rest.iter().any(|statement| statement.may_change(name)) {
+            return;
+        }
+
+        match (&oper.kind, rhs.fold()) {
+            (TokenKind::NotEquals, Some(0)) => {
+                self.assumed_nonzero.insert(symbol_id);
+            }
+            (TokenKind::Less | TokenKind::LessOrEqual, Some(bound)) => {
+                let bound = match oper.kind {
+                    TokenKind::Less => bound,
+                    _ => bound + 1,
+                };
+
+                if let Ok(bound) = u64::try_from(bound) {
+                    self.assumed_bounds.insert(symbol_id, bound);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Integers only, floats give infinity or not a number instead
+    fn compile_division_check(function: &mut Function<'src>, divisor: Argument<'src>) {
+        let end_label = function.add_label();
+
+        function.add_opcode(OpCode::GotoIfNotZero {
+            condition: divisor,
+            label_id: end_label,
+        });
+
+        function.add_opcode(OpCode::Trap);
+
+        function.add_opcode(OpCode::Label {
+            label_id: end_label,
+        });
+    }
+
+    // Indices are unsigned, so one comparison is enough
+    fn compile_bounds_check(function: &mut Function<'src>, index: Argument<'src>, length: usize) {
+        let in_bounds = Argument::Register(function.add_register(DataType::Bool));
+        let end_label = function.add_label();
+
+        function.add_opcode(OpCode::SetIfLess {
+            dst: in_bounds.clone(),
+            lhs: index,
+            rhs: Argument::Constant {
+                value: length as u64,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+
+        function.add_opcode(OpCode::GotoIfNotZero {
+            condition: in_bounds,
+            label_id: end_label,
+        });
+
+        function.add_opcode(OpCode::Trap);
+
+        function.add_opcode(OpCode::Label {
+            label_id: end_label,
+        });
+    }
+
+    fn compile_infix(
+        &mut self,
+        oper: &Token<'src>,
+        lhs: &'src Ast<'src>,
+        rhs: &'src Ast<'src>,
+        dst: Argument<'src>,
+        bytecode: &mut ByteCode<'src>,
+        function: &mut Function<'src>,
+    ) {
+        // The right hand side is skipped if the left hand side already decides the result
+        if let TokenKind::And | TokenKind::Or = oper.kind {
+            let end_label = function.add_label();
+
+            let lhs = self.compile_ast(lhs, bytecode, function);
+
+            function.add_opcode(OpCode::Mov {
+                dst: dst.clone(),
+                src: lhs,
+            });
+
+            if oper.kind == TokenKind::And {
+                function.add_opcode(OpCode::GotoIfZero {
+                    condition: dst.clone(),
+                    label_id: end_label,
+                });
+            } else {
+                function.add_opcode(OpCode::GotoIfNotZero {
+                    condition: dst.clone(),
+                    label_id: end_label,
+                });
+            }
+
+            let rhs = self.compile_ast(rhs, bytecode, function);
+
+            function.add_opcode(OpCode::Mov {
+                dst: dst.clone(),
+                src: rhs,
+            });
+
+            function.add_opcode(OpCode::Label {
+                label_id: end_label,
+            });
+
+            return;
+        }
+
+        // Pointers are scaled by the size of what they point to, so they count in elements
+        if let (DataType::Ref(_), TokenKind::Add | TokenKind::Sub) = (&lhs.data_type, &oper.kind) {
+            self.compile_pointer_infix(oper, lhs, rhs, dst, bytecode, function);
+
+            return;
+        }
+
+        let division_check = self.options.division_checks
+            && matches!(oper.kind, TokenKind::Div | TokenKind::Mod)
+            && rhs.data_type.is_integer()
+            && !self.is_assumed_nonzero(rhs);
+
+        let lhs = self.compile_ast(lhs, bytecode, function);
+        let rhs = self.compile_ast(rhs, bytecode, function);
+
+        if division_check {
+            Self::compile_division_check(function, rhs.clone());
+        }
+
+        match oper.kind {
+            TokenKind::Add => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Add {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::Sub => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Sub {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::Mul => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Mul {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::Div => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Div {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::Mod => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Mod {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::BitAnd => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::And {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::BitOr => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Or {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::BitXor => {
+                function.add_opcode(OpCode::Mov {
+                    dst: dst.clone(),
+                    src: lhs,
+                });
+
+                function.add_opcode(OpCode::Xor {
+                    dst: dst.clone(),
+                    src: rhs,
+                });
+            }
+            TokenKind::Equals => {
+                function.add_opcode(OpCode::SetIfEqual {
+                    dst: dst.clone(),
+                    lhs,
+                    rhs,
+                });
+            }
+            TokenKind::NotEquals => {
+                function.add_opcode(OpCode::SetIfNotEqual {
+                    dst: dst.clone(),
+                    lhs,
+                    rhs,
+                });
+            }
+            TokenKind::Greater => {
+                function.add_opcode(OpCode::SetIfGreater {
+                    dst: dst.clone(),
+                    lhs,
+                    rhs,
+                });
+            }
+            TokenKind::Less => {
+                function.add_opcode(OpCode::SetIfLess {
+                    dst: dst.clone(),
+                    lhs,
+                    rhs,
+                });
+            }
+            TokenKind::GreaterOrEqual => {
+                function.add_opcode(OpCode::SetIfGreaterOrEqual {
+                    dst: dst.clone(),
+                    lhs,
+                    rhs,
+                });
+            }
+            TokenKind::LessOrEqual => {
+                function.add_opcode(OpCode::SetIfLessOrEqual {
+                    dst: dst.clone(),
+                    lhs,
+                    rhs,
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // `pointer +- n * size`, or `(pointer - pointer) / size` which is signed since either can come first
+    fn compile_pointer_infix(
+        &mut self,
+        oper: &Token<'src>,
+        lhs: &'src Ast<'src>,
+        rhs: &'src Ast<'src>,
+        dst: Argument<'src>,
+        bytecode: &mut ByteCode<'src>,
+        function: &mut Function<'src>,
+    ) {
+        let DataType::Ref(ref deref) = lhs.data_type else {
+            unreachable!("Only pointers are scaled");
+        };
+
+        let is_difference = rhs.data_type == lhs.data_type;
+
+        let distance = Argument::Register(function.add_register(if is_difference {
+            DataType::Int(IntType::S64)
+        } else {
+            DataType::Int(IntType::U64)
+        }));
+        let size = Argument::Constant {
+            value: deref.size() as u64,
+            data_type: function.argument_data_type(&distance).clone(),
+        };
+
+        let lhs = self.compile_ast(lhs, bytecode, function);
+        let rhs = self.compile_ast(rhs, bytecode, function);
+
+        function.add_opcode(OpCode::Mov {
+            dst: distance.clone(),
+            src: rhs,
+        });
+
+        if !is_difference {
+            function.add_opcode(OpCode::Mul {
+                dst: distance.clone(),
+                src: size.clone(),
+            });
+        }
+
+        function.add_opcode(OpCode::Mov {
+            dst: dst.clone(),
+            src: lhs,
+        });
+
+        if oper.kind == TokenKind::Add {
+            function.add_opcode(OpCode::Add { dst, src: distance });
+        } else {
+            function.add_opcode(OpCode::Sub {
+                dst: dst.clone(),
+                src: distance,
+            });
+
+            if is_difference {
+                function.add_opcode(OpCode::Div { dst, src: size });
+            }
+        }
+    }
+
+    // Like `compile_ast`, but the result ends up in `dst`. Blocks and conditionals
+    // pass it down to their last expression, so it's written there directly
+    fn compile_ast_into(
+        &mut self,
+        ast: &'src Ast<'src>,
+        dst: Argument<'src>,
+        bytecode: &mut ByteCode<'src>,
+        function: &mut Function<'src>,
+    ) {
+        match ast.kind {
+            AstKind::Infix {
+                ref oper,
+                ref lhs,
+                ref rhs,
+            } => self.compile_infix(oper, lhs, rhs, dst, bytecode, function),
+            AstKind::Block {
+                scope_id,
+                ref statements,
+                ref positions,
+            } => {
+                self.symbol_table.enter_scope(scope_id);
+
+                let assumed_bounds = self.assumed_bounds.clone();
+                let assumed_nonzero = self.assumed_nonzero.clone();
+
+                for (n, statement) in statements.iter().enumerate() {
+                    // Declarations of anything but variables have no code in this function
+                    let has_code = !matches!(
+                        statement.kind,
+                        AstKind::FunctionDeclaration { .. }
+                            | AstKind::GenericFunctionDeclaration { .. }
+                            | AstKind::ExternDeclaration { .. }
+                            | AstKind::StructDeclaration { .. }
+                            | AstKind::EnumDeclaration { .. }
+                            | AstKind::ConstantDeclaration { .. }
+                            | AstKind::StaticDeclaration { .. }
+                    );
+
+                    if let (true, true, Some(position)) =
+                        (self.options.source_lines, has_code, positions.get(n))
+                    {
+                        function.add_opcode(OpCode::SourceLine {
+                            line: position.line,
+                            text: position.line_text.trim(),
+                        });
+                    }
+
+                    self.assume(statement, &statements[n + 1..], function);
+
+                    if n + 1 == statements.len() && ast.data_type != DataType::Void {
+                        self.compile_ast_into(statement, dst.clone(), bytecode, function);
+                    } else {
+                        self.compile_ast(statement, bytecode, function);
+                    }
+                }
+
+                self.assumed_bounds = assumed_bounds;
+                self.assumed_nonzero = assumed_nonzero;
+
+                self.symbol_table.leave_scope();
+            }
+            AstKind::IfStatement {
+                ref condition,
+                ref if_block,
+                ref else_block,
+            } => {
+                let condition = self.compile_ast(condition, bytecode, function);
+
+                let else_label = function.add_label();
+                let end_label = function.add_label();
+
+                function.add_opcode(OpCode::GotoIfZero {
+                    condition,
+                    label_id: else_label,
+                });
+
+                if ast.data_type == DataType::Void {
+                    self.compile_ast(if_block, bytecode, function);
+                } else {
+                    self.compile_ast_into(if_block, dst.clone(), bytecode, function);
+                }
+
+                function.add_opcode(OpCode::Goto {
+                    label_id: end_label,
+                });
+
+                function.add_opcode(OpCode::Label {
+                    label_id: else_label,
+                });
+
+                if let Some(else_block) = else_block {
+                    if ast.data_type == DataType::Void {
+                        self.compile_ast(else_block, bytecode, function);
+                    } else {
+                        self.compile_ast_into(else_block, dst.clone(), bytecode, function);
+                    }
+                }
+
+                function.add_opcode(OpCode::Label {
+                    label_id: end_label,
+                });
+            }
+            // A comparison for every arm, each one skips to the next when it isn't equal
+            AstKind::Match {
+                ref scrutinee,
+                ref arms,
+                ref default,
+            } => {
+                let value = Argument::Register(function.add_register(scrutinee.data_type.clone()));
+                let equal = Argument::Register(function.add_register(DataType::Bool));
+
+                let scrutinee = self.compile_ast(scrutinee, bytecode, function);
+
+                function.add_opcode(OpCode::Mov {
+                    dst: value.clone(),
+                    src: scrutinee,
+                });
+
+                let end_label = function.add_label();
+
+                for arm in arms {
+                    let next_label = function.add_label();
+
+                    let pattern = self.compile_ast(&arm.pattern, bytecode, function);
+
+                    function.add_opcode(OpCode::SetIfEqual {
+                        dst: equal.clone(),
+                        lhs: value.clone(),
+                        rhs: pattern,
+                    });
+
+                    function.add_opcode(OpCode::GotoIfZero {
+                        condition: equal.clone(),
+                        label_id: next_label,
+                    });
+
+                    if ast.data_type == DataType::Void {
+                        self.compile_ast(&arm.body, bytecode, function);
+                    } else {
+                        self.compile_ast_into(&arm.body, dst.clone(), bytecode, function);
+                    }
+
+                    function.add_opcode(OpCode::Goto {
+                        label_id: end_label,
+                    });
+
+                    function.add_opcode(OpCode::Label {
+                        label_id: next_label,
+                    });
+                }
+
+                if ast.data_type == DataType::Void {
+                    self.compile_ast(default, bytecode, function);
+                } else {
+                    self.compile_ast_into(default, dst, bytecode, function);
+                }
+
+                function.add_opcode(OpCode::Label {
+                    label_id: end_label,
+                });
+            }
+            _ => {
+                let src = self.compile_ast(ast, bytecode, function);
+
+                function.add_opcode(OpCode::Mov { dst, src });
+            }
+        }
+    }
+
+    // The string is built in a global buffer of its own, big enough for the longest text any of the
+    // values could turn into, followed by a terminating zero like the literals have. It outlives the
+    // function, so it can be returned, and it is built again in the same place each time the
+    // literal is evaluated
+    fn compile_interpolation(
+        &mut self,
+        pieces: &'src [Ast<'src>],
+        bytecode: &mut ByteCode<'src>,
+        function: &mut Function<'src>,
+    ) -> Argument<'src> {
+        let size = |value| Argument::Constant {
+            value,
+            data_type: DataType::Int(IntType::U64),
+        };
+
+        // Each digit is written as a whole integer, so the last one of a `U64` spills over by 7 bytes,
+        // and the terminating zero comes after that
+        let mut capacity = 8;
+        let mut values = Vec::new();
+
+        for piece in pieces {
+            let value = self.compile_ast(piece, bytecode, function);
+
+            let length = match (piece.string_literal(), &piece.data_type) {
+                (Some(text), _) => {
+                    capacity += text.len();
+
+                    Some(size(text.len() as u64))
+                }
+                (_, DataType::Bool) => {
+                    capacity += "false".len();
+
+                    None
+                }
+                (_, DataType::Char) => {
+                    capacity += 1;
+
+                    None
+                }
+                // The widest value has as many digits as the largest one, and maybe a sign
+                (_, DataType::Int(int_type)) => {
+                    capacity +=
+                        int_type.max_value().to_string().len() + int_type.is_signed() as usize;
+
+                    None
+                }
+                _ => unreachable!("Other strings could be any length, so they can't be interpolated"),
+            };
+
+            values.push((&piece.data_type, value, length));
+        }
+
+        let global_id = bytecode.add_global(Global {
+            data_type: DataType::Array {
+                element: Box::new(DataType::Int(IntType::U8)),
+                length: capacity,
+            },
+            value: None,
+        });
+        let buffer = Argument::Register(
+            function.add_register(DataType::Ref(Box::new(DataType::Int(IntType::U8)))),
+        );
+        let cursor = Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+
+        function.add_opcode(OpCode::Mov {
+            dst: buffer.clone(),
+            src: Argument::Symbol {
+                name: ByteCode::global_symbol_name(global_id),
+                data_type: DataType::Ref(Box::new(DataType::Int(IntType::U8))),
+            },
+        });
+
+        function.add_opcode(OpCode::Mov {
+            dst: cursor.clone(),
+            src: size(0),
+        });
+
+        for (data_type, value, length) in values {
+            match (data_type, length) {
+                (_, Some(length)) => {
+                    Self::compile_append(function, buffer.clone(), cursor.clone(), value, length)
+                }
+                (DataType::Bool, None) => {
+                    let false_label = function.add_label();
+                    let end_label = function.add_label();
+
+                    function.add_opcode(OpCode::GotoIfZero {
+                        condition: value,
+                        label_id: false_label,
+                    });
+
+                    let [true_text, false_text] = [b"true".as_slice(), b"false"].map(|text| {
+                        (
+                            Argument::Symbol {
+                                name: ByteCode::string_symbol_name(bytecode.add_string(text)),
+                                data_type: DataType::Ref(Box::new(DataType::Int(IntType::U8))),
+                            },
+                            size(text.len() as u64),
+                        )
+                    });
+
+                    Self::compile_append(
+                        function,
+                        buffer.clone(),
+                        cursor.clone(),
+                        true_text.0,
+                        true_text.1,
+                    );
+
+                    function.add_opcode(OpCode::Goto {
+                        label_id: end_label,
+                    });
+
+                    function.add_opcode(OpCode::Label {
+                        label_id: false_label,
+                    });
+
+                    Self::compile_append(
+                        function,
+                        buffer.clone(),
+                        cursor.clone(),
+                        false_text.0,
+                        false_text.1,
+                    );
+
+                    function.add_opcode(OpCode::Label {
+                        label_id: end_label,
+                    });
+                }
+                (DataType::Int(int_type), None) => Self::compile_integer_text(
+                    function,
+                    buffer.clone(),
+                    cursor.clone(),
+                    value,
+                    *int_type,
+                ),
+                (DataType::Char, None) => {
+                    function.add_opcode(OpCode::SetIndex {
+                        dst: buffer.clone(),
+                        src: value,
+                        index: cursor.clone(),
+                    });
+
+                    function.add_opcode(OpCode::Add {
+                        dst: cursor.clone(),
+                        src: size(1),
+                    });
+                }
+                _ => unreachable!(
+                    "Only integers, booleans, characters and strings can be interpolated"
+                ),
+            }
+        }
+
+        function.add_opcode(OpCode::SetIndex {
+            dst: buffer.clone(),
+            src: Argument::Constant {
+                value: 0,
+                data_type: DataType::Int(IntType::U8),
+            },
+            index: cursor,
+        });
+
+        buffer
+    }
+
+    // Counts the bytes up to the terminating zero
+    fn compile_string_length(
+        function: &mut Function<'src>,
+        string: Argument<'src>,
+    ) -> Argument<'src> {
+        let length = Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+        let byte = Argument::Register(function.add_register(DataType::Int(IntType::U8)));
+
+        let start_label = function.add_label();
+        let end_label = function.add_label();
+
+        function.add_opcode(OpCode::Mov {
+            dst: length.clone(),
+            src: Argument::Constant {
+                value: 0,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+
+        function.add_opcode(OpCode::Label {
+            label_id: start_label,
+        });
+
+        function.add_opcode(OpCode::Index {
+            dst: byte.clone(),
+            src: string,
+            index: length.clone(),
+        });
+
+        function.add_opcode(OpCode::GotoIfZero {
+            condition: byte,
+            label_id: end_label,
+        });
+
+        function.add_opcode(OpCode::Add {
+            dst: length.clone(),
+            src: Argument::Constant {
+                value: 1,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+
+        function.add_opcode(OpCode::Goto {
+            label_id: start_label,
+        });
+
+        function.add_opcode(OpCode::Label {
+            label_id: end_label,
+        });
+
+        length
+    }
+
+    // Copies `length` bytes of `text` to `buffer + cursor` and moves the cursor past them
+    fn compile_append(
+        function: &mut Function<'src>,
+        buffer: Argument<'src>,
+        cursor: Argument<'src>,
+        text: Argument<'src>,
+        length: Argument<'src>,
+    ) {
+        let address = Argument::Register(
+            function.add_register(DataType::Ref(Box::new(DataType::Int(IntType::U8)))),
+        );
+
+        function.add_opcode(OpCode::Mov {
+            dst: address.clone(),
+            src: buffer,
+        });
+
+        function.add_opcode(OpCode::Add {
+            dst: address.clone(),
+            src: cursor.clone(),
+        });
+
+        function.add_opcode(OpCode::Copy {
+            dst: address,
+            src: text,
+            size: length.clone(),
+        });
+
+        function.add_opcode(OpCode::Add {
+            dst: cursor,
+            src: length,
+        });
+    }
+
+    // Integers are converted to text at runtime by the `print_integer` builtin, booleans pick
+    // between two strings, characters are written from a copy on the stack, and strings are
+    // written up to their terminating zero
+    fn compile_print_value(
+        &mut self,
+        value: &'src Ast<'src>,
+        bytecode: &mut ByteCode<'src>,
+        function: &mut Function<'src>,
+    ) {
+        let compiled = self.compile_ast(value, bytecode, function);
+
+        match value.data_type {
+            DataType::Int(int_type) => {
+                let compiled = Self::compile_widen(function, compiled, int_type);
+
+                function.add_opcode(OpCode::Call {
+                    dst: Argument::VoidRegister,
+                    lhs: self.compile_builtin("print_integer"),
+                    // Stored last to first
+                    arguments: vec![
+                        Argument::Constant {
+                            value: int_type.is_signed() as u64,
+                            data_type: DataType::Bool,
+                        },
+                        compiled,
+                    ],
+                });
+            }
+            DataType::Bool => {
+                let false_label = function.add_label();
+                let end_label = function.add_label();
+
+                function.add_opcode(OpCode::GotoIfZero {
+                    condition: compiled,
+                    label_id: false_label,
+                });
+
+                self.compile_print_text(function, bytecode, b"true");
+
+                function.add_opcode(OpCode::Goto {
+                    label_id: end_label,
+                });
+
+                function.add_opcode(OpCode::Label {
+                    label_id: false_label,
+                });
+
+                self.compile_print_text(function, bytecode, b"false");
+
+                function.add_opcode(OpCode::Label {
+                    label_id: end_label,
+                });
+            }
+            DataType::Char => {
+                let byte = Argument::Register(function.add_register(DataType::Char));
+                let text = Argument::Register(
+                    function.add_register(DataType::Ref(Box::new(DataType::Int(IntType::U8)))),
+                );
+
+                function.add_opcode(OpCode::Mov {
+                    dst: byte.clone(),
+                    src: compiled,
+                });
 
-pub struct Compiler<'src> {
-    symbol_table: SymbolTable<'src>,
-    variable_registers: HashMap<SymbolID<'src>, Argument<'src>>,
-}
+                function.add_opcode(OpCode::Ref {
+                    dst: text.clone(),
+                    src: byte,
+                });
 
-impl<'src> Compiler<'src> {
-    pub fn compile(ast: &'src Ast<'src>, symbol_table: SymbolTable<'src>) -> ByteCode<'src> {
-        let mut bytecode = ByteCode::new();
+                self.compile_write(
+                    function,
+                    text,
+                    Argument::Constant {
+                        value: 1,
+                        data_type: DataType::Int(IntType::U64),
+                    },
+                );
+            }
+            _ => {
+                let length = Self::compile_string_length(function, compiled.clone());
 
-        let mut compiler = Self {
-            symbol_table,
-            variable_registers: HashMap::new(),
+                self.compile_write(function, compiled, length);
+            }
+        }
+    }
+
+    // Calls the `write` builtin
+    fn compile_write(
+        &self,
+        function: &mut Function<'src>,
+        text: Argument<'src>,
+        length: Argument<'src>,
+    ) {
+        function.add_opcode(OpCode::Call {
+            dst: Argument::VoidRegister,
+            lhs: self.compile_builtin("write"),
+            // Stored last to first
+            arguments: vec![length, text],
+        });
+    }
+
+    // The typechecking phase made sure the runtime provides it
+    fn compile_builtin(&self, name: &str) -> Argument<'src> {
+        let Some(builtin) = self.symbol_table.get_builtin(name) else {
+            unreachable!("`{name}` should have been caught missing in the typechecking phase")
         };
 
-        let mut main = Function::new("@main", ast.data_type.clone(), Vec::new());
+        Argument::Symbol {
+            name: name.to_string(),
+            data_type: builtin.data_type.clone(),
+        }
+    }
 
-        let data = compiler.compile_ast(ast, &mut bytecode, &mut main);
+    fn compile_print_text(
+        &self,
+        function: &mut Function<'src>,
+        bytecode: &mut ByteCode<'src>,
+        text: &'src [u8],
+    ) {
+        let string = Argument::Symbol {
+            name: ByteCode::string_symbol_name(bytecode.add_string(text)),
+            data_type: DataType::Ref(Box::new(DataType::Int(IntType::U8))),
+        };
 
-        main.add_opcode(OpCode::Mov {
-            dst: Argument::ReturnValue,
-            src: data,
+        self.compile_write(
+            function,
+            string,
+            Argument::Constant {
+                value: text.len() as u64,
+                data_type: DataType::Int(IntType::U64),
+            },
+        );
+    }
+
+    // Integers can't be converted either, so the value is stored into the bottom of a zeroed `U64`,
+    // and the bits above it are filled in when it's negative
+    fn compile_widen(
+        function: &mut Function<'src>,
+        value: Argument<'src>,
+        int_type: IntType,
+    ) -> Argument<'src> {
+        let wide = Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+
+        if int_type.size() == 8 {
+            function.add_opcode(OpCode::Mov {
+                dst: wide.clone(),
+                src: value,
+            });
+
+            return wide;
+        }
+
+        let address = Argument::Register(
+            function.add_register(DataType::Ref(Box::new(DataType::Int(IntType::U64)))),
+        );
+
+        function.add_opcode(OpCode::Mov {
+            dst: wide.clone(),
+            src: Argument::Constant {
+                value: 0,
+                data_type: DataType::Int(IntType::U64),
+            },
         });
 
-        bytecode.add_function(main);
+        function.add_opcode(OpCode::Ref {
+            dst: address.clone(),
+            src: wide.clone(),
+        });
 
-        bytecode
+        function.add_opcode(OpCode::SetIndex {
+            dst: address,
+            src: value.clone(),
+            index: Argument::Constant {
+                value: 0,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+
+        if int_type.is_signed() {
+            let negative = Argument::Register(function.add_register(DataType::Bool));
+            let positive_label = function.add_label();
+
+            function.add_opcode(OpCode::SetIfLess {
+                dst: negative.clone(),
+                lhs: value,
+                rhs: Argument::Constant {
+                    value: 0,
+                    data_type: DataType::Int(int_type),
+                },
+            });
+
+            function.add_opcode(OpCode::GotoIfZero {
+                condition: negative,
+                label_id: positive_label,
+            });
+
+            function.add_opcode(OpCode::Or {
+                dst: wide.clone(),
+                src: Argument::Constant {
+                    value: u64::MAX << (int_type.size() * 8),
+                    data_type: DataType::Int(IntType::U64),
+                },
+            });
+
+            function.add_opcode(OpCode::Label {
+                label_id: positive_label,
+            });
+        }
+
+        wide
+    }
+
+    // Finds the power of ten of the most significant digit, then writes the digits from there down.
+    // Integers can't be narrowed, so each digit is stored as a whole `int_type` whose lowest byte is
+    // the character, and the bytes after it are overwritten by whatever comes next
+    fn compile_integer_text(
+        function: &mut Function<'src>,
+        buffer: Argument<'src>,
+        cursor: Argument<'src>,
+        value: Argument<'src>,
+        int_type: IntType,
+    ) {
+        let data_type = DataType::Int(int_type);
+        let constant = |value| Argument::Constant {
+            value,
+            data_type: data_type.clone(),
+        };
+
+        let power = Argument::Register(function.add_register(data_type.clone()));
+        let digit = Argument::Register(function.add_register(data_type.clone()));
+        let negative = Argument::Register(function.add_register(DataType::Bool));
+
+        let power_label = function.add_label();
+        let digits_label = function.add_label();
+        let positive_label = function.add_label();
+
+        function.add_opcode(OpCode::Mov {
+            dst: power.clone(),
+            src: constant(1),
+        });
+
+        // `value / power / 10 != 0`, which never lets `power` get bigger than the value
+        function.add_opcode(OpCode::Label {
+            label_id: power_label,
+        });
+
+        function.add_opcode(OpCode::Mov {
+            dst: digit.clone(),
+            src: value.clone(),
+        });
+
+        function.add_opcode(OpCode::Div {
+            dst: digit.clone(),
+            src: power.clone(),
+        });
+
+        function.add_opcode(OpCode::Div {
+            dst: digit.clone(),
+            src: constant(10),
+        });
+
+        function.add_opcode(OpCode::GotoIfZero {
+            condition: digit.clone(),
+            label_id: digits_label,
+        });
+
+        function.add_opcode(OpCode::Mul {
+            dst: power.clone(),
+            src: constant(10),
+        });
+
+        function.add_opcode(OpCode::Goto {
+            label_id: power_label,
+        });
+
+        function.add_opcode(OpCode::Label {
+            label_id: digits_label,
+        });
+
+        if int_type.is_signed() {
+            function.add_opcode(OpCode::SetIfLess {
+                dst: negative.clone(),
+                lhs: value.clone(),
+                rhs: constant(0),
+            });
+
+            function.add_opcode(OpCode::GotoIfZero {
+                condition: negative.clone(),
+                label_id: positive_label,
+            });
+
+            function.add_opcode(OpCode::SetIndex {
+                dst: buffer.clone(),
+                src: Argument::Constant {
+                    value: b'-'.into(),
+                    data_type: DataType::Int(IntType::U8),
+                },
+                index: cursor.clone(),
+            });
+
+            function.add_opcode(OpCode::Add {
+                dst: cursor.clone(),
+                src: Argument::Constant {
+                    value: 1,
+                    data_type: DataType::Int(IntType::U64),
+                },
+            });
+
+            function.add_opcode(OpCode::Label {
+                label_id: positive_label,
+            });
+        }
+
+        let digit_label = function.add_label();
+        let digit_positive_label = function.add_label();
+
+        function.add_opcode(OpCode::Label {
+            label_id: digit_label,
+        });
+
+        function.add_opcode(OpCode::Mov {
+            dst: digit.clone(),
+            src: value,
+        });
+
+        function.add_opcode(OpCode::Div {
+            dst: digit.clone(),
+            src: power.clone(),
+        });
+
+        function.add_opcode(OpCode::Mod {
+            dst: digit.clone(),
+            src: constant(10),
+        });
+
+        // The remainder of a negative value is negative too
+        if int_type.is_signed() {
+            function.add_opcode(OpCode::GotoIfZero {
+                condition: negative,
+                label_id: digit_positive_label,
+            });
+
+            function.add_opcode(OpCode::Negate { dst: digit.clone() });
+
+            function.add_opcode(OpCode::Label {
+                label_id: digit_positive_label,
+            });
+        }
+
+        function.add_opcode(OpCode::Add {
+            dst: digit.clone(),
+            src: constant(b'0'.into()),
+        });
+
+        function.add_opcode(OpCode::SetIndex {
+            dst: buffer,
+            src: digit,
+            index: cursor.clone(),
+        });
+
+        function.add_opcode(OpCode::Add {
+            dst: cursor,
+            src: Argument::Constant {
+                value: 1,
+                data_type: DataType::Int(IntType::U64),
+            },
+        });
+
+        function.add_opcode(OpCode::Div {
+            dst: power.clone(),
+            src: constant(10),
+        });
+
+        function.add_opcode(OpCode::GotoIfNotZero {
+            condition: power,
+            label_id: digit_label,
+        });
     }
 
     pub fn compile_ast(
@@ -43,8 +1427,22 @@ impl<'src> Compiler<'src> {
     ) -> Argument<'src> {
         match &ast.kind {
             AstKind::Node { ref token } => match &token.kind {
-                TokenKind::Number(value) => Argument::Constant {
-                    value: *value,
+                // Negative numbers are kept as their two's complement, cut down to the size of their type
+                TokenKind::Number(value, _) => Argument::Constant {
+                    value: match ast.data_type {
+                        DataType::Int(int_type) => {
+                            *value as u64 & (u64::MAX >> (64 - int_type.size() * 8))
+                        }
+                        _ => *value as u64,
+                    },
+                    data_type: ast.data_type.clone(),
+                },
+                // Floats are stored as their bit pattern
+                TokenKind::Float(value) => Argument::Constant {
+                    value: match ast.data_type {
+                        DataType::Float(FloatType::F32) => (*value as f32).to_bits().into(),
+                        _ => value.to_bits(),
+                    },
                     data_type: ast.data_type.clone(),
                 },
                 TokenKind::Ident => {
@@ -64,6 +1462,11 @@ impl<'src> Compiler<'src> {
                     name: ByteCode::string_symbol_name(bytecode.add_string(text)),
                     data_type: ast.data_type.clone(),
                 },
+                // Either a `Char`, or a `U8` where it stands for its byte
+                TokenKind::Char(value) => Argument::Constant {
+                    value: (*value).into(),
+                    data_type: ast.data_type.clone(),
+                },
                 TokenKind::True => Argument::Constant {
                     value: 1,
                     data_type: DataType::Bool,
@@ -81,118 +1484,24 @@ impl<'src> Compiler<'src> {
             } => {
                 let dst = Argument::Register(function.add_register(ast.data_type.clone()));
 
-                let lhs = self.compile_ast(lhs, bytecode, function);
-                let rhs = self.compile_ast(rhs, bytecode, function);
-
-                match oper.kind {
-                    TokenKind::Add => {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: lhs,
-                        });
-
-                        function.add_opcode(OpCode::Add {
-                            dst: dst.clone(),
-                            src: rhs,
-                        });
-                    }
-                    TokenKind::Sub => {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: lhs,
-                        });
-
-                        function.add_opcode(OpCode::Sub {
-                            dst: dst.clone(),
-                            src: rhs,
-                        });
-                    }
-                    TokenKind::Mul => {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: lhs,
-                        });
-
-                        function.add_opcode(OpCode::Mul {
-                            dst: dst.clone(),
-                            src: rhs,
-                        });
-                    }
-                    TokenKind::Div => {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: lhs,
-                        });
-
-                        function.add_opcode(OpCode::Div {
-                            dst: dst.clone(),
-                            src: rhs,
-                        });
-                    }
-                    TokenKind::Mod => {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: lhs,
-                        });
-
-                        function.add_opcode(OpCode::Mod {
-                            dst: dst.clone(),
-                            src: rhs,
-                        });
-                    }
-                    TokenKind::Equals => {
-                        function.add_opcode(OpCode::SetIfEqual {
-                            dst: dst.clone(),
-                            lhs,
-                            rhs,
-                        });
-                    }
-                    TokenKind::NotEquals => {
-                        function.add_opcode(OpCode::SetIfNotEqual {
-                            dst: dst.clone(),
-                            lhs,
-                            rhs,
-                        });
-                    }
-                    TokenKind::Greater => {
-                        function.add_opcode(OpCode::SetIfGreater {
-                            dst: dst.clone(),
-                            lhs,
-                            rhs,
-                        });
-                    }
-                    TokenKind::Less => {
-                        function.add_opcode(OpCode::SetIfLess {
-                            dst: dst.clone(),
-                            lhs,
-                            rhs,
-                        });
-                    }
-                    TokenKind::GreaterOrEqual => {
-                        function.add_opcode(OpCode::SetIfGreaterOrEqual {
-                            dst: dst.clone(),
-                            lhs,
-                            rhs,
-                        });
-                    }
-                    TokenKind::LessOrEqual => {
-                        function.add_opcode(OpCode::SetIfLessOrEqual {
-                            dst: dst.clone(),
-                            lhs,
-                            rhs,
-                        });
-                    }
-                    _ => unreachable!(),
-                }
+                self.compile_infix(oper, lhs, rhs, dst.clone(), bytecode, function);
 
                 dst
             }
+            // Aggregates can't be loaded into a register, so they are used in place, like the elements of arrays
+            AstKind::Index { ref lhs, .. }
+                if ast.data_type.is_aggregate() || matches!(lhs.data_type, DataType::Array { .. }) =>
+            {
+                self.compile_place(ast, bytecode, function)
+            }
             AstKind::Index { ref lhs, ref index } => {
                 let dst = Argument::Register(function.add_register(ast.data_type.clone()));
 
                 let index = self.compile_ast(index, bytecode, function);
                 let lhs = self.compile_ast(lhs, bytecode, function);
 
+                let index = Self::compile_scaled_index(function, index, ast.data_type.size());
+
                 function.add_opcode(OpCode::Index {
                     dst: dst.clone(),
                     src: lhs,
@@ -201,8 +1510,50 @@ impl<'src> Compiler<'src> {
 
                 dst
             }
+            // Each element is stored right after the one before it, through a pointer that walks the array
+            AstKind::ArrayLiteral { ref elements } => {
+                let DataType::Array { ref element, .. } = ast.data_type else {
+                    unreachable!("An array literal should have an array type");
+                };
+
+                let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+                let address = Argument::Register(function.add_register(DataType::Ref(element.clone())));
+
+                function.add_opcode(OpCode::Ref {
+                    dst: address.clone(),
+                    src: dst.clone(),
+                });
+
+                for (n, value) in elements.iter().enumerate() {
+                    if n > 0 {
+                        function.add_opcode(OpCode::Add {
+                            dst: address.clone(),
+                            src: Argument::Constant {
+                                value: element.size() as u64,
+                                data_type: DataType::Int(IntType::U64),
+                            },
+                        });
+                    }
+
+                    let value = self.compile_ast(value, bytecode, function);
+
+                    if element.is_aggregate() {
+                        Self::compile_copy(function, Argument::Deref(Box::new(address.clone())), value);
+                    } else {
+                        function.add_opcode(OpCode::Mov {
+                            dst: Argument::Deref(Box::new(address.clone())),
+                            src: value,
+                        });
+                    }
+                }
+
+                dst
+            }
+            AstKind::Interpolation { ref pieces } => {
+                self.compile_interpolation(pieces, bytecode, function)
+            }
             AstKind::GetField { ref lhs, name } => {
-                let lhs = self.compile_ast(lhs, bytecode, function);
+                let lhs = self.compile_place(lhs, bytecode, function);
 
                 Argument::StructField {
                     data: Box::new(lhs),
@@ -238,6 +1589,20 @@ impl<'src> Compiler<'src> {
 
                     dst
                 }
+                TokenKind::BitNot => {
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+
+                    let node = self.compile_ast(node, bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: dst.clone(),
+                        src: node.clone(),
+                    });
+
+                    function.add_opcode(OpCode::BitNot { dst: dst.clone() });
+
+                    dst
+                }
                 TokenKind::Hash => {
                     let dst = Argument::Register(function.add_register(ast.data_type.clone()));
 
@@ -259,6 +1624,12 @@ impl<'src> Compiler<'src> {
             },
             AstKind::Assign { ref lhs, ref rhs } => {
                 match &lhs.kind {
+                    _ if lhs.data_type.is_aggregate() => {
+                        let lhs = self.compile_place(lhs, bytecode, function);
+                        let rhs = self.compile_ast(rhs, bytecode, function);
+
+                        Self::compile_copy(function, lhs, rhs);
+                    }
                     AstKind::Prefix {
                         oper:
                             Token {
@@ -276,19 +1647,24 @@ impl<'src> Compiler<'src> {
                             index: Argument::VoidRegister,
                         });
                     }
-                    AstKind::Index { ref lhs, ref index } => {
-                        let lhs = self.compile_ast(lhs, bytecode, function);
+                    AstKind::Index {
+                        lhs: ref pointer,
+                        ref index,
+                    } if pointer.data_type.is_reference() => {
+                        let pointer = self.compile_ast(pointer, bytecode, function);
                         let index = self.compile_ast(index, bytecode, function);
                         let rhs = self.compile_ast(rhs, bytecode, function);
 
+                        let index = Self::compile_scaled_index(function, index, lhs.data_type.size());
+
                         function.add_opcode(OpCode::SetIndex {
-                            dst: lhs,
+                            dst: pointer,
                             src: rhs,
                             index,
                         });
                     }
                     _ => {
-                        let lhs = self.compile_ast(lhs, bytecode, function);
+                        let lhs = self.compile_place(lhs, bytecode, function);
                         let rhs = self.compile_ast(rhs, bytecode, function);
 
                         function.add_opcode(OpCode::Mov { dst: lhs, src: rhs });
@@ -297,30 +1673,14 @@ impl<'src> Compiler<'src> {
 
                 Argument::VoidRegister
             }
-            AstKind::Block {
-                scope_id,
-                ref statements,
-            } => {
-                self.symbol_table.enter_scope(*scope_id);
-
+            AstKind::Block { .. } | AstKind::IfStatement { .. } | AstKind::Match { .. } => {
                 let dst = if ast.data_type == DataType::Void {
                     Argument::VoidRegister
                 } else {
                     Argument::Register(function.add_register(ast.data_type.clone()))
                 };
 
-                for (n, statement) in statements.iter().enumerate() {
-                    let statement = self.compile_ast(statement, bytecode, function);
-
-                    if n + 1 == statements.len() && ast.data_type != DataType::Void {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: statement,
-                        });
-                    };
-                }
-
-                self.symbol_table.leave_scope();
+                self.compile_ast_into(ast, dst.clone(), bytecode, function);
 
                 dst
             }
@@ -328,28 +1688,62 @@ impl<'src> Compiler<'src> {
                 name,
                 ref data_type,
                 ref value,
+                alignment,
             }) => {
-                let variable = Argument::Register(function.add_register(data_type.clone()));
+                let variable = Argument::Register(match *alignment {
+                    Some(alignment) => function.add_aligned_register(data_type.clone(), alignment),
+                    None => function.add_register(data_type.clone()),
+                });
+
+                self.variable_registers.insert(
+                    self.symbol_table.get_symbol_id(name).expect(
+                        "Unreachable: this variable should have been defined in the parsing stage",
+                    ),
+                    variable.clone(),
+                );
+
+                if let Some(value) = value {
+                    let value = self.compile_ast(value, bytecode, function);
+
+                    if data_type.is_aggregate() {
+                        Self::compile_copy(function, variable, value);
+                    } else {
+                        function.add_opcode(OpCode::Mov {
+                            dst: variable,
+                            src: value,
+                        });
+                    }
+                }
+
+                Argument::VoidRegister
+            }
+            // Reads and writes go through its address, like they would for a dereferenced pointer
+            AstKind::StaticDeclaration {
+                name,
+                ref data_type,
+                value,
+            } => {
+                let global_id = bytecode.add_global(Global {
+                    data_type: data_type.clone(),
+                    value: *value,
+                });
 
                 self.variable_registers.insert(
                     self.symbol_table.get_symbol_id(name).expect(
                         "Unreachable: this variable should have been defined in the parsing stage",
                     ),
-                    variable.clone(),
+                    Argument::Deref(Box::new(Argument::Symbol {
+                        name: ByteCode::global_symbol_name(global_id),
+                        data_type: DataType::Ref(Box::new(data_type.clone())),
+                    })),
                 );
 
-                if let Some(value) = value {
-                    let value = self.compile_ast(value, bytecode, function);
-                    function.add_opcode(OpCode::Mov {
-                        dst: variable,
-                        src: value,
-                    });
-                }
-
                 Argument::VoidRegister
             }
             AstKind::FunctionDeclaration {
                 name,
+                attributes,
+                ref type_arguments,
                 scope_id,
                 ref return_type,
                 ref arguments,
@@ -361,8 +1755,20 @@ impl<'src> Compiler<'src> {
                     .map(|VariableDeclaration { data_type, .. }| data_type.clone())
                     .collect();
 
+                let name = if type_arguments.is_empty() {
+                    name.to_string()
+                } else {
+                    self.instance_name(name, type_arguments)
+                };
+
                 let mut function = Function::new(name, return_type.clone(), argument_types);
 
+                function.inline = attributes.inline;
+                function.cold = attributes.cold;
+
+                // Instances aren't declared where they are put, so they can't just leave their scope
+                let outer_scope_id = self.symbol_table.get_scope();
+
                 self.symbol_table.enter_scope(*scope_id);
 
                 for (n, declaration) in arguments.iter().enumerate() {
@@ -385,66 +1791,56 @@ impl<'src> Compiler<'src> {
 
                 bytecode.add_function(function);
 
-                self.symbol_table.leave_scope();
+                self.symbol_table.enter_scope(outer_scope_id);
 
                 Argument::VoidRegister
             }
-            AstKind::StructDeclaration { .. } => Argument::VoidRegister,
-            AstKind::IfStatement {
-                condition,
-                if_block,
-                else_block,
-            } => {
-                let dst = if ast.data_type == DataType::Void {
-                    Argument::VoidRegister
-                } else {
-                    Argument::Register(function.add_register(ast.data_type.clone()))
-                };
-
-                let condition = self.compile_ast(condition, bytecode, function);
-
-                let else_label = function.add_label();
-                let end_label = function.add_label();
-
-                function.add_opcode(OpCode::GotoIfZero {
-                    condition,
-                    label_id: else_label,
-                });
-
-                let if_block = self.compile_ast(if_block, bytecode, function);
+            AstKind::Return { ref value, .. } => {
+                if let Some(value) = value {
+                    let value = self.compile_ast(value, bytecode, function);
 
-                if ast.data_type != DataType::Void {
                     function.add_opcode(OpCode::Mov {
-                        dst: dst.clone(),
-                        src: if_block,
+                        dst: Argument::ReturnValue,
+                        src: value,
                     });
                 }
 
-                function.add_opcode(OpCode::Goto {
-                    label_id: end_label,
-                });
-
-                function.add_opcode(OpCode::Label {
-                    label_id: else_label,
-                });
-
-                if let Some(ref else_block) = else_block {
-                    let else_block = self.compile_ast(else_block, bytecode, function);
+                function.add_opcode(OpCode::Return);
 
-                    if ast.data_type != DataType::Void {
-                        function.add_opcode(OpCode::Mov {
-                            dst: dst.clone(),
-                            src: else_block,
-                        });
-                    }
+                // Never reached, but whatever uses it still needs something of the right type
+                if ast.data_type == DataType::Void {
+                    Argument::VoidRegister
+                } else {
+                    Argument::Register(function.add_register(ast.data_type.clone()))
                 }
+            }
+            AstKind::StructDeclaration { .. }
+            | AstKind::EnumDeclaration { .. }
+            | AstKind::ConstantDeclaration { .. }
+            | AstKind::GenericFunctionDeclaration { .. }
+            | AstKind::ExternDeclaration { .. } => Argument::VoidRegister,
+            AstKind::Error => unreachable!("Programs with errors are never compiled"),
+            // Variants are numbered in the order they're declared
+            AstKind::EnumVariant { variant, .. } => {
+                let DataType::Enum(ref enum_type) = ast.data_type else {
+                    unreachable!("Variants are always typed as their enumeration");
+                };
 
-                function.add_opcode(OpCode::Label {
-                    label_id: end_label,
-                });
-
-                dst
+                Argument::Constant {
+                    value: enum_type
+                        .variant(variant)
+                        .expect("Undefined variants are caught in the typechecking phase")
+                        as u64,
+                    data_type: ast.data_type.clone(),
+                }
             }
+            AstKind::Instance {
+                name,
+                ref type_arguments,
+            } => Argument::Symbol {
+                name: self.instance_name(name, type_arguments),
+                data_type: ast.data_type.clone(),
+            },
             AstKind::WhileLoop { condition, body } => {
                 let start_label = function.add_label();
                 let end_label = function.add_label();
@@ -495,6 +1891,444 @@ impl<'src> Compiler<'src> {
 
                 dst
             }
+            AstKind::Intrinsic {
+                intrinsic,
+                arguments,
+            } => match intrinsic {
+                Intrinsic::Breakpoint => {
+                    function.add_opcode(OpCode::Breakpoint);
+
+                    Argument::VoidRegister
+                }
+                Intrinsic::Trap => {
+                    function.add_opcode(OpCode::Trap);
+
+                    Argument::VoidRegister
+                }
+                Intrinsic::Rdtsc => {
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+
+                    function.add_opcode(OpCode::ReadCycleCounter { dst: dst.clone() });
+
+                    dst
+                }
+                Intrinsic::SourceLocation => {
+                    unreachable!("The location should have been turned into a string while parsing")
+                }
+                Intrinsic::Print | Intrinsic::PrintLine => {
+                    for argument in arguments {
+                        self.compile_print_value(argument, bytecode, function);
+                    }
+
+                    if *intrinsic == Intrinsic::PrintLine {
+                        self.compile_print_text(function, bytecode, b"\n");
+                    }
+
+                    Argument::VoidRegister
+                }
+                Intrinsic::Length => match arguments[0].string_literal_length() {
+                    Some(length) => Argument::Constant {
+                        value: length as u64,
+                        data_type: ast.data_type.clone(),
+                    },
+                    None => {
+                        let string = self.compile_ast(&arguments[0], bytecode, function);
+
+                        Self::compile_string_length(function, string)
+                    }
+                },
+                // The value is only used for its type, so it is never compiled
+                Intrinsic::SizeOfVal => Argument::Constant {
+                    value: arguments[0].data_type.size_aligned() as u64,
+                    data_type: ast.data_type.clone(),
+                },
+                // Neither is the array
+                Intrinsic::CountOf => {
+                    let DataType::Array { length, .. } = arguments[0].data_type else {
+                        unreachable!("This should be an array, it should have been caught in the typechecking phase")
+                    };
+
+                    Argument::Constant {
+                        value: length as u64,
+                        data_type: ast.data_type.clone(),
+                    }
+                }
+                // `(addr + n - 1) & -n` and `addr & -n`, where `-n` is the same as `!(n - 1)`
+                Intrinsic::AlignUp | Intrinsic::AlignDown => {
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+                    let mask =
+                        Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+                    let alignment = self.compile_ast(&arguments[1], bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: mask.clone(),
+                        src: alignment.clone(),
+                    });
+
+                    function.add_opcode(OpCode::Negate { dst: mask.clone() });
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: dst.clone(),
+                        src: pointer,
+                    });
+
+                    if *intrinsic == Intrinsic::AlignUp {
+                        function.add_opcode(OpCode::Add {
+                            dst: dst.clone(),
+                            src: alignment,
+                        });
+
+                        function.add_opcode(OpCode::Sub {
+                            dst: dst.clone(),
+                            src: Argument::Constant {
+                                value: 1,
+                                data_type: DataType::Int(IntType::U64),
+                            },
+                        });
+                    }
+
+                    function.add_opcode(OpCode::And {
+                        dst: dst.clone(),
+                        src: mask,
+                    });
+
+                    dst
+                }
+                // `addr & (n - 1) == 0`
+                Intrinsic::IsAligned => {
+                    let dst = Argument::Register(function.add_register(DataType::Bool));
+                    let remainder =
+                        Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+                    let mask =
+                        Argument::Register(function.add_register(DataType::Int(IntType::U64)));
+
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+                    let alignment = self.compile_ast(&arguments[1], bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: mask.clone(),
+                        src: alignment,
+                    });
+
+                    function.add_opcode(OpCode::Sub {
+                        dst: mask.clone(),
+                        src: Argument::Constant {
+                            value: 1,
+                            data_type: DataType::Int(IntType::U64),
+                        },
+                    });
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: remainder.clone(),
+                        src: pointer,
+                    });
+
+                    function.add_opcode(OpCode::And {
+                        dst: remainder.clone(),
+                        src: mask,
+                    });
+
+                    function.add_opcode(OpCode::SetIfEqual {
+                        dst: dst.clone(),
+                        lhs: remainder,
+                        rhs: Argument::Constant {
+                            value: 0,
+                            data_type: DataType::Int(IntType::U64),
+                        },
+                    });
+
+                    dst
+                }
+                Intrinsic::Alloc => {
+                    let DataType::Ref(ref deref) = ast.data_type else {
+                        unreachable!("The allocation should have been inferred to a reference in the typechecking phase")
+                    };
+
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+
+                    function.add_opcode(OpCode::Call {
+                        dst: dst.clone(),
+                        lhs: self.compile_builtin("malloc"),
+                        arguments: vec![Argument::Constant {
+                            value: deref.size_aligned() as u64,
+                            data_type: DataType::Int(IntType::U64),
+                        }],
+                    });
+
+                    dst
+                }
+                Intrinsic::Free => {
+                    let DataType::Ref(ref deref) = arguments[0].data_type else {
+                        unreachable!("This should be a reference, it should have been caught in the typechecking phase")
+                    };
+
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+
+                    // Call arguments are stored last to first
+                    function.add_opcode(OpCode::Call {
+                        dst: Argument::VoidRegister,
+                        lhs: self.compile_builtin("free"),
+                        arguments: vec![
+                            Argument::Constant {
+                                value: deref.size_aligned() as u64,
+                                data_type: DataType::Int(IntType::U64),
+                            },
+                            pointer,
+                        ],
+                    });
+
+                    Argument::VoidRegister
+                }
+                Intrinsic::Memcpy => {
+                    let dst = self.compile_ast(&arguments[0], bytecode, function);
+                    let src = self.compile_ast(&arguments[1], bytecode, function);
+                    let size = self.compile_ast(&arguments[2], bytecode, function);
+
+                    function.add_opcode(OpCode::Copy { dst, src, size });
+
+                    Argument::VoidRegister
+                }
+                // The condition is never evaluated, the block it's in remembers what it says
+                Intrinsic::Assume => Argument::VoidRegister,
+                Intrinsic::Null => Argument::Constant {
+                    value: 0,
+                    data_type: ast.data_type.clone(),
+                },
+                // Only the type changes, the byte stays the same
+                Intrinsic::ToChar | Intrinsic::ToByte => {
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+
+                    let value = self.compile_ast(&arguments[0], bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: dst.clone(),
+                        src: value,
+                    });
+
+                    dst
+                }
+                // Negated as the unsigned type of the same size, which overflow checks leave alone
+                Intrinsic::WrappingNeg => {
+                    let DataType::Int(int_type) = ast.data_type else {
+                        unreachable!("Only signed integers can be negated with wrapping");
+                    };
+
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+                    let unsigned = Argument::Register(
+                        function.add_register(DataType::Int(int_type.unsigned())),
+                    );
+
+                    let value = self.compile_ast(&arguments[0], bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: unsigned.clone(),
+                        src: value,
+                    });
+
+                    function.add_opcode(OpCode::Negate {
+                        dst: unsigned.clone(),
+                    });
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: dst.clone(),
+                        src: unsigned,
+                    });
+
+                    dst
+                }
+                // `pointer + count * size`, wrapping multiplication makes negative counts go backwards
+                Intrinsic::Offset => {
+                    let DataType::Ref(ref deref) = ast.data_type else {
+                        unreachable!("The offset should return a reference");
+                    };
+
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+                    let distance =
+                        Argument::Register(function.add_register(arguments[1].data_type.clone()));
+
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+                    let count = self.compile_ast(&arguments[1], bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: distance.clone(),
+                        src: count,
+                    });
+
+                    function.add_opcode(OpCode::Mul {
+                        dst: distance.clone(),
+                        src: Argument::Constant {
+                            value: deref.size() as u64,
+                            data_type: arguments[1].data_type.clone(),
+                        },
+                    });
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: dst.clone(),
+                        src: pointer,
+                    });
+
+                    function.add_opcode(OpCode::Add {
+                        dst: dst.clone(),
+                        src: distance,
+                    });
+
+                    dst
+                }
+                // Bytes are one wide, so the index doesn't need scaling
+                Intrinsic::ByteAt => {
+                    let dst = Argument::Register(function.add_register(ast.data_type.clone()));
+
+                    let index = self.compile_ast(&arguments[1], bytecode, function);
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+
+                    function.add_opcode(OpCode::Index {
+                        dst: dst.clone(),
+                        src: pointer,
+                        index,
+                    });
+
+                    dst
+                }
+                Intrinsic::SetByteAt => {
+                    let address =
+                        Argument::Register(function.add_register(arguments[0].data_type.clone()));
+
+                    let index = self.compile_ast(&arguments[1], bytecode, function);
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+                    let value = self.compile_ast(&arguments[2], bytecode, function);
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: address.clone(),
+                        src: pointer,
+                    });
+
+                    function.add_opcode(OpCode::Add {
+                        dst: address.clone(),
+                        src: index,
+                    });
+
+                    function.add_opcode(OpCode::Mov {
+                        dst: Argument::Deref(Box::new(address)),
+                        src: value,
+                    });
+
+                    Argument::VoidRegister
+                }
+                Intrinsic::IsNull => {
+                    let dst = Argument::Register(function.add_register(DataType::Bool));
+
+                    let pointer = self.compile_ast(&arguments[0], bytecode, function);
+
+                    function.add_opcode(OpCode::SetIfEqual {
+                        dst: dst.clone(),
+                        lhs: pointer,
+                        rhs: Argument::Constant {
+                            value: 0,
+                            data_type: arguments[0].data_type.clone(),
+                        },
+                    });
+
+                    dst
+                }
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, parser::Parser};
+
+    fn traps(source: &str, options: CompilerOptions) -> usize {
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+
+        Compiler::compile_with(&ast, symbol_table, options)
+            .functions
+            .iter()
+            .flat_map(|function| &function.opcodes)
+            .filter(|opcode| matches!(opcode, OpCode::Trap))
+            .count()
+    }
+
+    #[test]
+    fn assumed_indices_are_not_checked() {
+        let options = CompilerOptions {
+            bounds_checks: true,
+            ..CompilerOptions::default()
+        };
+
+        let index = |statements: &str| {
+            let source = format!(
+                "{{
+                    let values: [U64; 4] = [1, 2, 3, 4];
+                    let i: U64 = 3;
+                    {statements}
+                    values[i];
+                }}"
+            );
+
+            traps(&source, options)
+        };
+
+        assert_eq!(index(""), 1);
+        assert_eq!(index("@assume(i < 4);"), 0);
+        assert_eq!(index("@assume(i <= 3);"), 0);
+        assert_eq!(index("@assume(i < 5);"), 1);
+        assert_eq!(index("@assume(i < 4); i = 7;"), 1);
+        assert_eq!(index("let p = #i; @assume(i < 4); @p = 7;"), 1);
+    }
+
+    #[test]
+    fn assumed_divisors_are_not_checked() {
+        let options = CompilerOptions {
+            division_checks: true,
+            ..CompilerOptions::default()
+        };
+
+        let divide = |statements: &str| {
+            let source = format!(
+                "{{
+                    let x: U64 = 7;
+                    {statements}
+                    84 / x;
+                }}"
+            );
+
+            traps(&source, options)
+        };
+
+        assert_eq!(divide(""), 1);
+        assert_eq!(divide("@assume(x != 0);"), 0);
+        assert_eq!(divide("@assume(x != 1);"), 1);
+        assert_eq!(divide("@assume(x != 0); x = 0;"), 1);
+        assert_eq!(traps("{ 84 / 2; }", options), 0);
+    }
+
+    #[test]
+    fn returned_interpolations_outlive_the_function() {
+        let source = "{
+            fn label(let n: U64): String {
+                \"n={n}\";
+            };
+
+            let text: String = label(7);
+            let result: U64 = 0;
+
+            if text[0] == 'n' && text[2] == '7' && text[3] == 0 {
+                result = 42;
+            };
+
+            result;
+        }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+
+        assert_eq!(Interpreter::run(&bytecode).unwrap(), 42);
+    }
+}
@@ -0,0 +1,732 @@
+use crate::{
+    ast::{Ast, AstKind, FunctionAttributes, InlineHint, Intrinsic, VariableDeclaration},
+    parser::{Token, TokenKind},
+    symbol_table::{Symbol, SymbolTable},
+    types::{DataType, IntType},
+    CompilerResult,
+};
+use std::fmt::Write;
+
+const INDENT: &str = "    ";
+
+// Writes a parsed program back out as source. Blocks are indented by four spaces, and anything
+// spanning several lines gets an empty line around it. Comments aren't part of the AST, so
+// sources with any shouldn't be formatted, see `Parser::has_comments`. Every variable gets its
+// type written out even if it was inferred, and constants are declared with the value they were
+// folded into. Parsing the result again gives the same program
+pub fn format<'src>(
+    ast: &Ast<'src>,
+    symbol_table: &mut SymbolTable<'src>,
+) -> CompilerResult<'src, String> {
+    let mut formatter = Formatter {
+        text: String::new(),
+        indent: 0,
+        symbol_table,
+    };
+
+    formatter.expr(ast, 0, 0)?;
+    formatter.text.push('\n');
+
+    Ok(formatter.text)
+}
+
+struct Formatter<'a, 'src> {
+    text: String,
+    indent: usize,
+    // Constants, enumerations and generic functions are only fully known to the symbol table
+    symbol_table: &'a mut SymbolTable<'src>,
+}
+
+impl<'a, 'src> Formatter<'a, 'src> {
+    // `left` and `right` are how tightly the operators on either side bind, like in the parser,
+    // so that parentheses are only added where they are needed
+    fn expr(&mut self, ast: &Ast<'src>, left: usize, right: usize) -> CompilerResult<'src, ()> {
+        let parenthesized = match &ast.kind {
+            AstKind::Infix { oper, .. } => {
+                let (left_bp, right_bp) = oper.kind.infix_bp().expect("Only infix operators");
+
+                left_bp < left || right_bp <= right
+            }
+            AstKind::IfStatement { .. } if is_ternary(ast) => 2 < left || 1 <= right,
+            AstKind::Block { .. }
+            | AstKind::IfStatement { .. }
+            | AstKind::WhileLoop { .. }
+            | AstKind::Match { .. } => left > 0 || right > 0,
+            _ => false,
+        };
+
+        let (left, right) = if parenthesized {
+            self.text.push('(');
+            (0, 0)
+        } else {
+            (left, right)
+        };
+
+        match &ast.kind {
+            AstKind::Node { token } => self.node(token)?,
+            AstKind::Prefix { oper, node } => {
+                write!(self.text, "{oper:?}")?;
+
+                // Otherwise it would be read back as a negative literal
+                let is_number = matches!(
+                    node.kind,
+                    AstKind::Node {
+                        token: Token {
+                            kind: TokenKind::Number(..),
+                            ..
+                        }
+                    }
+                );
+
+                if oper.kind == TokenKind::Sub && is_number {
+                    self.text.push('(');
+                    self.expr(node, 0, 0)?;
+                    self.text.push(')');
+                } else {
+                    let prefix_bp = oper.kind.prefix_bp().expect("Only prefix operators");
+
+                    self.expr(node, prefix_bp, 0)?;
+                }
+            }
+            AstKind::Infix { oper, lhs, rhs } => {
+                let (left_bp, right_bp) = oper.kind.infix_bp().expect("Only infix operators");
+
+                self.expr(lhs, left, left_bp)?;
+                write!(self.text, " {oper:?} ")?;
+                self.expr(rhs, right_bp, right)?;
+            }
+            AstKind::IfStatement {
+                condition,
+                if_block,
+                else_block: Some(else_block),
+            } if is_ternary(ast) => {
+                self.expr(condition, left, 2)?;
+                self.text.push_str(" ? ");
+                self.expr(if_block, 0, 0)?;
+                self.text.push_str(" : ");
+                self.expr(else_block, 1, right)?;
+            }
+            AstKind::IfStatement {
+                condition,
+                if_block,
+                else_block,
+            } => {
+                self.text.push_str("if ");
+                self.expr(condition, 0, 0)?;
+                self.text.push(' ');
+                self.expr(if_block, 0, 0)?;
+
+                if let Some(else_block) = else_block {
+                    self.text.push_str(" else ");
+                    self.expr(else_block, 0, 0)?;
+                }
+            }
+            AstKind::WhileLoop { condition, body } => {
+                self.text.push_str("while ");
+                self.expr(condition, 0, 0)?;
+                self.text.push(' ');
+                self.expr(body, 0, 0)?;
+            }
+            AstKind::Block {
+                scope_id,
+                statements,
+                ..
+            } => match for_loop(statements) {
+                Some((init, condition, body, step)) => {
+                    self.with_scope(*scope_id, |formatter| {
+                        formatter.text.push_str("for (");
+                        formatter.statement(init)?;
+                        formatter.text.push_str("; ");
+                        formatter.expr(condition, 0, 0)?;
+                        formatter.text.push_str("; ");
+                        formatter.statement(step)?;
+                        formatter.text.push_str(") ");
+                        formatter.expr(body, 0, 0)
+                    })?;
+                }
+                None => self.block(*scope_id, statements)?,
+            },
+            AstKind::Match {
+                scrutinee,
+                arms,
+                default,
+            } => {
+                self.text.push_str("match ");
+                self.expr(scrutinee, 0, 0)?;
+                self.text.push_str(" {\n");
+
+                self.indent += 1;
+
+                let patterns = arms.iter().map(|arm| (Some(&arm.pattern), &arm.body));
+
+                for (pattern, body) in patterns.chain([(None, default.as_ref())]) {
+                    self.push_indent();
+
+                    match pattern {
+                        Some(pattern) => self.expr(pattern, 0, 0)?,
+                        None => self.text.push('_'),
+                    }
+
+                    self.text.push_str(" => ");
+                    self.expr(body, 0, 0)?;
+                    self.text.push_str(",\n");
+                }
+
+                self.indent -= 1;
+
+                self.push_indent();
+                self.text.push('}');
+            }
+            AstKind::Index { lhs, index } => {
+                self.postfix_lhs(lhs)?;
+                self.text.push('[');
+                self.expr(index, 0, 0)?;
+                self.text.push(']');
+            }
+            AstKind::GetField { lhs, name } => {
+                self.postfix_lhs(lhs)?;
+                write!(self.text, ".{name}")?;
+            }
+            AstKind::Call { lhs, arguments } => {
+                self.postfix_lhs(lhs)?;
+                self.list("(", arguments, ")")?;
+            }
+            AstKind::Intrinsic {
+                intrinsic,
+                arguments,
+            } => {
+                if !intrinsic.is_named() {
+                    self.text.push('@');
+                }
+
+                self.text.push_str(intrinsic.name());
+                self.list("(", arguments, ")")?;
+            }
+            AstKind::ArrayLiteral { elements } => self.list("[", elements, "]")?,
+            AstKind::Interpolation { pieces } => {
+                self.text.push('"');
+
+                for piece in pieces {
+                    match &piece.kind {
+                        AstKind::Node {
+                            token:
+                                Token {
+                                    kind: TokenKind::Str(text),
+                                    ..
+                                },
+                        } => self.text.push_str(&escape(text, '"')),
+                        AstKind::Node { token } => write!(self.text, "{{{}}}", token.text)?,
+                        _ => unreachable!("Only names are interpolated"),
+                    }
+                }
+
+                self.text.push('"');
+            }
+            AstKind::EnumVariant { name, variant } => write!(self.text, "{name}::{variant}")?,
+            AstKind::Instance { name, .. } => self.text.push_str(name),
+            _ => self.statement(ast)?,
+        }
+
+        if parenthesized {
+            self.text.push(')');
+        }
+
+        Ok(())
+    }
+
+    // Without the `;`, which the block adds
+    fn statement(&mut self, ast: &Ast<'src>) -> CompilerResult<'src, ()> {
+        match &ast.kind {
+            AstKind::VariableDeclaration(declaration) => self.variable_declaration(declaration)?,
+            AstKind::Assign { lhs, rhs } => {
+                self.expr(lhs, 0, 0)?;
+                self.text.push_str(" = ");
+                self.expr(rhs, 0, 0)?;
+            }
+            AstKind::Return { value, .. } => {
+                self.text.push_str("return");
+
+                if let Some(value) = value {
+                    self.text.push(' ');
+                    self.expr(value, 0, 0)?;
+                }
+            }
+            AstKind::FunctionDeclaration {
+                name,
+                attributes,
+                scope_id,
+                return_type,
+                arguments,
+                body,
+                ..
+            } => {
+                self.attributes(*attributes);
+
+                write!(self.text, "fn {name}")?;
+
+                self.with_scope(*scope_id, |formatter| {
+                    formatter.text.push('(');
+
+                    for (n, argument) in arguments.iter().enumerate() {
+                        if n > 0 {
+                            formatter.text.push_str(", ");
+                        }
+
+                        formatter.variable_declaration(argument)?;
+                    }
+
+                    formatter.text.push(')');
+
+                    if *return_type != DataType::Void {
+                        write!(formatter.text, ": {}", type_name(return_type))?;
+                    }
+
+                    formatter.text.push(' ');
+                    formatter.expr(body, 0, 0)
+                })?;
+            }
+            // The names of the arguments aren't kept, only their types
+            AstKind::ExternDeclaration { name } => {
+                let extern_function = self
+                    .symbol_table
+                    .externs
+                    .iter()
+                    .find(|extern_function| extern_function.name == *name)
+                    .expect("Every extern declaration is listed");
+
+                let DataType::Function {
+                    ref return_type,
+                    ref argument_types,
+                } = extern_function.data_type
+                else {
+                    unreachable!("An extern declaration is always a function");
+                };
+
+                let arguments = argument_types
+                    .iter()
+                    .enumerate()
+                    .map(|(n, data_type)| format!("let _{n}: {}", type_name(data_type)))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                write!(self.text, "extern fn {name}({arguments})")?;
+
+                if **return_type != DataType::Void {
+                    write!(self.text, ": {}", type_name(return_type))?;
+                }
+            }
+            AstKind::GenericFunctionDeclaration { name } => {
+                let Some(Symbol::Generic(generic)) = self.symbol_table.get_symbol(name) else {
+                    unreachable!("A generic function is always declared");
+                };
+
+                let attributes = generic.attributes();
+                let type_parameters = generic.type_parameter_names().join(", ");
+                let source = generic.source()?;
+
+                self.attributes(attributes);
+
+                write!(self.text, "fn {name}<{type_parameters}>{source}")?;
+            }
+            AstKind::StructDeclaration { name, fields } => {
+                writeln!(self.text, "struct {name} {{")?;
+
+                self.indent += 1;
+
+                for field in fields {
+                    self.push_indent();
+                    self.variable_declaration(field)?;
+                    self.text.push_str(";\n");
+                }
+
+                self.indent -= 1;
+
+                self.push_indent();
+                self.text.push('}');
+            }
+            AstKind::EnumDeclaration { name } => {
+                let Some(Symbol::Enum(DataType::Enum(enum_type))) =
+                    self.symbol_table.get_symbol(name)
+                else {
+                    unreachable!("An enumeration is always declared");
+                };
+
+                write!(
+                    self.text,
+                    "enum {name} {{ {} }}",
+                    enum_type.variants.join(", ")
+                )?;
+            }
+            AstKind::ConstantDeclaration { name } => {
+                let Some(Symbol::Constant(kind)) = self.symbol_table.get_symbol(name) else {
+                    unreachable!("A constant is always declared");
+                };
+
+                let (data_type, value) = match *kind {
+                    TokenKind::Number(value, Some(int_type)) => {
+                        (DataType::Int(int_type), value.to_string())
+                    }
+                    TokenKind::Char(value) => {
+                        (DataType::Char, format!("'{}'", escape(&[value], '\'')))
+                    }
+                    TokenKind::True => (DataType::Bool, "true".to_string()),
+                    TokenKind::False => (DataType::Bool, "false".to_string()),
+                    _ => unreachable!("Only integers, characters and booleans can be folded"),
+                };
+
+                write!(
+                    self.text,
+                    "const {name}: {} = {value}",
+                    type_name(&data_type)
+                )?;
+            }
+            AstKind::StaticDeclaration {
+                name,
+                data_type,
+                value,
+            } => {
+                write!(self.text, "static {name}: {}", type_name(data_type))?;
+
+                if let Some(value) = *value {
+                    let value = match data_type {
+                        DataType::Int(int_type) if int_type.is_signed() => {
+                            int_type.wrap(value as i64 as i128).to_string()
+                        }
+                        DataType::Bool => (value != 0).to_string(),
+                        DataType::Char => format!("'{}'", escape(&[value as u8], '\'')),
+                        _ => value.to_string(),
+                    };
+
+                    write!(self.text, " = {value}")?;
+                }
+            }
+            _ => self.expr(ast, 0, 0)?,
+        }
+
+        Ok(())
+    }
+
+    fn block(&mut self, scope_id: usize, statements: &[Ast<'src>]) -> CompilerResult<'src, ()> {
+        if statements.is_empty() {
+            self.text.push_str("{}");
+
+            return Ok(());
+        }
+
+        self.text.push_str("{\n");
+
+        self.indent += 1;
+
+        self.with_scope(scope_id, |formatter| {
+            let mut previous_spans_lines = None;
+
+            for statement in statements {
+                // Generic instances are written out once, as the generic function
+                if let AstKind::FunctionDeclaration { type_arguments, .. } = &statement.kind {
+                    if !type_arguments.is_empty() {
+                        continue;
+                    }
+                }
+
+                let start = formatter.text.len();
+
+                formatter.push_indent();
+                formatter.statement(statement)?;
+                formatter.text.push_str(";\n");
+
+                let spans_lines = formatter.text[start..].trim_end().contains('\n');
+
+                if let Some(previous_spans_lines) = previous_spans_lines {
+                    if spans_lines || previous_spans_lines {
+                        formatter.text.insert(start, '\n');
+                    }
+                }
+
+                previous_spans_lines = Some(spans_lines);
+            }
+
+            Ok(())
+        })?;
+
+        self.indent -= 1;
+
+        self.push_indent();
+        self.text.push('}');
+
+        Ok(())
+    }
+
+    fn node(&mut self, token: &Token<'src>) -> CompilerResult<'src, ()> {
+        // A constant is replaced by its literal, but the token keeps the name it was used by
+        if matches!(
+            self.symbol_table.get_symbol(token.text),
+            Some(Symbol::Constant(_))
+        ) {
+            self.text.push_str(token.text);
+
+            return Ok(());
+        }
+
+        match token.kind {
+            // Like `sizeof(T)`, which became its value
+            TokenKind::Number(value, Some(int_type))
+                if !token.text.starts_with(|ch: char| ch.is_ascii_digit()) =>
+            {
+                write!(self.text, "{value}{}", suffix(int_type))?
+            }
+            // `@source_location()` became the location, but the token keeps the intrinsic's name
+            TokenKind::Str(_) if token.text == Intrinsic::SourceLocation.name() => {
+                write!(self.text, "@{}()", token.text)?
+            }
+            // Joined with any literals right after it, so only the value is left
+            TokenKind::Str(ref text) => write!(self.text, "\"{}\"", escape(text, '"'))?,
+            TokenKind::Float(_) => self.text.push_str(token.text),
+            _ => write!(self.text, "{token:?}")?,
+        }
+
+        Ok(())
+    }
+
+    fn variable_declaration(
+        &mut self,
+        declaration: &VariableDeclaration<'src>,
+    ) -> CompilerResult<'src, ()> {
+        if let Some(alignment) = declaration.alignment {
+            write!(self.text, "@align({alignment}) ")?;
+        }
+
+        write!(
+            self.text,
+            "let {}: {}",
+            declaration.name,
+            type_name(&declaration.data_type)
+        )?;
+
+        if let Some(value) = &declaration.value {
+            self.text.push_str(" = ");
+            self.expr(value, 0, 0)?;
+        }
+
+        Ok(())
+    }
+
+    fn attributes(&mut self, attributes: FunctionAttributes) {
+        match attributes.inline {
+            InlineHint::Always => self.text.push_str("@inline "),
+            InlineHint::Never => self.text.push_str("@noinline "),
+            InlineHint::Auto => {}
+        }
+
+        if attributes.cold {
+            self.text.push_str("@cold ");
+        }
+    }
+
+    // Calls, indexing and fields bind tighter than anything else
+    fn postfix_lhs(&mut self, lhs: &Ast<'src>) -> CompilerResult<'src, ()> {
+        let is_primary = matches!(
+            lhs.kind,
+            AstKind::Node { .. }
+                | AstKind::Index { .. }
+                | AstKind::GetField { .. }
+                | AstKind::Call { .. }
+                | AstKind::Intrinsic { .. }
+                | AstKind::ArrayLiteral { .. }
+                | AstKind::Interpolation { .. }
+                | AstKind::EnumVariant { .. }
+                | AstKind::Instance { .. }
+        );
+
+        if is_primary {
+            self.expr(lhs, 0, 0)
+        } else {
+            self.text.push('(');
+            self.expr(lhs, 0, 0)?;
+            self.text.push(')');
+
+            Ok(())
+        }
+    }
+
+    fn list(&mut self, open: &str, items: &[Ast<'src>], close: &str) -> CompilerResult<'src, ()> {
+        self.text.push_str(open);
+
+        for (n, item) in items.iter().enumerate() {
+            if n > 0 {
+                self.text.push_str(", ");
+            }
+
+            self.expr(item, 0, 0)?;
+        }
+
+        self.text.push_str(close);
+
+        Ok(())
+    }
+
+    fn with_scope(
+        &mut self,
+        scope_id: usize,
+        function: impl FnOnce(&mut Self) -> CompilerResult<'src, ()>,
+    ) -> CompilerResult<'src, ()> {
+        let outer_scope_id = self.symbol_table.get_scope();
+
+        self.symbol_table.enter_scope(scope_id);
+
+        let result = function(self);
+
+        self.symbol_table.enter_scope(outer_scope_id);
+
+        result
+    }
+
+    fn push_indent(&mut self) {
+        self.text.push_str(&INDENT.repeat(self.indent));
+    }
+}
+
+// `condition ? a : b` is an `if` whose branches aren't blocks
+fn is_ternary(ast: &Ast) -> bool {
+    let AstKind::IfStatement {
+        if_block,
+        else_block,
+        ..
+    } = &ast.kind
+    else {
+        return false;
+    };
+
+    let is_block = |ast: &Ast| match ast.kind {
+        AstKind::Block { .. } => true,
+        AstKind::IfStatement { .. } => !is_ternary(ast),
+        _ => false,
+    };
+
+    !matches!(if_block.kind, AstKind::Block { .. }) || !else_block.as_deref().is_none_or(is_block)
+}
+
+// `for (init; condition; step) body` is parsed into `{ init; while condition { body; step; }; }`,
+// so that shape is written as a `for` loop again
+fn for_loop<'b, 'src>(
+    statements: &'b [Ast<'src>],
+) -> Option<(&'b Ast<'src>, &'b Ast<'src>, &'b Ast<'src>, &'b Ast<'src>)> {
+    let [init, while_loop] = statements else {
+        return None;
+    };
+
+    let AstKind::WhileLoop { condition, body } = &while_loop.kind else {
+        return None;
+    };
+
+    let AstKind::Block { statements, .. } = &body.kind else {
+        return None;
+    };
+
+    let [body @ Ast {
+        kind: AstKind::Block { .. },
+        ..
+    }, step] = &statements[..]
+    else {
+        return None;
+    };
+
+    Some((init, condition, body, step))
+}
+
+// Like the type is written in errors, except that references to bytes are `String`
+fn type_name(data_type: &DataType) -> String {
+    match data_type {
+        DataType::Ref(deref) if **deref == DataType::Int(IntType::U8) => "String".to_string(),
+        DataType::Ref(deref) => format!("#{}", type_name(deref)),
+        DataType::Array { element, length } => format!("[{}; {length}]", type_name(element)),
+        DataType::Function {
+            return_type,
+            argument_types,
+        } => {
+            let argument_types = argument_types
+                .iter()
+                .map(type_name)
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            match **return_type {
+                DataType::Void => format!("fn({argument_types})"),
+                _ => format!("fn({argument_types}): {}", type_name(return_type)),
+            }
+        }
+        _ => format!("{data_type:?}"),
+    }
+}
+
+fn suffix(int_type: IntType) -> String {
+    format!("{int_type:?}").to_lowercase()
+}
+
+// Printable ASCII is kept, everything else is escaped. Braces are doubled in strings, since
+// they would start an interpolation
+fn escape(bytes: &[u8], quote: char) -> String {
+    let mut text = String::new();
+
+    for &byte in bytes {
+        match byte {
+            b'\n' => text.push_str("\\n"),
+            b'\t' => text.push_str("\\t"),
+            b'\r' => text.push_str("\\r"),
+            0 => text.push_str("\\0"),
+            b'\\' => text.push_str("\\\\"),
+            b'{' | b'}' if quote == '"' => {
+                text.push(byte as char);
+                text.push(byte as char);
+            }
+            _ if byte as char == quote => {
+                text.push('\\');
+                text.push(quote);
+            }
+            b' '..=b'~' => text.push(byte as char),
+            _ => write!(text, "\\x{byte:02x}").expect("Writing to a string can't fail"),
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, ParserConfig};
+
+    #[test]
+    fn sources_with_operators_are_formatted() {
+        let source = "{
+            let x: U64 = 1;
+
+            if x == 1 && x != 2 || x >= 3 {
+                42;
+            } else {
+                0;
+            };
+        }";
+
+        assert!(!Parser::has_comments(source, ParserConfig::default()).unwrap());
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let formatted = format(&ast, &mut symbol_table).unwrap();
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(&formatted, &mut symbol_table).unwrap();
+
+        assert_eq!(format(&ast, &mut symbol_table).unwrap(), formatted);
+    }
+
+    #[test]
+    fn source_locations_are_kept() {
+        let source = "{ let here: String = @source_location(); len(here); }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let formatted = format(&ast, &mut symbol_table).unwrap();
+
+        assert!(formatted.contains("let here: String = @source_location();"));
+    }
+}
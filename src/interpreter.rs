@@ -0,0 +1,804 @@
+use crate::{
+    bytecode::{Argument, ByteCode, Function, OpCode},
+    types::{DataType, FloatType},
+};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    time::Instant,
+};
+
+pub enum InterpreterError {
+    InvalidAddress { address: u64, size: usize },
+    StackOverflow,
+    DivideError,
+    Breakpoint,
+    Trap,
+    // Nothing is linked in, so there is no code to run
+    Extern { name: String },
+    Io(io::Error),
+}
+
+impl InterpreterError {
+    // What the shell reports for a native program killed by the matching signal
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::InvalidAddress { .. } | Self::StackOverflow => 128 + 11, // SIGSEGV
+            Self::DivideError => 128 + 8,                                  // SIGFPE
+            Self::Breakpoint => 128 + 5,                                   // SIGTRAP
+            Self::Trap => 128 + 4,                                         // SIGILL
+            Self::Extern { .. } | Self::Io(_) => 1,
+        }
+    }
+}
+
+impl Error for InterpreterError {}
+
+impl fmt::Debug for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidAddress { address, size } => {
+                write!(f, "invalid access of {size} bytes at address {address:#x}")
+            }
+            Self::StackOverflow => write!(f, "stack overflow"),
+            Self::DivideError => write!(f, "division by zero, or a quotient that doesn't fit"),
+            Self::Breakpoint => write!(f, "hit a breakpoint"),
+            Self::Trap => write!(f, "hit a trap"),
+            Self::Extern { name } => write!(f, "can't call the extern function `{name}`"),
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+type InterpreterResult<T> = Result<T, InterpreterError>;
+
+// Nothing lives in the first page, so null references are caught
+const DATA_START: u64 = 0x1000;
+// Functions get addresses too, but there is nothing to read there
+const CODE_START: u64 = 0x10_0000;
+const HEAP_START: u64 = 0x1_0000_0000;
+const STACK_START: u64 = 0x100_0000_0000;
+
+const PAGE_SIZE: usize = 4096;
+const STACK_LIMIT: usize = 8 * 1024 * 1024;
+
+// A range of addresses backed by memory
+struct Segment {
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+impl Segment {
+    fn new(start: u64) -> Self {
+        Self {
+            start,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
+    }
+}
+
+// Where everything of a function is, from the start of its frame
+struct Layout {
+    arguments: Vec<u64>,
+    registers: Vec<u64>,
+    size: usize,
+    alignment: u64,
+    // The opcode each label is at
+    labels: Vec<usize>,
+}
+
+impl Layout {
+    // The return value comes first, then the arguments and then the registers
+    fn new(function: &Function) -> Self {
+        let return_size = function.return_type.size_aligned();
+
+        let arguments = (0..function.argument_types.len())
+            .map(|argument_id| (return_size + function.argument_position(argument_id)) as u64)
+            .collect();
+
+        let mut registers = Vec::new();
+        let mut position = return_size + function.arguments_size;
+
+        // Frames start at an address aligned to the most any of their registers need
+        for (register_id, data_type) in function.register_types.iter().enumerate() {
+            position = position.next_multiple_of(function.register_alignment(register_id));
+
+            registers.push(position as u64);
+
+            position += data_type.size_aligned();
+        }
+
+        let mut labels = vec![0; function.labels];
+
+        for (n, opcode) in function.opcodes.iter().enumerate() {
+            if let OpCode::Label { label_id } = opcode {
+                labels[*label_id] = n;
+            }
+        }
+
+        let alignment = (0..function.register_types.len())
+            .map(|register_id| function.register_alignment(register_id))
+            .fold(8, usize::max);
+
+        Self {
+            arguments,
+            registers,
+            size: position,
+            alignment: alignment as u64,
+            labels,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    function: usize,
+    address: u64,
+}
+
+enum Callee {
+    Function(usize),
+    Builtin(usize),
+    Extern(usize),
+}
+
+// Runs the bytecode directly. Registers live in stack frames in memory, with made up addresses,
+// so references to them work like they do natively
+pub struct Interpreter<'a, 'src> {
+    bytecode: &'a ByteCode<'src>,
+    segments: [Segment; 3],
+    symbols: HashMap<String, u64>,
+    callees: HashMap<u64, Callee>,
+    layouts: Vec<Layout>,
+    start: Instant,
+}
+
+impl<'a, 'src> Interpreter<'a, 'src> {
+    // Runs `@main` and returns its result
+    pub fn run(bytecode: &'a ByteCode<'src>) -> InterpreterResult<u64> {
+        let mut interpreter = Self::new(bytecode);
+
+        let main = bytecode
+            .functions
+            .iter()
+            .position(|function| function.name == "@main")
+            .expect("There is always a main function");
+
+        let frame = interpreter.push_frame(main, Vec::new())?;
+
+        interpreter.execute(frame)?;
+
+        let return_type = &bytecode.functions[main].return_type;
+
+        match return_type {
+            DataType::Bool | DataType::Int(_) | DataType::Ref(_) => {
+                interpreter.read(frame.address, return_type.size())
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn new(bytecode: &'a ByteCode<'src>) -> Self {
+        let mut data = Segment::new(DATA_START);
+        let mut symbols = HashMap::new();
+        let mut callees = HashMap::new();
+
+        for (id, string) in bytecode.strings.iter().enumerate() {
+            symbols.insert(ByteCode::string_symbol_name(id), data.end());
+
+            data.bytes.extend_from_slice(string);
+            data.bytes.push(0);
+        }
+
+        for (id, global) in bytecode.globals.iter().enumerate() {
+            data.bytes.resize(data.bytes.len().next_multiple_of(8), 0);
+
+            symbols.insert(ByteCode::global_symbol_name(id), data.end());
+
+            let bytes = global
+                .bytes()
+                .unwrap_or_else(|| vec![0; global.data_type.size()]);
+
+            data.bytes.extend_from_slice(&bytes);
+        }
+
+        for (index, function) in bytecode.functions.iter().enumerate() {
+            let address = CODE_START + index as u64;
+
+            symbols.insert(function.name.clone(), address);
+            callees.insert(address, Callee::Function(index));
+        }
+
+        for (index, builtin) in bytecode.builtins.iter().enumerate() {
+            let address = CODE_START + (bytecode.functions.len() + index) as u64;
+
+            symbols.insert(builtin.name.to_string(), address);
+            callees.insert(address, Callee::Builtin(index));
+        }
+
+        let extern_start = bytecode.functions.len() + bytecode.builtins.len();
+
+        for (index, extern_function) in bytecode.externs.iter().enumerate() {
+            let address = CODE_START + (extern_start + index) as u64;
+
+            symbols.insert(extern_function.name.to_string(), address);
+            callees.insert(address, Callee::Extern(index));
+        }
+
+        let layouts = bytecode.functions.iter().map(Layout::new).collect();
+
+        Self {
+            bytecode,
+            segments: [data, Segment::new(HEAP_START), Segment::new(STACK_START)],
+            symbols,
+            callees,
+            layouts,
+            start: Instant::now(),
+        }
+    }
+
+    fn memory(&mut self, address: u64, size: usize) -> InterpreterResult<&mut [u8]> {
+        let segment = self
+            .segments
+            .iter_mut()
+            .find(|segment| {
+                segment.start <= address
+                    && address
+                        .checked_add(size as u64)
+                        .is_some_and(|end| end <= segment.end())
+            })
+            .ok_or(InterpreterError::InvalidAddress { address, size })?;
+
+        let offset = (address - segment.start) as usize;
+
+        Ok(&mut segment.bytes[offset..offset + size])
+    }
+
+    // Little endian, zero extended
+    fn read(&mut self, address: u64, size: usize) -> InterpreterResult<u64> {
+        let mut bytes = [0; 8];
+
+        bytes[..size].copy_from_slice(self.memory(address, size)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn write(&mut self, address: u64, size: usize, value: u64) -> InterpreterResult<()> {
+        self.memory(address, size)?
+            .copy_from_slice(&value.to_le_bytes()[..size]);
+
+        Ok(())
+    }
+
+    // Like `memmove`
+    fn copy(&mut self, dst: u64, src: u64, size: usize) -> InterpreterResult<()> {
+        let bytes = self.memory(src, size)?.to_vec();
+
+        self.memory(dst, size)?.copy_from_slice(&bytes);
+
+        Ok(())
+    }
+
+    fn push_frame(&mut self, function: usize, arguments: Vec<Vec<u8>>) -> InterpreterResult<Frame> {
+        let layout = &self.layouts[function];
+        let stack = &mut self.segments[2];
+
+        let frame = Frame {
+            function,
+            address: stack.end().next_multiple_of(layout.alignment),
+        };
+
+        let end = (frame.address - stack.start) as usize + layout.size;
+
+        if end > STACK_LIMIT {
+            return Err(InterpreterError::StackOverflow);
+        }
+
+        stack.bytes.resize(end, 0);
+
+        for (argument_id, bytes) in arguments.into_iter().enumerate() {
+            let address = frame.address + self.layouts[function].arguments[argument_id];
+
+            self.memory(address, bytes.len())?.copy_from_slice(&bytes);
+        }
+
+        Ok(frame)
+    }
+
+    fn address(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        argument: &Argument<'src>,
+    ) -> InterpreterResult<u64> {
+        let layout = &self.layouts[frame.function];
+
+        let address = match argument {
+            Argument::ReturnValue => frame.address,
+            Argument::Argument(argument_id) => frame.address + layout.arguments[*argument_id],
+            Argument::Register(register_id) => frame.address + layout.registers[*register_id],
+            Argument::Deref(deref) => self.load(function, frame, deref)?,
+            Argument::StructField { data, name } => {
+                let DataType::Struct(ref struct_type) = function.argument_data_type(data) else {
+                    panic!();
+                };
+
+                self.address(function, frame, data)? + struct_type.field_offset(name) as u64
+            }
+            Argument::Symbol { name, .. } => self.symbols[name],
+            Argument::Constant { .. } | Argument::VoidRegister => unreachable!(),
+        };
+
+        Ok(address)
+    }
+
+    fn load(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        argument: &Argument<'src>,
+    ) -> InterpreterResult<u64> {
+        match argument {
+            Argument::Constant { value, data_type } => {
+                Ok(value & (u64::MAX >> (64 - data_type.size() * 8)))
+            }
+            Argument::Symbol { name, .. } => Ok(self.symbols[name]),
+            _ => {
+                let size = function.argument_data_type(argument).size();
+                let address = self.address(function, frame, argument)?;
+
+                self.read(address, size)
+            }
+        }
+    }
+
+    fn store(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        dst: &Argument<'src>,
+        value: u64,
+    ) -> InterpreterResult<()> {
+        let size = function.argument_data_type(dst).size();
+        let address = self.address(function, frame, dst)?;
+
+        self.write(address, size, value)
+    }
+
+    // The bytes of the argument, however big it is
+    fn bytes(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        argument: &Argument<'src>,
+    ) -> InterpreterResult<Vec<u8>> {
+        let data_type = function.argument_data_type(argument);
+
+        if data_type.is_aggregate() {
+            let address = self.address(function, frame, argument)?;
+
+            Ok(self.memory(address, data_type.size())?.to_vec())
+        } else {
+            let value = self.load(function, frame, argument)?;
+
+            Ok(value.to_le_bytes()[..data_type.size()].to_vec())
+        }
+    }
+
+    fn execute(&mut self, frame: Frame) -> InterpreterResult<()> {
+        let function = &self.bytecode.functions[frame.function];
+
+        let mut n = 0;
+
+        while let Some(opcode) = function.opcodes.get(n) {
+            n += 1;
+
+            match opcode {
+                OpCode::Goto { label_id } => n = self.layouts[frame.function].labels[*label_id],
+                OpCode::GotoIfZero {
+                    condition,
+                    label_id,
+                } => {
+                    if self.load(function, frame, condition)? == 0 {
+                        n = self.layouts[frame.function].labels[*label_id];
+                    }
+                }
+                OpCode::GotoIfNotZero {
+                    condition,
+                    label_id,
+                } => {
+                    if self.load(function, frame, condition)? != 0 {
+                        n = self.layouts[frame.function].labels[*label_id];
+                    }
+                }
+                OpCode::Return => return Ok(()),
+                _ => self.execute_opcode(function, frame, opcode)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_opcode(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        opcode: &OpCode<'src>,
+    ) -> InterpreterResult<()> {
+        match opcode {
+            OpCode::Mov { dst, src } if function.argument_data_type(dst).is_aggregate() => {
+                let size = function.argument_data_type(dst).size();
+
+                let dst_address = self.address(function, frame, dst)?;
+                let src_address = self.address(function, frame, src)?;
+
+                self.copy(dst_address, src_address, size)?;
+            }
+            OpCode::Mov { dst, src } => {
+                let value = self.load(function, frame, src)?;
+
+                self.store(function, frame, dst, value)?;
+            }
+            OpCode::Add { dst, src }
+            | OpCode::Sub { dst, src }
+            | OpCode::Mul { dst, src }
+            | OpCode::Div { dst, src }
+            | OpCode::Mod { dst, src }
+            | OpCode::And { dst, src }
+            | OpCode::Or { dst, src }
+            | OpCode::Xor { dst, src } => {
+                let data_type = function.argument_data_type(dst);
+
+                let lhs = self.load(function, frame, dst)?;
+                let rhs = self.load(function, frame, src)?;
+
+                let result = match data_type {
+                    DataType::Float(float_type) => float_arithmetic(opcode, float_type, lhs, rhs),
+                    _ => integer_arithmetic(opcode, data_type, lhs, rhs)?,
+                };
+
+                self.store(function, frame, dst, result)?;
+            }
+            OpCode::Not { dst } => {
+                let value = self.load(function, frame, dst)?;
+
+                self.store(function, frame, dst, (value & 1) ^ 1)?;
+            }
+            OpCode::BitNot { dst } => {
+                let value = self.load(function, frame, dst)?;
+
+                self.store(function, frame, dst, !value)?;
+            }
+            // Flips the sign bit of floats
+            OpCode::Negate { dst } => {
+                let data_type = function.argument_data_type(dst);
+                let value = self.load(function, frame, dst)?;
+
+                let result = if data_type.is_float() {
+                    value ^ (1 << (data_type.size() * 8 - 1))
+                } else {
+                    value.wrapping_neg()
+                };
+
+                self.store(function, frame, dst, result)?;
+            }
+            OpCode::Ref { dst, src } => {
+                let address = self.address(function, frame, src)?;
+
+                self.store(function, frame, dst, address)?;
+            }
+            // The index is in bytes
+            OpCode::Index { dst, src, index } => {
+                let address = self.load(function, frame, src)?;
+                let offset = self.offset(function, frame, index)?;
+
+                let size = function.argument_data_type(dst).size();
+                let value = self.read(address.wrapping_add(offset), size)?;
+
+                self.store(function, frame, dst, value)?;
+            }
+            OpCode::SetIndex { dst, src, index } => {
+                let address = self.load(function, frame, dst)?;
+                let offset = self.offset(function, frame, index)?;
+
+                let bytes = self.bytes(function, frame, src)?;
+
+                self.memory(address.wrapping_add(offset), bytes.len())?
+                    .copy_from_slice(&bytes);
+            }
+            OpCode::SetField { dst, src, offset } => {
+                let address = self.load(function, frame, dst)?;
+
+                let bytes = self.bytes(function, frame, src)?;
+
+                self.memory(address.wrapping_add(*offset as u64), bytes.len())?
+                    .copy_from_slice(&bytes);
+            }
+            OpCode::Copy { dst, src, size } => {
+                let dst_address = self.load(function, frame, dst)?;
+                let src_address = self.load(function, frame, src)?;
+                let size = self.load(function, frame, size)?;
+
+                self.copy(dst_address, src_address, size as usize)?;
+            }
+            OpCode::SetIfEqual { dst, lhs, rhs }
+            | OpCode::SetIfNotEqual { dst, lhs, rhs }
+            | OpCode::SetIfGreater { dst, lhs, rhs }
+            | OpCode::SetIfLess { dst, lhs, rhs }
+            | OpCode::SetIfGreaterOrEqual { dst, lhs, rhs }
+            | OpCode::SetIfLessOrEqual { dst, lhs, rhs } => {
+                let data_type = function.argument_data_type(lhs);
+
+                let lhs = self.load(function, frame, lhs)?;
+                let rhs = self.load(function, frame, rhs)?;
+
+                let ordering = compare(data_type, lhs, rhs);
+
+                let result = match opcode {
+                    OpCode::SetIfEqual { .. } => ordering == Some(Ordering::Equal),
+                    OpCode::SetIfNotEqual { .. } => ordering != Some(Ordering::Equal),
+                    OpCode::SetIfGreater { .. } => ordering == Some(Ordering::Greater),
+                    OpCode::SetIfLess { .. } => ordering == Some(Ordering::Less),
+                    OpCode::SetIfGreaterOrEqual { .. } => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                    _ => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                };
+
+                self.store(function, frame, dst, result as u64)?;
+            }
+            OpCode::Call {
+                dst,
+                lhs,
+                arguments,
+            } => self.call(function, frame, dst, lhs, arguments)?,
+            OpCode::Label { .. } | OpCode::SourceLine { .. } => {}
+            OpCode::Breakpoint => return Err(InterpreterError::Breakpoint),
+            OpCode::Trap => return Err(InterpreterError::Trap),
+            OpCode::ReadCycleCounter { dst } => {
+                let cycles = self.start.elapsed().as_nanos() as u64;
+
+                self.store(function, frame, dst, cycles)?;
+            }
+            OpCode::Goto { .. }
+            | OpCode::GotoIfZero { .. }
+            | OpCode::GotoIfNotZero { .. }
+            | OpCode::Return => unreachable!("Jumps are taken care of by `execute`"),
+        }
+
+        Ok(())
+    }
+
+    fn offset(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        index: &Argument<'src>,
+    ) -> InterpreterResult<u64> {
+        match index {
+            Argument::VoidRegister => Ok(0),
+            _ => {
+                let data_type = function.argument_data_type(index);
+                let value = self.load(function, frame, index)?;
+
+                Ok(sign_extend(data_type, value))
+            }
+        }
+    }
+
+    // Arguments are stored last to first
+    fn call(
+        &mut self,
+        function: &Function<'src>,
+        frame: Frame,
+        dst: &Argument<'src>,
+        lhs: &Argument<'src>,
+        arguments: &[Argument<'src>],
+    ) -> InterpreterResult<()> {
+        let address = self.load(function, frame, lhs)?;
+
+        let arguments = arguments
+            .iter()
+            .rev()
+            .map(|argument| self.bytes(function, frame, argument))
+            .collect::<InterpreterResult<Vec<Vec<u8>>>>()?;
+
+        let callee_frame = match self.callees.get(&address) {
+            Some(Callee::Function(index)) => self.push_frame(*index, arguments)?,
+            Some(Callee::Builtin(index)) => {
+                let result = self.builtin(*index, arguments)?;
+
+                if *dst != Argument::VoidRegister {
+                    self.store(function, frame, dst, result)?;
+                }
+
+                return Ok(());
+            }
+            Some(Callee::Extern(index)) => {
+                return Err(InterpreterError::Extern {
+                    name: self.bytecode.externs[*index].name.to_string(),
+                })
+            }
+            None => return Err(InterpreterError::InvalidAddress { address, size: 1 }),
+        };
+
+        self.execute(callee_frame)?;
+
+        if *dst != Argument::VoidRegister {
+            let size = function.argument_data_type(dst).size();
+            let dst_address = self.address(function, frame, dst)?;
+
+            self.copy(dst_address, callee_frame.address, size)?;
+        }
+
+        self.segments[2]
+            .bytes
+            .truncate((callee_frame.address - STACK_START) as usize);
+
+        Ok(())
+    }
+
+    fn builtin(&mut self, index: usize, arguments: Vec<Vec<u8>>) -> InterpreterResult<u64> {
+        let arguments = arguments
+            .iter()
+            .map(|bytes| {
+                let mut value = [0; 8];
+
+                value[..bytes.len()].copy_from_slice(bytes);
+
+                u64::from_le_bytes(value)
+            })
+            .collect::<Vec<u64>>();
+
+        match self.bytecode.builtins[index].name {
+            "write" => {
+                let text = self.memory(arguments[0], arguments[1] as usize)?.to_vec();
+
+                let mut stdout = io::stdout();
+
+                stdout.write_all(&text).map_err(InterpreterError::Io)?;
+                stdout.flush().map_err(InterpreterError::Io)?;
+
+                Ok(0)
+            }
+            "print_integer" => {
+                let text = if arguments[1] != 0 {
+                    (arguments[0] as i64).to_string()
+                } else {
+                    arguments[0].to_string()
+                };
+
+                let mut stdout = io::stdout();
+
+                stdout
+                    .write_all(text.as_bytes())
+                    .map_err(InterpreterError::Io)?;
+                stdout.flush().map_err(InterpreterError::Io)?;
+
+                Ok(0)
+            }
+            // A single read, like the syscall
+            "read" => {
+                let mut text = vec![0; arguments[1] as usize];
+
+                let length = io::stdin().read(&mut text).map_err(InterpreterError::Io)?;
+
+                self.memory(arguments[0], length)?
+                    .copy_from_slice(&text[..length]);
+
+                Ok(0)
+            }
+            // Whole pages that start out zeroed, like `mmap`
+            "malloc" => {
+                let heap = &mut self.segments[1];
+
+                let start = heap.bytes.len().next_multiple_of(PAGE_SIZE);
+                let size = (arguments[0] as usize).next_multiple_of(PAGE_SIZE);
+
+                heap.bytes.resize(start + size, 0);
+
+                Ok(heap.start + start as u64)
+            }
+            // Memory is only given back once the program is done
+            "free" => Ok(0),
+            name => unreachable!("There is no builtin `{name}`"),
+        }
+    }
+}
+
+fn sign_extend(data_type: &DataType, value: u64) -> u64 {
+    if !data_type.is_signed_integer() {
+        return value;
+    }
+
+    let unused_bits = 64 - data_type.size() * 8;
+
+    (((value << unused_bits) as i64) >> unused_bits) as u64
+}
+
+// Any bits past the size of the type get cut off when the result is stored
+fn integer_arithmetic(
+    opcode: &OpCode,
+    data_type: &DataType,
+    lhs: u64,
+    rhs: u64,
+) -> InterpreterResult<u64> {
+    let result = match opcode {
+        OpCode::Add { .. } => lhs.wrapping_add(rhs),
+        OpCode::Sub { .. } => lhs.wrapping_sub(rhs),
+        OpCode::Mul { .. } => lhs.wrapping_mul(rhs),
+        OpCode::And { .. } => lhs & rhs,
+        OpCode::Or { .. } => lhs | rhs,
+        OpCode::Xor { .. } => lhs ^ rhs,
+        // Overflowing the type is an error, like it is for `div` and `idiv`
+        OpCode::Div { .. } | OpCode::Mod { .. } if data_type.is_signed_integer() => {
+            let lhs = sign_extend(data_type, lhs) as i64;
+            let rhs = sign_extend(data_type, rhs) as i64;
+
+            let quotient = lhs.checked_div(rhs).ok_or(InterpreterError::DivideError)?;
+
+            if sign_extend(data_type, quotient as u64) as i64 != quotient {
+                return Err(InterpreterError::DivideError);
+            }
+
+            match opcode {
+                OpCode::Div { .. } => quotient as u64,
+                _ => (lhs - quotient * rhs) as u64,
+            }
+        }
+        OpCode::Div { .. } => lhs.checked_div(rhs).ok_or(InterpreterError::DivideError)?,
+        OpCode::Mod { .. } => lhs.checked_rem(rhs).ok_or(InterpreterError::DivideError)?,
+        _ => unreachable!(),
+    };
+
+    Ok(result)
+}
+
+fn float_arithmetic(opcode: &OpCode, float_type: &FloatType, lhs: u64, rhs: u64) -> u64 {
+    let operate = |lhs: f64, rhs: f64| match opcode {
+        OpCode::Add { .. } => lhs + rhs,
+        OpCode::Sub { .. } => lhs - rhs,
+        OpCode::Mul { .. } => lhs * rhs,
+        OpCode::Div { .. } => lhs / rhs,
+        _ => unreachable!("There are no other float operations"),
+    };
+
+    match float_type {
+        FloatType::F32 => {
+            let result = operate(
+                f32::from_bits(lhs as u32).into(),
+                f32::from_bits(rhs as u32).into(),
+            );
+
+            (result as f32).to_bits().into()
+        }
+        FloatType::F64 => operate(f64::from_bits(lhs), f64::from_bits(rhs)).to_bits(),
+    }
+}
+
+// `None` when floats can't be compared
+fn compare(data_type: &DataType, lhs: u64, rhs: u64) -> Option<Ordering> {
+    match data_type {
+        DataType::Float(FloatType::F32) => {
+            f32::from_bits(lhs as u32).partial_cmp(&f32::from_bits(rhs as u32))
+        }
+        DataType::Float(FloatType::F64) => f64::from_bits(lhs).partial_cmp(&f64::from_bits(rhs)),
+        _ if data_type.is_signed_integer() => {
+            Some((sign_extend(data_type, lhs) as i64).cmp(&(sign_extend(data_type, rhs) as i64)))
+        }
+        _ => Some(lhs.cmp(&rhs)),
+    }
+}
@@ -1,16 +1,24 @@
 use std::error::Error;
 
 pub mod ast;
+pub mod builtins;
 pub mod bytecode;
+pub mod c;
 pub mod compiler;
+pub mod format;
+pub mod interpreter;
+pub mod lint;
+pub mod llvm;
 pub mod nasm;
 pub mod parser;
+pub mod serialize;
 pub mod symbol_table;
+pub mod syntax;
 pub mod types;
 
 pub type CompilerResult<'src, T> = Result<T, Box<dyn Error + 'src>>;
 
 #[inline(always)]
 pub const fn div_round_up(a: usize, b: usize) -> usize {
-    (a + b - 1) / b
+    a.div_ceil(b)
 }
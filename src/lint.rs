@@ -0,0 +1,274 @@
+use crate::{
+    ast::{Ast, AstKind},
+    parser::{Token, TokenKind},
+};
+use std::{collections::HashSet, fmt};
+
+// Every warning can be turned off on its own
+#[derive(Clone, Copy)]
+pub struct Lints {
+    pub infinite_loops: bool,
+    pub unused_variables: bool,
+    pub constant_comparisons: bool,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Self {
+            infinite_loops: true,
+            unused_variables: true,
+            constant_comparisons: true,
+        }
+    }
+}
+
+pub enum Warning<'src> {
+    InfiniteLoop,
+    UnusedVariable { name: &'src str },
+    ConstantComparison { oper: Token<'src>, result: bool },
+}
+
+impl fmt::Debug for Warning<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InfiniteLoop => write!(
+                f,
+                "this loop never stops and doesn't do anything, so it just hangs the program"
+            ),
+            Self::UnusedVariable { name } => write!(
+                f,
+                "variable `{name}` is never read, start its name with `_` if that's on purpose"
+            ),
+            Self::ConstantComparison { oper, result } => write!(
+                f,
+                "comparison `{oper:?}` at {} is always {result}, use a named constant if that's on purpose",
+                oper.position
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Warning<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")?;
+
+        match self {
+            Self::ConstantComparison { oper, .. } => {
+                writeln!(f)?;
+                oper.position.underline(f, oper.text.chars().count())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn lint<'src>(ast: &Ast<'src>, lints: Lints) -> Vec<Warning<'src>> {
+    let mut warnings = Vec::new();
+
+    lint_ast(ast, lints, &mut warnings);
+
+    if lints.unused_variables {
+        unused_variables(ast, &mut Vec::new(), &mut HashSet::new(), &mut warnings);
+    }
+
+    warnings
+}
+
+fn lint_ast<'src>(ast: &Ast<'src>, lints: Lints, warnings: &mut Vec<Warning<'src>>) {
+    if let AstKind::IfStatement { condition, .. } | AstKind::WhileLoop { condition, .. } = &ast.kind
+    {
+        if lints.constant_comparisons {
+            constant_comparisons(condition, warnings);
+        }
+    }
+
+    if let AstKind::WhileLoop {
+        ref condition,
+        ref body,
+    } = ast.kind
+    {
+        // Folded, so that `!false` and `1 == 1` count as well as `true`
+        let always_true = condition.fold().is_some_and(|value| value != 0);
+
+        if lints.infinite_loops && always_true && !body.has_effects() {
+            warnings.push(Warning::InfiniteLoop);
+        }
+    }
+
+    for child in ast.children() {
+        lint_ast(child, lints, warnings);
+    }
+}
+
+// Comparisons in a condition, and in the `&&`, `||` and `!` it is made of
+fn constant_comparisons<'src>(condition: &Ast<'src>, warnings: &mut Vec<Warning<'src>>) {
+    match &condition.kind {
+        AstKind::Infix { oper, lhs, rhs }
+            if matches!(oper.kind, TokenKind::And | TokenKind::Or) =>
+        {
+            constant_comparisons(lhs, warnings);
+            constant_comparisons(rhs, warnings);
+        }
+        AstKind::Prefix { oper, node } if oper.kind == TokenKind::Not => {
+            constant_comparisons(node, warnings)
+        }
+        AstKind::Infix { oper, .. }
+            if matches!(
+                oper.kind,
+                TokenKind::Equals
+                    | TokenKind::NotEquals
+                    | TokenKind::Greater
+                    | TokenKind::Less
+                    | TokenKind::GreaterOrEqual
+                    | TokenKind::LessOrEqual
+            ) && is_literal(condition) =>
+        {
+            if let Some(value) = condition.fold() {
+                warnings.push(Warning::ConstantComparison {
+                    oper: oper.clone(),
+                    result: value != 0,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+// Written out as nothing but literals. A named constant or `sizeof` can be meant to change with
+// the configuration or the platform, even though it folds just the same
+fn is_literal(ast: &Ast) -> bool {
+    match &ast.kind {
+        AstKind::Node { token } => {
+            matches!(token.text, "true" | "false")
+                || token
+                    .text
+                    .starts_with(|ch: char| ch.is_ascii_digit() || ch == '\'' || ch == '-')
+        }
+        AstKind::Prefix { node, .. } => is_literal(node),
+        AstKind::Infix { lhs, rhs, .. } => is_literal(lhs) && is_literal(rhs),
+        _ => false,
+    }
+}
+
+// The variables declared in each scope the walk is in, innermost last, and whether they were read
+type Scopes<'src> = Vec<Vec<(&'src str, bool)>>;
+
+// Names resolve to the innermost declaration, like in the symbol table
+fn unused_variables<'src>(
+    ast: &Ast<'src>,
+    scopes: &mut Scopes<'src>,
+    instances: &mut HashSet<&'src str>,
+    warnings: &mut Vec<Warning<'src>>,
+) {
+    match &ast.kind {
+        AstKind::Node {
+            token:
+                Token {
+                    kind: TokenKind::Ident,
+                    text,
+                    ..
+                },
+        } => {
+            let variable = scopes
+                .iter_mut()
+                .rev()
+                .flat_map(|scope| scope.iter_mut().rev())
+                .find(|(name, _)| name == text);
+
+            if let Some((_, read)) = variable {
+                *read = true;
+            }
+        }
+        // Writing to a variable isn't reading it
+        AstKind::Assign { lhs, rhs } if lhs.kind.assignable() && lhs.children().is_empty() => {
+            unused_variables(rhs, scopes, instances, warnings)
+        }
+        // The value can't refer to the variable yet
+        AstKind::VariableDeclaration(declaration) => {
+            if let Some(ref value) = declaration.value {
+                unused_variables(value, scopes, instances, warnings);
+            }
+
+            if let Some(scope) = scopes.last_mut() {
+                scope.push((declaration.name, false));
+            }
+        }
+        // Every instance of a generic function has the same variables, so only the first is checked
+        AstKind::FunctionDeclaration {
+            name,
+            type_arguments,
+            ..
+        } if !type_arguments.is_empty() && !instances.insert(name) => {}
+        // Arguments aren't checked, but they hide variables with the same name outside
+        AstKind::FunctionDeclaration {
+            arguments, body, ..
+        } => {
+            scopes.push(
+                arguments
+                    .iter()
+                    .map(|argument| (argument.name, true))
+                    .collect(),
+            );
+
+            unused_variables(body, scopes, instances, warnings);
+
+            scopes.pop();
+        }
+        AstKind::Block { statements, .. } => {
+            scopes.push(Vec::new());
+
+            for statement in statements {
+                unused_variables(statement, scopes, instances, warnings);
+            }
+
+            for (name, read) in scopes.pop().unwrap_or_default() {
+                if !read && !name.starts_with('_') {
+                    warnings.push(Warning::UnusedVariable { name });
+                }
+            }
+        }
+        _ => {
+            for child in ast.children() {
+                unused_variables(child, scopes, instances, warnings);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, symbol_table::SymbolTable};
+
+    fn warnings(source: &str) -> Vec<String> {
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+
+        lint(&ast, Lints::default())
+            .iter()
+            .map(|warning| format!("{warning:?}"))
+            .collect()
+    }
+
+    #[test]
+    fn loops_that_never_stop_and_do_nothing_warn() {
+        let infinite_loop = [format!("{:?}", Warning::InfiniteLoop)];
+
+        assert_eq!(warnings("{ while true {}; 0; }"), infinite_loop);
+        assert_eq!(warnings("{ while !false {}; 0; }"), infinite_loop);
+        assert!(warnings("{ while true { println(\"spinning\"); }; 0; }").is_empty());
+    }
+
+    #[test]
+    fn comparisons_of_literals_warn() {
+        assert_eq!(
+            warnings("{ if 1 == 2 { 3; } else { 4; }; }"),
+            ["comparison `==` at line 1, column 8 is always false, use a named constant if that's on purpose"]
+        );
+        assert_eq!(
+            warnings("{ let x: U64 = 1; if x < 2 && !(3 >= 3) { x; } else { 0; }; }"),
+            ["comparison `>=` at line 1, column 35 is always true, use a named constant if that's on purpose"]
+        );
+        assert!(warnings("{ const A: U64 = 1; if A == 1 { 3; } else { 4; }; }").is_empty());
+    }
+}
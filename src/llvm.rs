@@ -0,0 +1,925 @@
+use crate::{
+    builtins::Extern,
+    bytecode::{Argument, ByteCode, CodeGenerator, Function, OpCode},
+    types::{DataType, FloatType, IntType},
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+};
+
+// What the builtins are built on
+pub const PROLOGUE_LLVM: &str = "\
+declare i64 @write(i32, i8*, i64)
+declare i64 @read(i32, i8*, i64)
+declare i8* @aligned_alloc(i64, i64)
+declare void @free(i8*)
+declare i32 @dprintf(i32, i8*, ...)
+declare i8* @memset(i8*, i32, i64)
+declare i8* @memcpy(i8*, i8*, i64)
+declare void @llvm.trap()
+declare void @llvm.debugtrap()
+declare i64 @llvm.readcyclecounter()
+";
+
+pub const READ_CODE_LLVM: &str = "\
+define internal void @\"oil.read\"(i8* %text, i64 %length) {
+  call i64 @read(i32 0, i8* %text, i64 %length)
+  ret void
+}
+";
+
+pub const WRITE_CODE_LLVM: &str = "\
+define internal void @\"oil.write\"(i8* %text, i64 %length) {
+  call i64 @write(i32 1, i8* %text, i64 %length)
+  ret void
+}
+";
+
+pub const PRINT_INTEGER_CODE_LLVM: &str = "\
+@\"oil.signed_format\" = private constant [5 x i8] c\"%lld\\00\"
+@\"oil.unsigned_format\" = private constant [5 x i8] c\"%llu\\00\"
+
+define internal void @\"oil.print_integer\"(i64 %value, i8 %is_signed) {
+  %signed = icmp ne i8 %is_signed, 0
+  %signed_format = getelementptr [5 x i8], [5 x i8]* @\"oil.signed_format\", i64 0, i64 0
+  %unsigned_format = getelementptr [5 x i8], [5 x i8]* @\"oil.unsigned_format\", i64 0, i64 0
+  %format = select i1 %signed, i8* %signed_format, i8* %unsigned_format
+  call i32 (i32, i8*, ...) @dprintf(i32 1, i8* %format, i64 %value)
+  ret void
+}
+";
+
+// Whole pages that start out zeroed, like the `mmap` of the assembly version
+pub const MALLOC_CODE_LLVM: &str = "\
+define internal i8* @\"oil.malloc\"(i64 %length) {
+  %pages = add i64 %length, 4095
+  %size = and i64 %pages, -4096
+  %data = call i8* @aligned_alloc(i64 4096, i64 %size)
+  %failed = icmp eq i8* %data, null
+  br i1 %failed, label %done, label %clear
+clear:
+  call i8* @memset(i8* %data, i32 0, i64 %size)
+  br label %done
+done:
+  ret i8* %data
+}
+";
+
+pub const FREE_CODE_LLVM: &str = "\
+define internal void @\"oil.free\"(i8* %data, i64 %length) {
+  call void @free(i8* %data)
+  ret void
+}
+";
+
+// The instruction for the kind of number
+fn arithmetic(
+    data_type: &DataType,
+    float: &'static str,
+    signed: &'static str,
+    unsigned: &'static str,
+) -> &'static str {
+    if data_type.is_float() {
+        float
+    } else if data_type.is_signed_integer() {
+        signed
+    } else {
+        unsigned
+    }
+}
+
+// Moves between references and integers, or integers of different sizes
+fn conversion(from: &DataType, to: &DataType) -> &'static str {
+    match (from, to) {
+        (DataType::Ref(_) | DataType::Function { .. }, _) => "ptrtoint",
+        (_, DataType::Ref(_) | DataType::Function { .. }) => "inttoptr",
+        _ if from.size() > to.size() => "trunc",
+        _ if from.size() < to.size() && from.is_signed_integer() => "sext",
+        _ if from.size() < to.size() => "zext",
+        _ => "bitcast",
+    }
+}
+
+// Quoted, so names like `@main` or `name.0` are fine, and prefixed so they don't clash with libc
+fn symbol(name: &str) -> String {
+    format!("@\"oil.{name}\"")
+}
+
+// References are plain bytes that get cast to whatever they point to, aggregates are arrays of bytes
+fn llvm_type(data_type: &DataType) -> String {
+    match data_type {
+        DataType::Void => "void".to_string(),
+        DataType::Bool | DataType::Char => "i8".to_string(),
+        DataType::Int(_) | DataType::Enum(_) => format!("i{}", data_type.size() * 8),
+        DataType::Float(FloatType::F32) => "float".to_string(),
+        DataType::Float(FloatType::F64) => "double".to_string(),
+        DataType::Ref(_) | DataType::Function { .. } => "i8*".to_string(),
+        DataType::Struct(_) | DataType::Array { .. } => format!("[{} x i8]", data_type.size()),
+        DataType::Inferred(_) => unreachable!(),
+    }
+}
+
+// Without the pointer, `RT (A, B)`
+fn function_type(data_type: &DataType) -> String {
+    let DataType::Function {
+        return_type,
+        argument_types,
+    } = data_type
+    else {
+        unreachable!("Only functions have a function type")
+    };
+
+    let argument_types = argument_types
+        .iter()
+        .map(llvm_type)
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("{} ({argument_types})", llvm_type(return_type))
+}
+
+fn constant(value: u64, data_type: &DataType) -> String {
+    match data_type {
+        // Float constants are always written as the bits of a double
+        DataType::Float(FloatType::F32) => {
+            format!("0x{:016X}", (f32::from_bits(value as u32) as f64).to_bits())
+        }
+        DataType::Float(FloatType::F64) => format!("0x{value:016X}"),
+        DataType::Ref(_) | DataType::Function { .. } if value == 0 => "null".to_string(),
+        DataType::Ref(_) | DataType::Function { .. } => format!("inttoptr (i64 {value} to i8*)"),
+        _ => {
+            let unused_bits = 64 - data_type.size() * 8;
+
+            (((value << unused_bits) as i64) >> unused_bits).to_string()
+        }
+    }
+}
+
+// Textual LLVM IR. Every register gets a stack slot, which LLVM turns into SSA values itself
+pub struct LlvmBackend {
+    text: String,
+    // The type of every global, which is needed to take its address
+    symbol_types: HashMap<String, String>,
+    // Numbers the values and blocks of the function being generated
+    temporaries: usize,
+    // Whether the block was ended by a jump, so that the next instruction has to start a new one
+    terminated: bool,
+}
+
+impl LlvmBackend {
+    fn temporary(&mut self) -> String {
+        self.temporaries += 1;
+
+        format!("%t{}", self.temporaries - 1)
+    }
+
+    fn block(&mut self, name: &str) -> fmt::Result {
+        self.terminated = false;
+
+        writeln!(self.text, "{}:", name.trim_start_matches('%'))
+    }
+
+    fn instruction(&mut self, instruction: &str) -> fmt::Result {
+        if self.terminated {
+            let block = self.temporary();
+
+            self.block(&block)?;
+        }
+
+        writeln!(self.text, "  {instruction}")
+    }
+
+    fn value_instruction(&mut self, instruction: &str) -> Result<String, fmt::Error> {
+        let value = self.temporary();
+
+        self.instruction(&format!("{value} = {instruction}"))?;
+
+        Ok(value)
+    }
+
+    fn terminator(&mut self, instruction: &str) -> fmt::Result {
+        self.instruction(instruction)?;
+
+        self.terminated = true;
+
+        Ok(())
+    }
+
+    fn cast_pointer(
+        &mut self,
+        address: String,
+        data_type: &DataType,
+    ) -> Result<String, fmt::Error> {
+        match llvm_type(data_type).as_str() {
+            "i8" => Ok(address),
+            llvm_type => self.value_instruction(&format!("bitcast i8* {address} to {llvm_type}*")),
+        }
+    }
+
+    // A typed pointer to where the argument is stored
+    fn generate_pointer<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> Result<String, fmt::Error> {
+        match argument {
+            Argument::ReturnValue => Ok("%ret".to_string()),
+            Argument::Register(register_id) => Ok(format!("%r{register_id}")),
+            Argument::Argument(argument_id) => Ok(format!("%a{argument_id}")),
+            Argument::Deref(deref) => {
+                let address = self.generate_value(function, deref)?;
+
+                self.cast_pointer(address, function.argument_data_type(argument))
+            }
+            Argument::StructField { data, name } => {
+                let DataType::Struct(ref struct_type) = function.argument_data_type(data) else {
+                    panic!();
+                };
+
+                let address = self.generate_address(function, data)?;
+                let field = self.value_instruction(&format!(
+                    "getelementptr i8, i8* {address}, i64 {}",
+                    struct_type.field_offset(name)
+                ))?;
+
+                self.cast_pointer(field, function.argument_data_type(argument))
+            }
+            Argument::Constant { .. } | Argument::Symbol { .. } | Argument::VoidRegister => {
+                unreachable!()
+            }
+        }
+    }
+
+    // Where the argument is stored, as an `i8*`
+    fn generate_address<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> Result<String, fmt::Error> {
+        if let Argument::Symbol { name, .. } = argument {
+            return Ok(format!(
+                "bitcast ({}* {} to i8*)",
+                self.symbol_types[name],
+                symbol(name)
+            ));
+        }
+
+        let pointer = self.generate_pointer(function, argument)?;
+
+        match llvm_type(function.argument_data_type(argument)).as_str() {
+            "i8" => Ok(pointer),
+            llvm_type => self.value_instruction(&format!("bitcast {llvm_type}* {pointer} to i8*")),
+        }
+    }
+
+    fn generate_value<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> Result<String, fmt::Error> {
+        match argument {
+            Argument::Constant { value, data_type } => Ok(constant(*value, data_type)),
+            Argument::Symbol { .. } => self.generate_address(function, argument),
+            _ => {
+                let llvm_type = llvm_type(function.argument_data_type(argument));
+                let pointer = self.generate_pointer(function, argument)?;
+
+                self.value_instruction(&format!("load {llvm_type}, {llvm_type}* {pointer}"))
+            }
+        }
+    }
+
+    // An integer, reference or boolean widened to an `i64`
+    fn generate_i64<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> Result<String, fmt::Error> {
+        let data_type = function.argument_data_type(argument);
+
+        let llvm_type = llvm_type(data_type);
+        let value = self.generate_value(function, argument)?;
+
+        let conversion = match data_type {
+            DataType::Ref(_) | DataType::Function { .. } => "ptrtoint",
+            DataType::Int(IntType::S64 | IntType::U64) => return Ok(value),
+            _ if data_type.is_signed_integer() => "sext",
+            _ => "zext",
+        };
+
+        self.value_instruction(&format!("{conversion} {llvm_type} {value} to i64"))
+    }
+
+    fn generate_store<'src>(
+        &mut self,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        value: &str,
+    ) -> fmt::Result {
+        let llvm_type = llvm_type(function.argument_data_type(dst));
+        let pointer = self.generate_pointer(function, dst)?;
+
+        self.instruction(&format!(
+            "store {llvm_type} {value}, {llvm_type}* {pointer}"
+        ))
+    }
+
+    // References are calculated with as integers
+    fn generate_infix<'src>(
+        &mut self,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        src: &Argument<'src>,
+        operation: &str,
+    ) -> fmt::Result {
+        let data_type = function.argument_data_type(dst);
+
+        let result = if let DataType::Ref(_) = data_type {
+            let dst_wide = self.generate_i64(function, dst)?;
+            let src_wide = self.generate_i64(function, src)?;
+
+            let result =
+                self.value_instruction(&format!("{operation} i64 {dst_wide}, {src_wide}"))?;
+
+            self.value_instruction(&format!("inttoptr i64 {result} to i8*"))?
+        } else {
+            let llvm_type = llvm_type(data_type);
+            let dst_value = self.generate_value(function, dst)?;
+            let src_value = self.generate_value(function, src)?;
+
+            self.value_instruction(&format!("{operation} {llvm_type} {dst_value}, {src_value}"))?
+        };
+
+        self.generate_store(function, dst, &result)
+    }
+
+    // The `operation` is a predicate without the signedness, like `gt`
+    fn generate_comparison<'src>(
+        &mut self,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        lhs: &Argument<'src>,
+        rhs: &Argument<'src>,
+        operation: &str,
+    ) -> fmt::Result {
+        let data_type = function.argument_data_type(lhs);
+
+        let comparison = match operation {
+            "eq" if data_type.is_float() => "fcmp oeq".to_string(),
+            "ne" if data_type.is_float() => "fcmp une".to_string(),
+            _ if data_type.is_float() => format!("fcmp o{operation}"),
+            "eq" | "ne" => format!("icmp {operation}"),
+            _ if data_type.is_signed_integer() => format!("icmp s{operation}"),
+            _ => format!("icmp u{operation}"),
+        };
+
+        let llvm_type = llvm_type(data_type);
+        let lhs_value = self.generate_value(function, lhs)?;
+        let rhs_value = self.generate_value(function, rhs)?;
+
+        let flag = self.value_instruction(&format!(
+            "{comparison} {llvm_type} {lhs_value}, {rhs_value}"
+        ))?;
+        let result = self.value_instruction(&format!("zext i1 {flag} to i8"))?;
+
+        self.generate_store(function, dst, &result)
+    }
+
+    // A pointer of the type of `data`, `offset` bytes past the reference in `pointer`
+    fn generate_offset<'src>(
+        &mut self,
+        function: &Function<'src>,
+        pointer: &Argument<'src>,
+        offset: &Argument<'src>,
+        data: &Argument<'src>,
+    ) -> Result<String, fmt::Error> {
+        let address = self.generate_value(function, pointer)?;
+
+        let address = match offset {
+            Argument::VoidRegister => address,
+            _ => {
+                let offset = self.generate_i64(function, offset)?;
+
+                self.value_instruction(&format!("getelementptr i8, i8* {address}, i64 {offset}"))?
+            }
+        };
+
+        self.cast_pointer(address, function.argument_data_type(data))
+    }
+
+    fn generate_opcode<'src>(
+        &mut self,
+        function: &Function<'src>,
+        opcode: &OpCode<'src>,
+    ) -> fmt::Result {
+        match opcode {
+            OpCode::Mov { dst, src } => {
+                if dst != src {
+                    let dst_type = function.argument_data_type(dst);
+                    let src_type = function.argument_data_type(src);
+
+                    let mut value = self.generate_value(function, src)?;
+
+                    if llvm_type(dst_type) != llvm_type(src_type) {
+                        value = self.value_instruction(&format!(
+                            "{} {} {value} to {}",
+                            conversion(src_type, dst_type),
+                            llvm_type(src_type),
+                            llvm_type(dst_type)
+                        ))?;
+                    }
+
+                    self.generate_store(function, dst, &value)?;
+                }
+            }
+            OpCode::Add { dst, src } => {
+                let operation = arithmetic(function.argument_data_type(dst), "fadd", "add", "add");
+
+                self.generate_infix(function, dst, src, operation)?
+            }
+            OpCode::Sub { dst, src } => {
+                let operation = arithmetic(function.argument_data_type(dst), "fsub", "sub", "sub");
+
+                self.generate_infix(function, dst, src, operation)?
+            }
+            OpCode::Mul { dst, src } => {
+                let operation = arithmetic(function.argument_data_type(dst), "fmul", "mul", "mul");
+
+                self.generate_infix(function, dst, src, operation)?
+            }
+            OpCode::Div { dst, src } => {
+                let operation =
+                    arithmetic(function.argument_data_type(dst), "fdiv", "sdiv", "udiv");
+
+                self.generate_infix(function, dst, src, operation)?
+            }
+            OpCode::Mod { dst, src } => {
+                let operation =
+                    arithmetic(function.argument_data_type(dst), "frem", "srem", "urem");
+
+                self.generate_infix(function, dst, src, operation)?
+            }
+            OpCode::And { dst, src } => self.generate_infix(function, dst, src, "and")?,
+            OpCode::Or { dst, src } => self.generate_infix(function, dst, src, "or")?,
+            OpCode::Xor { dst, src } => self.generate_infix(function, dst, src, "xor")?,
+            OpCode::Not { dst } => {
+                let llvm_type = llvm_type(function.argument_data_type(dst));
+                let value = self.generate_value(function, dst)?;
+
+                let bit = self.value_instruction(&format!("and {llvm_type} {value}, 1"))?;
+                let result = self.value_instruction(&format!("xor {llvm_type} {bit}, 1"))?;
+
+                self.generate_store(function, dst, &result)?;
+            }
+            OpCode::BitNot { dst } => {
+                let llvm_type = llvm_type(function.argument_data_type(dst));
+                let value = self.generate_value(function, dst)?;
+
+                let result = self.value_instruction(&format!("xor {llvm_type} {value}, -1"))?;
+
+                self.generate_store(function, dst, &result)?;
+            }
+            OpCode::Negate { dst } => {
+                let llvm_type = llvm_type(function.argument_data_type(dst));
+                let value = self.generate_value(function, dst)?;
+
+                let result = if function.argument_data_type(dst).is_float() {
+                    self.value_instruction(&format!("fneg {llvm_type} {value}"))?
+                } else {
+                    self.value_instruction(&format!("sub {llvm_type} 0, {value}"))?
+                };
+
+                self.generate_store(function, dst, &result)?;
+            }
+            OpCode::Ref { dst, src } => {
+                let address = self.generate_address(function, src)?;
+
+                self.generate_store(function, dst, &address)?;
+            }
+            // The index is in bytes
+            OpCode::Index { dst, src, index } => {
+                let llvm_type = llvm_type(function.argument_data_type(dst));
+                let pointer = self.generate_offset(function, src, index, dst)?;
+
+                let value =
+                    self.value_instruction(&format!("load {llvm_type}, {llvm_type}* {pointer}"))?;
+
+                self.generate_store(function, dst, &value)?;
+            }
+            OpCode::SetIndex { dst, src, index } => {
+                let llvm_type = llvm_type(function.argument_data_type(src));
+                let pointer = self.generate_offset(function, dst, index, src)?;
+                let value = self.generate_value(function, src)?;
+
+                self.instruction(&format!(
+                    "store {llvm_type} {value}, {llvm_type}* {pointer}"
+                ))?;
+            }
+            OpCode::SetField { dst, src, offset } => {
+                let offset = Argument::Constant {
+                    value: *offset as u64,
+                    data_type: DataType::Int(IntType::U64),
+                };
+
+                let llvm_type = llvm_type(function.argument_data_type(src));
+                let pointer = self.generate_offset(function, dst, &offset, src)?;
+                let value = self.generate_value(function, src)?;
+
+                self.instruction(&format!(
+                    "store {llvm_type} {value}, {llvm_type}* {pointer}"
+                ))?;
+            }
+            OpCode::Copy { dst, src, size } => {
+                let dst_value = self.generate_value(function, dst)?;
+                let src_value = self.generate_value(function, src)?;
+                let size_value = self.generate_i64(function, size)?;
+
+                self.instruction(&format!(
+                    "call i8* @memcpy(i8* {dst_value}, i8* {src_value}, i64 {size_value})"
+                ))?;
+            }
+            OpCode::SetIfEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "eq")?
+            }
+            OpCode::SetIfNotEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "ne")?
+            }
+            OpCode::SetIfGreater { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "gt")?
+            }
+            OpCode::SetIfLess { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "lt")?
+            }
+            OpCode::SetIfGreaterOrEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "ge")?
+            }
+            OpCode::SetIfLessOrEqual { dst, lhs, rhs } => {
+                self.generate_comparison(function, dst, lhs, rhs, "le")?
+            }
+            // Falling through into a label still needs a jump in LLVM
+            OpCode::Label { label_id } => {
+                if !self.terminated {
+                    self.instruction(&format!("br label %L{label_id}"))?;
+                }
+
+                self.block(&format!("L{label_id}"))?;
+            }
+            OpCode::Goto { label_id } => self.terminator(&format!("br label %L{label_id}"))?,
+            OpCode::GotoIfZero {
+                condition,
+                label_id,
+            }
+            | OpCode::GotoIfNotZero {
+                condition,
+                label_id,
+            } => {
+                let data_type = function.argument_data_type(condition);
+
+                let llvm_type = llvm_type(data_type);
+                let value = self.generate_value(function, condition)?;
+                let zero = constant(0, data_type);
+
+                let is_zero =
+                    self.value_instruction(&format!("icmp eq {llvm_type} {value}, {zero}"))?;
+                let next = self.temporary();
+
+                let (if_zero, otherwise) = match opcode {
+                    OpCode::GotoIfZero { .. } => (format!("%L{label_id}"), next.clone()),
+                    _ => (next.clone(), format!("%L{label_id}")),
+                };
+
+                self.terminator(&format!(
+                    "br i1 {is_zero}, label {if_zero}, label {otherwise}"
+                ))?;
+                self.block(&next)?;
+            }
+            OpCode::Return => self.terminator("br label %return")?,
+            // Arguments are stored last to first
+            OpCode::Call {
+                dst,
+                lhs,
+                arguments,
+            } => {
+                let data_type = function.argument_data_type(lhs);
+
+                let DataType::Function { return_type, .. } = data_type else {
+                    unreachable!("This should be a function. If there was an error, it should have been caught in the typechecking phase.")
+                };
+
+                let callee = match lhs {
+                    Argument::Symbol { name, .. } => symbol(name),
+                    _ => {
+                        let pointer = self.generate_value(function, lhs)?;
+
+                        self.value_instruction(&format!(
+                            "bitcast i8* {pointer} to {}*",
+                            function_type(data_type)
+                        ))?
+                    }
+                };
+
+                let mut arguments_compiled = Vec::new();
+
+                for argument in arguments.iter().rev() {
+                    let llvm_type = llvm_type(function.argument_data_type(argument));
+                    let value = self.generate_value(function, argument)?;
+
+                    arguments_compiled.push(format!("{llvm_type} {value}"));
+                }
+
+                let call = format!(
+                    "call {} {callee}({})",
+                    llvm_type(return_type),
+                    arguments_compiled.join(", ")
+                );
+
+                if *dst == Argument::VoidRegister || **return_type == DataType::Void {
+                    self.instruction(&call)?;
+                } else {
+                    let result = self.value_instruction(&call)?;
+
+                    self.generate_store(function, dst, &result)?;
+                }
+            }
+            OpCode::SourceLine { .. } => {}
+            OpCode::Breakpoint => self.instruction("call void @llvm.debugtrap()")?,
+            OpCode::Trap => self.instruction("call void @llvm.trap()")?,
+            OpCode::ReadCycleCounter { dst } => {
+                let result = self.value_instruction("call i64 @llvm.readcyclecounter()")?;
+
+                self.generate_store(function, dst, &result)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Arguments are copied into slots of their own, so that they can be assigned to like registers
+    // Every other symbol is prefixed, so the extern gets a wrapper under that name which calls the
+    // real one. Functions the builtins rely on are already declared by the prologue
+    fn generate_extern<'src>(&mut self, extern_function: &Extern<'src>) -> fmt::Result {
+        let DataType::Function {
+            return_type,
+            argument_types,
+        } = &extern_function.data_type
+        else {
+            unreachable!("Extern declarations are always functions");
+        };
+
+        self.terminated = false;
+
+        let name = format!("@{}", extern_function.name);
+        let return_type = llvm_type(return_type);
+
+        if !PROLOGUE_LLVM.contains(&format!("{name}(")) {
+            let argument_types = argument_types
+                .iter()
+                .map(llvm_type)
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            writeln!(self.text, "declare {return_type} {name}({argument_types})")?;
+        }
+
+        let arguments = argument_types
+            .iter()
+            .enumerate()
+            .map(|(argument_id, data_type)| format!("{} %arg{argument_id}", llvm_type(data_type)))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        writeln!(
+            self.text,
+            "define internal {return_type} {}({arguments}) {{",
+            symbol(extern_function.name)
+        )?;
+
+        let call = format!("call {return_type} {name}({arguments})");
+
+        if return_type == "void" {
+            self.instruction(&call)?;
+            self.instruction("ret void")?;
+        } else {
+            self.instruction(&format!("%result = {call}"))?;
+            self.instruction(&format!("ret {return_type} %result"))?;
+        }
+
+        writeln!(self.text, "}}")
+    }
+
+    fn generate_function<'src>(&mut self, function: &Function<'src>) -> fmt::Result {
+        self.temporaries = 0;
+        self.terminated = false;
+
+        let return_type = llvm_type(&function.return_type);
+
+        let arguments = function
+            .argument_types
+            .iter()
+            .enumerate()
+            .map(|(argument_id, data_type)| format!("{} %arg{argument_id}", llvm_type(data_type)))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        writeln!(
+            self.text,
+            "define internal {return_type} {}({arguments}) {{",
+            symbol(&function.name)
+        )?;
+
+        if function.return_type != DataType::Void {
+            self.instruction(&format!("%ret = alloca {return_type}, align 8"))?;
+            self.instruction(&format!(
+                "store {return_type} zeroinitializer, {return_type}* %ret"
+            ))?;
+        }
+
+        for (argument_id, data_type) in function.argument_types.iter().enumerate() {
+            let llvm_type = llvm_type(data_type);
+
+            self.instruction(&format!("%a{argument_id} = alloca {llvm_type}, align 8"))?;
+            self.instruction(&format!(
+                "store {llvm_type} %arg{argument_id}, {llvm_type}* %a{argument_id}"
+            ))?;
+        }
+
+        // Zeroed, so nothing is read uninitialized
+        for (register_id, data_type) in function.register_types.iter().enumerate() {
+            if *data_type == DataType::Void {
+                continue;
+            }
+
+            let llvm_type = llvm_type(data_type);
+
+            let alignment = function.register_alignment(register_id);
+
+            self.instruction(&format!(
+                "%r{register_id} = alloca {llvm_type}, align {alignment}"
+            ))?;
+            self.instruction(&format!(
+                "store {llvm_type} zeroinitializer, {llvm_type}* %r{register_id}"
+            ))?;
+        }
+
+        for opcode in &function.opcodes {
+            self.generate_opcode(function, opcode)?;
+        }
+
+        if !self.terminated {
+            self.instruction("br label %return")?;
+        }
+
+        self.block("return")?;
+
+        if function.return_type == DataType::Void {
+            self.instruction("ret void")?;
+        } else {
+            let result =
+                self.value_instruction(&format!("load {return_type}, {return_type}* %ret"))?;
+
+            self.instruction(&format!("ret {return_type} {result}"))?;
+        }
+
+        writeln!(self.text, "}}\n")
+    }
+}
+
+// Strings are written out whole, with anything that isn't printable escaped
+fn string(bytes: &[u8]) -> String {
+    let mut text = String::new();
+
+    for byte in bytes.iter().chain(std::iter::once(&0)) {
+        match byte {
+            b'"' | b'\\' => text.push_str(&format!("\\{byte:02X}")),
+            b' '..=b'~' => text.push(*byte as char),
+            _ => text.push_str(&format!("\\{byte:02X}")),
+        }
+    }
+
+    format!("c\"{text}\"")
+}
+
+impl<'src> CodeGenerator<'src> for LlvmBackend {
+    fn generate(bytecode: &ByteCode<'src>) -> Result<String, fmt::Error> {
+        let mut llvm = Self {
+            text: String::from(PROLOGUE_LLVM),
+            symbol_types: HashMap::new(),
+            temporaries: 0,
+            terminated: false,
+        };
+
+        for builtin in &bytecode.builtins {
+            llvm.symbol_types
+                .insert(builtin.name.to_string(), function_type(&builtin.data_type));
+
+            write!(llvm.text, "\n{}", builtin.llvm)?;
+        }
+
+        writeln!(llvm.text)?;
+
+        for extern_function in &bytecode.externs {
+            llvm.symbol_types.insert(
+                extern_function.name.to_string(),
+                function_type(&extern_function.data_type),
+            );
+
+            llvm.generate_extern(extern_function)?;
+        }
+
+        for (id, bytes) in bytecode.strings.iter().enumerate() {
+            let name = ByteCode::string_symbol_name(id);
+            let llvm_type = format!("[{} x i8]", bytes.len() + 1);
+
+            writeln!(
+                llvm.text,
+                "{} = internal global {llvm_type} {}",
+                symbol(&name),
+                string(bytes)
+            )?;
+
+            llvm.symbol_types.insert(name, llvm_type);
+        }
+
+        for (id, global) in bytecode.globals.iter().enumerate() {
+            let name = ByteCode::global_symbol_name(id);
+            let llvm_type = format!("[{} x i8]", global.data_type.size().max(1));
+
+            let value = match global.bytes() {
+                Some(bytes) => format!(
+                    "[{}]",
+                    bytes
+                        .iter()
+                        .map(|byte| format!("i8 {byte}"))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+                None => "zeroinitializer".to_string(),
+            };
+
+            writeln!(
+                llvm.text,
+                "{} = internal global {llvm_type} {value}, align 8",
+                symbol(&name)
+            )?;
+
+            llvm.symbol_types.insert(name, llvm_type);
+        }
+
+        writeln!(llvm.text)?;
+
+        for function in &bytecode.functions {
+            let data_type = DataType::Function {
+                return_type: Box::new(function.return_type.clone()),
+                argument_types: function.argument_types.clone(),
+            };
+
+            llvm.symbol_types
+                .insert(function.name.clone(), function_type(&data_type));
+        }
+
+        for function in &bytecode.functions {
+            llvm.generate_function(function)?;
+        }
+
+        // `_start` calls `@main` and exits with its result
+        let main = bytecode
+            .functions
+            .iter()
+            .find(|function| function.name == "@main")
+            .expect("There is always a main function");
+
+        let return_type = llvm_type(&main.return_type);
+
+        writeln!(llvm.text, "define i32 @main() {{")?;
+
+        match main.return_type {
+            DataType::Bool | DataType::Int(_) => {
+                let conversion = match main.return_type.size() {
+                    8 => "trunc",
+                    4 => "bitcast",
+                    _ => "zext",
+                };
+
+                writeln!(
+                    llvm.text,
+                    "  %result = call {return_type} {}()",
+                    symbol(&main.name)
+                )?;
+                writeln!(
+                    llvm.text,
+                    "  %code = {conversion} {return_type} %result to i32"
+                )?;
+                writeln!(llvm.text, "  ret i32 %code")?;
+            }
+            _ => {
+                writeln!(llvm.text, "  call {return_type} {}()", symbol(&main.name))?;
+                writeln!(llvm.text, "  ret i32 0")?;
+            }
+        }
+
+        writeln!(llvm.text, "}}")?;
+
+        Ok(llvm.text)
+    }
+}
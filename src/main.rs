@@ -1,33 +1,193 @@
 use oil::{
-    bytecode::CodeGenerator, compiler::Compiler, nasm::Nasm, parser::Parser,
-    symbol_table::SymbolTable, CompilerResult,
+    bytecode::CodeGenerator,
+    c::CBackend,
+    llvm::LlvmBackend,
+    compiler::{Compiler, CompilerOptions},
+    format,
+    interpreter::Interpreter,
+    lint::{self, Lints},
+    nasm::{Nasm, NasmOptions},
+    parser::{Parser, ParserConfig},
+    serialize,
+    symbol_table::SymbolTable,
+    syntax::{Att, Intel},
+    CompilerResult,
 };
 use std::{
     env,
     fs::{self, File},
-    io::Write,
+    io::{self, Write},
+    process, str,
 };
 
+enum Backend {
+    Nasm,
+    C,
+    Llvm,
+}
+
 fn main() -> CompilerResult<'static, ()> {
     let mut args = env::args();
 
     assert!(args.next().is_some()); // executable
 
+    let (flags, arguments): (Vec<String>, Vec<String>) =
+        args.partition(|argument| argument.starts_with("--"));
+
+    let mut lints = Lints::default();
+    let mut att_syntax = false;
+    let mut compiler_options = CompilerOptions::default();
+    let mut nasm_options = NasmOptions::default();
+    let mut backend = Backend::Nasm;
+    let mut interpret = false;
+    let mut emit_bytecode = false;
+    let mut emit_binary = false;
+    let mut load_bytecode = false;
+    let mut format_source = false;
+    let mut map_file_path = None;
+
+    for flag in flags {
+        match flag.as_str() {
+            "--allow=infinite_loops" => lints.infinite_loops = false,
+            "--allow=unused_variables" => lints.unused_variables = false,
+            "--allow=constant_comparisons" => lints.constant_comparisons = false,
+            "--syntax=intel" => att_syntax = false,
+            "--syntax=att" => att_syntax = true,
+            "--bounds-checks=on" => compiler_options.bounds_checks = true,
+            "--bounds-checks=off" => compiler_options.bounds_checks = false,
+            "--division-checks=on" => compiler_options.division_checks = true,
+            "--division-checks=off" => compiler_options.division_checks = false,
+            "--register-allocation=on" => nasm_options.allocate_registers = true,
+            "--register-allocation=off" => nasm_options.allocate_registers = false,
+            // The source lines have to be kept in the bytecode for the comments to show them
+            "--comments=on" => {
+                nasm_options.comments = true;
+                compiler_options.source_lines = true;
+            }
+            "--comments=off" => {
+                nasm_options.comments = false;
+                compiler_options.source_lines = false;
+            }
+            "--overflow-checks=on" => nasm_options.overflow_checks = true,
+            "--overflow-checks=off" => nasm_options.overflow_checks = false,
+            "--backend=nasm" => backend = Backend::Nasm,
+            "--backend=c" => backend = Backend::C,
+            "--backend=llvm" => backend = Backend::Llvm,
+            "--interpret" => interpret = true,
+            "--emit-bytecode" => emit_bytecode = true,
+            "--emit-bytecode=binary" => emit_binary = true,
+            "--load-bytecode" => load_bytecode = true,
+            "--format" => format_source = true,
+            // Lists the symbols and their sizes, alongside the generated code
+            _ if flag.starts_with("--map=") => {
+                map_file_path = Some(flag["--map=".len()..].to_string())
+            }
+            _ => return Err(format!("Unknown flag `{flag}`.").into()),
+        }
+    }
+
+    let mut args = arguments.into_iter();
+
     let Some(input_file_path) = args.next() else {
         return Err("Not enough arguments provided.".into());
     };
 
-    let input_file = fs::read_to_string(input_file_path)?;
+    let input_file = fs::read(&input_file_path)?;
+
+    // The bytecode refers to it, so it has to live as long
+    let ast;
+
+    // Compiled before, so it goes straight to the backends
+    let bytecode = if load_bytecode {
+        serialize::deserialize(&input_file)?
+    } else {
+        let input_file = str::from_utf8(&input_file)?;
+
+        let mut symbol_table = SymbolTable::new();
+
+        // Printed here, since parse errors span several lines to show where they happened
+        let config = ParserConfig {
+            file: &input_file_path,
+            ..ParserConfig::default()
+        };
+
+        ast = match Parser::parse_with_config(input_file, &mut symbol_table, config) {
+            Ok(ast) => ast,
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("Error: {error}");
+                }
+
+                process::exit(1);
+            }
+        };
+
+        // Writes the program back out as source instead of compiling it
+        if format_source {
+            // They aren't part of the AST, so they would be lost. It was just parsed, so it lexes
+            if matches!(Parser::has_comments(input_file, config), Ok(true)) {
+                eprintln!("Error: `{input_file_path}` has comments, formatting would remove them.");
 
-    let mut symbol_table = SymbolTable::new();
+                process::exit(1);
+            }
 
-    let ast = Parser::parse(&input_file, &mut symbol_table).map_err(|e| e.to_string())?;
+            let source = match format::format(&ast, &mut symbol_table) {
+                Ok(source) => source,
+                Err(error) => {
+                    eprintln!("Error: {error}");
 
-    let bytecode = Compiler::compile(&ast, symbol_table);
+                    process::exit(1);
+                }
+            };
+
+            match args.next() {
+                Some(output_file_path) => fs::write(output_file_path, source)?,
+                None => print!("{source}"),
+            }
+
+            return Ok(());
+        }
+
+        for warning in lint::lint(&ast, lints) {
+            eprintln!("Warning: {warning}");
+        }
+
+        Compiler::compile_with(&ast, symbol_table, compiler_options)
+    };
 
     eprintln!("{bytecode:#?}");
 
-    let code = Nasm::generate(&bytecode)?;
+    // Exits like the program would have, instead of generating any code
+    if interpret {
+        match Interpreter::run(&bytecode) {
+            Ok(result) => process::exit((result & 0xff) as i32),
+            Err(error) => {
+                eprintln!("Error: {error}");
+
+                process::exit(error.exit_code());
+            }
+        }
+    }
+
+    // Not text, so it is written out as is
+    if emit_binary {
+        let bytes = serialize::serialize(&bytecode);
+
+        match args.next() {
+            Some(output_file_path) => fs::write(output_file_path, bytes)?,
+            None => io::stdout().write_all(&bytes)?,
+        }
+
+        return Ok(());
+    }
+
+    let code = match (backend, att_syntax) {
+        _ if emit_bytecode => bytecode.to_string(),
+        (Backend::C, _) => CBackend::generate(&bytecode)?,
+        (Backend::Llvm, _) => LlvmBackend::generate(&bytecode)?,
+        (Backend::Nasm, true) => Nasm::<Att>::generate_with(&bytecode, nasm_options)?,
+        (Backend::Nasm, false) => Nasm::<Intel>::generate_with(&bytecode, nasm_options)?,
+    };
 
     if let Some(output_file_path) = args.next() {
         let mut output_file = File::create(output_file_path)?;
@@ -37,5 +197,9 @@ fn main() -> CompilerResult<'static, ()> {
         println!("{code}");
     }
 
+    if let Some(map_file_path) = map_file_path {
+        fs::write(map_file_path, bytecode.generate_map()?)?;
+    }
+
     Ok(())
 }
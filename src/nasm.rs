@@ -1,15 +1,28 @@
 use crate::{
-    bytecode::{Argument, ByteCode, CodeGenerator, Function, OpCode},
-    types::DataType,
+    bytecode::{Argument, ByteCode, CodeGenerator, Function, OpCode, RegisterID},
+    syntax::{Address, Operand, Syntax},
+    types::{DataType, FloatType, IntType},
 };
-use std::fmt::{self, Write};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Write},
+    marker::PhantomData,
+};
+
+pub fn float_suffix(float_type: &FloatType) -> &'static str {
+    match float_type {
+        FloatType::F32 => "ss",
+        FloatType::F64 => "sd",
+    }
+}
 
-pub fn data_type_generate(data_type: &DataType) -> &'static str {
+// Extends the sign of the dividend into `rdx` (or `ah` for bytes) for `idiv`
+pub fn sign_extension_generate(data_type: &DataType) -> &'static str {
     match data_type.size() {
-        1 => "byte",
-        2 => "word",
-        4 => "dword",
-        8 => "qword",
+        1 => "cbw",
+        2 => "cwd",
+        4 => "cdq",
+        8 => "cqo",
         _ => unreachable!(),
     }
 }
@@ -38,7 +51,7 @@ pub enum NasmRegister {
 }
 
 impl NasmRegister {
-    fn generate(&self, data_type: &DataType) -> &str {
+    fn generate(&self, data_type: &DataType) -> &'static str {
         let text_options = match self {
             Self::Rax => &["al", "ax", "eax", "rax"],
             Self::Rbx => &["bl", "bx", "ebx", "rbx"],
@@ -48,10 +61,10 @@ impl NasmRegister {
             Self::Rdi => &["dil", "di", "edi", "rdi"],
             Self::Rsp => &["spl", "sp", "esp", "rsp"],
             Self::Rbp => &["bpl", "bp", "ebp", "rbp"],
-            Self::R8 => &["r8b", "r8d", "r8w", "r8"],
-            Self::R9 => &["r9b", "r9d", "r9w", "r9"],
-            Self::R10 => &["r10b", "r10d", "r10w", "r10"],
-            Self::R11 => &["r11b", "r11d", "r11w", "r11"],
+            Self::R8 => &["r8b", "r8w", "r8d", "r8"],
+            Self::R9 => &["r9b", "r9w", "r9d", "r9"],
+            Self::R10 => &["r10b", "r10w", "r10d", "r10"],
+            Self::R11 => &["r11b", "r11w", "r11d", "r11"],
         };
 
         text_options[match data_type.size() {
@@ -64,36 +77,74 @@ impl NasmRegister {
     }
 }
 
-const READ_CODE: &str = "\
+pub const READ_CODE: &str = "\
 read:
     enter 0, 0
     mov rax, 0x0        ; syscall read
+    mov rdx, rsi        ; length
+    mov rsi, rdi        ; text
     mov rdi, 0x0        ; stdin
-    mov rsi, [rbp + 24] ; text
-    mov rdx, [rbp + 16] ; length
     syscall
     leave
     ret
 ";
 
-const PRINT_CODE: &str = "\
-print:
+pub const WRITE_CODE: &str = "\
+write:
     enter 0, 0
     mov rax, 0x1        ; syscall write
+    mov rdx, rsi        ; length
+    mov rsi, rdi        ; text
     mov rdi, 0x1        ; stdout
-    mov rsi, [rbp + 24] ; text
-    mov rdx, [rbp + 16] ; length
     syscall
     leave
     ret
 ";
 
-const MALLOC_CODE: &str = "\
+// Digits are divided off the end into a buffer on the stack, so they come out last to first.
+// The second argument says whether the value is signed
+pub const PRINT_INTEGER_CODE: &str = "\
+print_integer:
+    enter 32, 0
+    mov rax, rdi
+    mov r8, rbp         ; start of the text, moves back with every digit
+    mov rcx, 10
+    test sil, sil
+    jz .digits
+    test rax, rax
+    jns .digits
+    neg rax
+.digits:
+    xor rdx, rdx
+    div rcx
+    add dl, 0x30        ; '0'
+    dec r8
+    mov [r8], dl
+    test rax, rax
+    jnz .digits
+    test sil, sil
+    jz .write
+    test rdi, rdi
+    jns .write
+    dec r8
+    mov byte [r8], 0x2d ; '-'
+.write:
+    mov rax, 0x1        ; syscall write
+    mov rdx, rbp
+    sub rdx, r8         ; length
+    mov rsi, r8         ; text
+    mov rdi, 0x1        ; stdout
+    syscall
+    leave
+    ret
+";
+
+pub const MALLOC_CODE: &str = "\
 malloc:
     enter 0, 0
     mov rax, 0x9        ; syscall mmap
+    mov rsi, rdi        ; length
     mov rdi, 0x0        ; addr chosed by kernel
-    mov rsi, [rbp + 16] ; length
     mov rdx, 0x3        ; PROT_READ | PROT_WRITE
     mov r10, 0x22       ; MAP_ANONYMOUS | MAP_PRIVATE
     mov r8, -1          ; no file descriptor
@@ -101,106 +152,617 @@ malloc:
     syscall
     cmp rax, -1    ; MAP_FAILED
     mov rbx, 0x0   ; null
-    mov qword [rbp + 24], rax
     leave
     ret
 ";
 
-const FREE_CODE: &str = "\
+pub const FREE_CODE: &str = "\
 free:
     enter 0, 0
-    mov rax, 0xb        ; syscall munmap
-    mov rdi, [rbp + 24] ; addr
-    mov rsi, [rbp + 16] ; length
+    mov rax, 0xb        ; syscall munmap, addr and length are already in rdi and rsi
     syscall
     leave
     ret
 ";
 
-const ENTRY_CODE: &str = "\
+pub const ENTRY_CODE: &str = "\
 _start:
-    sub rsp, 0x8
+    sub rsp, 0x10 ; in case the result doesn't fit in rax, and to align the stack
     call @main
+    mov rdi, rax  ; exit code
     mov rax, 0x3c ; syscall exit
-    pop rdi       ; exit code
     syscall
 ";
 
-pub struct Nasm {
+pub const READ_CODE_GAS: &str = "\
+read:
+    enter $0, $0
+    mov $0x0, %rax      # syscall read
+    mov %rsi, %rdx      # length
+    mov %rdi, %rsi      # text
+    mov $0x0, %rdi      # stdin
+    syscall
+    leave
+    ret
+";
+
+pub const WRITE_CODE_GAS: &str = "\
+write:
+    enter $0, $0
+    mov $0x1, %rax      # syscall write
+    mov %rsi, %rdx      # length
+    mov %rdi, %rsi      # text
+    mov $0x1, %rdi      # stdout
+    syscall
+    leave
+    ret
+";
+
+pub const PRINT_INTEGER_CODE_GAS: &str = "\
+print_integer:
+    enter $32, $0
+    mov %rdi, %rax
+    mov %rbp, %r8       # start of the text, moves back with every digit
+    mov $10, %rcx
+    test %sil, %sil
+    jz .Lprint_integer.digits
+    test %rax, %rax
+    jns .Lprint_integer.digits
+    neg %rax
+.Lprint_integer.digits:
+    xor %rdx, %rdx
+    div %rcx
+    add $0x30, %dl      # '0'
+    dec %r8
+    mov %dl, (%r8)
+    test %rax, %rax
+    jnz .Lprint_integer.digits
+    test %sil, %sil
+    jz .Lprint_integer.write
+    test %rdi, %rdi
+    jns .Lprint_integer.write
+    dec %r8
+    movb $0x2d, (%r8)   # '-'
+.Lprint_integer.write:
+    mov $0x1, %rax      # syscall write
+    mov %rbp, %rdx
+    sub %r8, %rdx       # length
+    mov %r8, %rsi       # text
+    mov $0x1, %rdi      # stdout
+    syscall
+    leave
+    ret
+";
+
+pub const MALLOC_CODE_GAS: &str = "\
+malloc:
+    enter $0, $0
+    mov $0x9, %rax      # syscall mmap
+    mov %rdi, %rsi      # length
+    mov $0x0, %rdi      # addr chosed by kernel
+    mov $0x3, %rdx      # PROT_READ | PROT_WRITE
+    mov $0x22, %r10     # MAP_ANONYMOUS | MAP_PRIVATE
+    mov $-1, %r8        # no file descriptor
+    mov $0, %r9         # no offset
+    syscall
+    cmp $-1, %rax  # MAP_FAILED
+    mov $0x0, %rbx # null
+    leave
+    ret
+";
+
+pub const FREE_CODE_GAS: &str = "\
+free:
+    enter $0, $0
+    mov $0xb, %rax      # syscall munmap, addr and length are already in rdi and rsi
+    syscall
+    leave
+    ret
+";
+
+pub const ENTRY_CODE_GAS: &str = "\
+_start:
+    sub $0x10, %rsp # in case the result doesn't fit in rax, and to align the stack
+    call \"@main\"
+    mov %rax, %rdi  # exit code
+    mov $0x3c, %rax # syscall exit
+    syscall
+";
+
+// System V: the first six arguments that fit in a general purpose register are passed in these
+const ARGUMENT_REGISTERS: [NasmRegister; 6] = [
+    NasmRegister::Rdi,
+    NasmRegister::Rsi,
+    NasmRegister::Rdx,
+    NasmRegister::Rcx,
+    NasmRegister::R8,
+    NasmRegister::R9,
+];
+
+// And floats in these
+const FLOAT_ARGUMENT_REGISTERS: [&str; 8] = [
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7",
+];
+
+#[derive(Clone, Copy)]
+enum ArgumentLocation {
+    Register(NasmRegister),
+    FloatRegister(&'static str),
+    // From the first argument on the stack, which is at the lowest address
+    Stack(usize),
+}
+
+// Where each argument is passed, first to last, and how much of the stack they take up
+fn argument_locations(argument_types: &[DataType]) -> (Vec<ArgumentLocation>, usize) {
+    let mut registers = ARGUMENT_REGISTERS.iter();
+    let mut float_registers = FLOAT_ARGUMENT_REGISTERS.iter();
+    let mut stack_size = 0;
+
+    let locations = argument_types
+        .iter()
+        .map(|data_type| {
+            let fits = matches!(
+                data_type,
+                DataType::Bool
+                    | DataType::Char
+                    | DataType::Int(_)
+                    | DataType::Enum(_)
+                    | DataType::Ref(_)
+                    | DataType::Function { .. }
+            );
+
+            let location = match data_type {
+                DataType::Float(_) => float_registers
+                    .next()
+                    .map(|xmm| ArgumentLocation::FloatRegister(xmm)),
+                _ if fits => registers
+                    .next()
+                    .map(|nasm_register| ArgumentLocation::Register(*nasm_register)),
+                _ => None,
+            };
+
+            match location {
+                Some(location) => location,
+                None => {
+                    stack_size += data_type.size_aligned();
+
+                    ArgumentLocation::Stack(stack_size - data_type.size_aligned())
+                }
+            }
+        })
+        .collect();
+
+    (locations, stack_size)
+}
+
+// Anything bigger is returned through a slot the caller reserves above the arguments.
+// Floats come back in `xmm0` instead of `rax`
+fn is_returned_in_register(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Bool
+            | DataType::Char
+            | DataType::Int(_)
+            | DataType::Enum(_)
+            | DataType::Float(_)
+            | DataType::Ref(_)
+            | DataType::Function { .. }
+    )
+}
+
+// Never used as scratch registers by the instructions below, so values can be kept in them
+const ALLOCATABLE_REGISTERS: [NasmRegister; 3] =
+    [NasmRegister::R9, NasmRegister::R10, NasmRegister::R11];
+
+// Linear scan over the registers that fit in a general purpose register, whose address is never taken,
+// and that aren't alive during a call, which could overwrite them. The rest stay on the stack
+fn allocate_registers(function: &Function) -> HashMap<RegisterID, NasmRegister> {
+    let calls: Vec<usize> = function
+        .opcodes
+        .iter()
+        .enumerate()
+        .filter(|(_, opcode)| matches!(opcode, OpCode::Call { .. }))
+        .map(|(n, _)| n)
+        .collect();
+
+    let address_taken: HashSet<RegisterID> = function
+        .opcodes
+        .iter()
+        .filter_map(|opcode| match opcode {
+            OpCode::Ref { src, .. } => src.register(),
+            _ => None,
+        })
+        .collect();
+
+    let mut live_ranges: Vec<(RegisterID, usize, usize)> = function
+        .live_ranges()
+        .into_iter()
+        .enumerate()
+        .filter_map(|(register_id, live_range)| {
+            let (start, end) = live_range?;
+
+            let fits = matches!(
+                function.register_types[register_id],
+                DataType::Bool
+                    | DataType::Char
+                    | DataType::Int(_)
+                    | DataType::Enum(_)
+                    | DataType::Ref(_)
+            );
+
+            (fits
+                && !address_taken.contains(&register_id)
+                && !calls.iter().any(|call| start < *call && *call < end))
+                .then_some((register_id, start, end))
+        })
+        .collect();
+
+    live_ranges.sort_by_key(|(_, start, _)| *start);
+
+    let mut allocation = HashMap::new();
+    let mut active: Vec<(RegisterID, usize, NasmRegister)> = Vec::new();
+    let mut free = ALLOCATABLE_REGISTERS.to_vec();
+
+    for (register_id, start, end) in live_ranges {
+        active.retain(|(_, active_end, nasm_register)| {
+            if *active_end < start {
+                free.push(*nasm_register);
+            }
+
+            *active_end >= start
+        });
+
+        if let Some(nasm_register) = free.pop() {
+            allocation.insert(register_id, nasm_register);
+            active.push((register_id, end, nasm_register));
+
+            continue;
+        }
+
+        // Out of registers, whichever lives the longest goes to the stack
+        let Some(longest) = active
+            .iter_mut()
+            .max_by_key(|(_, active_end, _)| *active_end)
+            .filter(|(_, active_end, _)| *active_end > end)
+        else {
+            continue;
+        };
+
+        allocation.remove(&longest.0);
+        allocation.insert(register_id, longest.2);
+
+        *longest = (register_id, end, longest.2);
+    }
+
+    allocation
+}
+
+// What `generate` does, unless told otherwise
+#[derive(Clone, Copy)]
+pub struct NasmOptions {
+    // Keeps short-lived values in registers
+    pub allocate_registers: bool,
+    // Puts the opcode each group of instructions comes from above it
+    pub comments: bool,
+    // Traps when integer addition, subtraction or multiplication doesn't fit in its type,
+    // instead of wrapping around
+    pub overflow_checks: bool,
+}
+
+impl Default for NasmOptions {
+    fn default() -> Self {
+        Self {
+            allocate_registers: true,
+            comments: false,
+            overflow_checks: false,
+        }
+    }
+}
+
+// Instruction selection for x86-64, written down in the syntax `S`
+pub struct Nasm<S: Syntax> {
     text: String,
+    options: NasmOptions,
+    // Of the function being generated
+    allocation: HashMap<RegisterID, NasmRegister>,
+    syntax: PhantomData<S>,
 }
 
-impl Nasm {
-    fn generate_argument(
+impl<S: Syntax> Nasm<S> {
+    // Every register on the stack, mostly to compare against the allocated version
+    pub fn generate_without_allocation(bytecode: &ByteCode) -> Result<String, fmt::Error> {
+        Self::generate_with(
+            bytecode,
+            NasmOptions {
+                allocate_registers: false,
+                ..NasmOptions::default()
+            },
+        )
+    }
+
+    // Where the register was put, if it was allocated one
+    fn allocated(&self, function: &Function, argument: &Argument) -> Option<&'static str> {
+        let Argument::Register(register_id) = argument else {
+            return None;
+        };
+
+        self.allocation
+            .get(register_id)
+            .map(|nasm_register| nasm_register.generate(&function.register_types[*register_id]))
+    }
+
+    fn instruction(&mut self, mnemonic: &str, operands: &[Operand]) -> fmt::Result {
+        writeln!(self.text, "    {}", S::instruction(mnemonic, operands))
+    }
+
+    fn local_label(function: &Function, name: &str) -> Operand {
+        Operand::Symbol(S::local_label(&function.name, name))
+    }
+
+    fn generate_function<'src>(&mut self, function: &Function<'src>) -> fmt::Result {
+        self.allocation = if self.options.allocate_registers {
+            allocate_registers(function)
+        } else {
+            HashMap::new()
+        };
+
+        writeln!(self.text, "{}", S::label(&function.name))?;
+
+        // Entered with the return address on an aligned stack, so after `rbp` is pushed a frame
+        // that is a multiple of 16 bytes keeps it aligned for the calls in the function
+        self.instruction(
+            "enter",
+            &[
+                Operand::Constant(Self::frame_size(function).next_multiple_of(16) as u64),
+                Operand::Constant(0),
+            ],
+        )?;
+
+        // Arguments passed in registers are kept in the frame like the rest
+        let (locations, _) = argument_locations(&function.argument_types);
+
+        for (argument_id, location) in locations.into_iter().enumerate() {
+            let argument = Argument::Argument(argument_id);
+
+            match location {
+                ArgumentLocation::Register(nasm_register) => {
+                    let argument_compiled =
+                        self.generate_argument(function, &argument, NasmRegister::Rax)?;
+
+                    self.instruction(
+                        "mov",
+                        &[
+                            argument_compiled,
+                            register(nasm_register, &function.argument_types[argument_id]),
+                        ],
+                    )?;
+                }
+                ArgumentLocation::FloatRegister(xmm) => {
+                    self.generate_float_store(function, &argument, xmm)?;
+                }
+                ArgumentLocation::Stack(_) => {}
+            }
+        }
+
+        for opcode in &function.opcodes {
+            if self.options.comments
+                && !matches!(opcode, OpCode::Label { .. } | OpCode::SourceLine { .. })
+            {
+                writeln!(self.text, "    {}", S::comment(&opcode.to_string()))?;
+            }
+
+            self.generate_opcode(function, opcode)?;
+        }
+
+        if function
+            .opcodes
+            .iter()
+            .any(|opcode| matches!(opcode, OpCode::Return))
+        {
+            writeln!(
+                self.text,
+                "{}",
+                S::label(&S::local_label(&function.name, "return"))
+            )?;
+        }
+
+        if let DataType::Float(_) = function.return_type {
+            self.generate_float_load(function, "xmm0", &Argument::ReturnValue, NasmRegister::Rax)?;
+        } else if is_returned_in_register(&function.return_type) {
+            let rax = register(NasmRegister::Rax, &function.return_type);
+
+            let return_value =
+                self.generate_argument(function, &Argument::ReturnValue, NasmRegister::Rax)?;
+
+            self.instruction("mov", &[rax, return_value])?;
+        }
+
+        self.instruction("leave", &[])?;
+        self.instruction("ret", &[])?;
+
+        if self.options.overflow_checks
+            && function.opcodes.iter().any(|opcode| match opcode {
+                OpCode::Add { dst, .. } | OpCode::Sub { dst, .. } | OpCode::Mul { dst, .. } => {
+                    matches!(function.argument_data_type(dst), DataType::Int(_))
+                }
+                OpCode::Negate { dst } => function.argument_data_type(dst).is_signed_integer(),
+                _ => false,
+            })
+        {
+            writeln!(
+                self.text,
+                "{}",
+                S::label(&S::local_label(&function.name, "overflow"))
+            )?;
+
+            self.instruction("ud2", &[])?;
+        }
+
+        Ok(())
+    }
+
+    // Jumps to the trap at the end of the function when the last instruction overflowed.
+    // Unsigned overflow only shows up in the carry flag, except after `mul`, which sets both
+    fn generate_overflow_check<'src>(
         &mut self,
-        function: &Function,
-        argument: &Argument,
-        scratch: NasmRegister,
-        lvalue: bool,
-    ) -> Result<String, fmt::Error> {
-        let mut can_rvalue = true;
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        unsigned_jump: &str,
+    ) -> fmt::Result {
+        let DataType::Int(int_type) = function.argument_data_type(dst) else {
+            return Ok(());
+        };
 
-        let text = match argument {
+        if !self.options.overflow_checks {
+            return Ok(());
+        }
+
+        let jump = if int_type.is_signed() {
+            "jo"
+        } else {
+            unsigned_jump
+        };
+
+        self.instruction(jump, &[Self::local_label(function, "overflow")])
+    }
+
+    // A result returned in `rax` is kept below everything else until then
+    fn frame_size(function: &Function) -> usize {
+        if is_returned_in_register(&function.return_type) {
+            function.stack_size() + function.return_type.size_aligned()
+        } else {
+            function.stack_size()
+        }
+    }
+
+    // Where the argument is stored, for arguments that live in memory
+    fn generate_address<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+        scratch: NasmRegister,
+    ) -> Result<Address, fmt::Error> {
+        let address = match argument {
+            // Addresses point to the lowest byte of the data, struct fields go upwards from there
+            Argument::ReturnValue if is_returned_in_register(&function.return_type) => {
+                Address::register("rbp").offset(-(Self::frame_size(function) as i64))
+            }
+            // Above the arguments passed on the stack
             Argument::ReturnValue => {
-                format!(
-                    "rbp + {}",
-                    8 + function.arguments_size + function.return_type.size_aligned()
-                )
+                let (_, stack_size) = argument_locations(&function.argument_types);
+
+                Address::register("rbp").offset(16 + stack_size as i64)
             }
-            Argument::Register(register_id) => {
-                format!("rbp - {}", 8 + function.register_position(*register_id))
+            // Over-aligned ones are rounded down to their alignment, into the room left below them
+            Argument::Register(register_id) if function.register_alignment(*register_id) > 8 => {
+                let scratch_compiled = scratch.generate(&DataType::Int(IntType::U64));
+                let alignment = function.register_alignment(*register_id) as u64;
+
+                let address = Address::register("rbp").offset(
+                    -((function.register_position(*register_id)
+                        + function.register_types[*register_id].size_aligned())
+                        as i64),
+                );
+
+                let scratch_operand = Operand::Register(scratch_compiled);
+
+                self.instruction("lea", &[scratch_operand.clone(), Operand::memory(address)])?;
+                self.instruction(
+                    "and",
+                    &[scratch_operand, Operand::Constant(alignment.wrapping_neg())],
+                )?;
+
+                Address::register(scratch_compiled)
             }
+            Argument::Register(register_id) => Address::register("rbp").offset(
+                -((function.register_position(*register_id)
+                    + function.register_types[*register_id].size_aligned())
+                    as i64),
+            ),
+            // Arguments passed in registers are put in the space reserved for them below the registers
             Argument::Argument(argument_id) => {
-                format!(
-                    "rbp + {}",
-                    8 + function.arguments_size - function.argument_position(*argument_id)
-                )
+                let (locations, _) = argument_locations(&function.argument_types);
+
+                match locations[*argument_id] {
+                    ArgumentLocation::Register(_) | ArgumentLocation::FloatRegister(_) => {
+                        Address::register("rbp").offset(
+                            -((function.registers_size
+                                + function.argument_position(*argument_id)
+                                + function.argument_types[*argument_id].size_aligned())
+                                as i64),
+                        )
+                    }
+                    ArgumentLocation::Stack(offset) => {
+                        Address::register("rbp").offset(16 + offset as i64)
+                    }
+                }
+            }
+            // The pointer is already in a register
+            Argument::Deref(deref) if self.allocated(function, deref).is_some() => {
+                Address::register(self.allocated(function, deref).unwrap())
+            }
+            // Statics are at a fixed address, which is what the symbol already is
+            Argument::Deref(deref) if matches!(**deref, Argument::Symbol { .. }) => {
+                self.generate_address(function, deref, scratch)?
             }
             Argument::Deref(deref) => {
-                let deref_compiled = self.generate_argument(function, deref, scratch, true)?;
+                let deref_address = self.generate_address(function, deref, scratch)?;
                 let scratch_compiled = scratch.generate(function.argument_data_type(deref));
 
-                writeln!(self.text, "    mov {scratch_compiled}, [{deref_compiled}]")?;
+                self.instruction(
+                    "mov",
+                    &[
+                        Operand::Register(scratch_compiled),
+                        Operand::memory(deref_address),
+                    ],
+                )?;
 
-                scratch_compiled.to_owned()
+                Address::register(scratch_compiled)
             }
             Argument::StructField { ref data, name } => {
-                let DataType::Struct(ref fields) = function.argument_data_type(data) else { panic!(); };
-
-                let offset = fields
-                    .iter()
-                    .take_while(|(find_name, _)| find_name != name)
-                    .map(|(_, data_type)| data_type.size_aligned())
-                    .sum::<usize>();
+                let DataType::Struct(ref struct_type) = function.argument_data_type(data) else {
+                    panic!();
+                };
 
-                let data_compiled = self.generate_argument(function, data, scratch, true)?;
+                let offset = struct_type.field_offset(name);
 
-                format!("{data_compiled} - {offset}")
-            }
-            Argument::Constant { value, .. } => {
-                can_rvalue = false;
-                value.to_string()
-            }
-            Argument::Symbol { name, .. } => {
-                can_rvalue = false;
-                name.clone()
+                self.generate_address(function, data, scratch)?
+                    .offset(offset as i64)
             }
+            Argument::Symbol { name, .. } => Address::symbol(name.clone()),
             _ => unreachable!(),
         };
 
-        if !lvalue && can_rvalue {
-            Ok(format!(
-                "{} [{text}]",
-                data_type_generate(function.argument_data_type(argument))
-            ))
-        } else {
-            Ok(text)
+        Ok(address)
+    }
+
+    fn generate_argument<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+        scratch: NasmRegister,
+    ) -> Result<Operand, fmt::Error> {
+        match argument {
+            Argument::Constant { value, .. } => Ok(Operand::Constant(*value)),
+            Argument::Symbol { name, .. } => Ok(Operand::Symbol(name.clone())),
+            Argument::Register(_) if self.allocated(function, argument).is_some() => {
+                Ok(Operand::Register(self.allocated(function, argument).unwrap()))
+            }
+            _ => Ok(Operand::Memory {
+                size: Some(function.argument_data_type(argument).size()),
+                address: self.generate_address(function, argument, scratch)?,
+            }),
         }
     }
 
-    fn generate_push_argument(&mut self, function: &Function, argument: &Argument) -> fmt::Result {
-        if let DataType::Struct(ref fields) = function.argument_data_type(argument) {
-            for (name, _) in fields.iter().rev() {
+    // Fields are pushed last to first, so that the first one ends up at the lowest address
+    fn generate_push_argument<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> fmt::Result {
+        if let DataType::Struct(ref struct_type) = function.argument_data_type(argument) {
+            for (name, _) in struct_type.fields().iter().rev() {
                 self.generate_push_argument(
                     function,
                     &Argument::StructField {
@@ -209,24 +771,39 @@ impl Nasm {
                     },
                 )?;
             }
+        } else if let data_type @ DataType::Array { .. } = function.argument_data_type(argument) {
+            // A quadword at a time, the last one first
+            let address = self.generate_address(function, argument, NasmRegister::Rbx)?;
+
+            for offset in (0..data_type.size_aligned()).step_by(8).rev() {
+                self.instruction(
+                    "push",
+                    &[Operand::Memory {
+                        size: Some(8),
+                        address: address.clone().offset(offset as i64),
+                    }],
+                )?;
+            }
         } else {
-            let rax = NasmRegister::Rax.generate(function.argument_data_type(argument));
+            let rax = register(NasmRegister::Rax, function.argument_data_type(argument));
 
             let argument_compiled =
-                self.generate_argument(function, argument, NasmRegister::Rbx, false)?;
+                self.generate_argument(function, argument, NasmRegister::Rbx)?;
 
-            writeln!(
-                self.text,
-                "    mov {rax}, {argument_compiled}\n    push rax"
-            )?;
+            self.instruction("mov", &[rax, argument_compiled])?;
+            self.instruction("push", &[Operand::Register("rax")])?;
         }
 
         Ok(())
     }
 
-    fn generate_pop_argument(&mut self, function: &Function, argument: &Argument) -> fmt::Result {
-        if let DataType::Struct(ref fields) = function.argument_data_type(argument) {
-            for (name, _) in fields.iter().rev() {
+    fn generate_pop_argument<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> fmt::Result {
+        if let DataType::Struct(ref struct_type) = function.argument_data_type(argument) {
+            for (name, _) in struct_type.fields() {
                 self.generate_pop_argument(
                     function,
                     &Argument::StructField {
@@ -235,29 +812,44 @@ impl Nasm {
                     },
                 )?;
             }
+        } else if let data_type @ DataType::Array { .. } = function.argument_data_type(argument) {
+            let address = self.generate_address(function, argument, NasmRegister::Rbx)?;
+
+            for offset in (0..data_type.size_aligned()).step_by(8) {
+                self.instruction(
+                    "pop",
+                    &[Operand::Memory {
+                        size: Some(8),
+                        address: address.clone().offset(offset as i64),
+                    }],
+                )?;
+            }
         } else {
-            let rax = NasmRegister::Rax.generate(function.argument_data_type(argument));
+            let rax = register(NasmRegister::Rax, function.argument_data_type(argument));
 
             let argument_compiled =
-                self.generate_argument(function, argument, NasmRegister::Rbx, false)?;
+                self.generate_argument(function, argument, NasmRegister::Rbx)?;
 
-            writeln!(self.text, "    pop rax\n    mov {argument_compiled}, {rax}")?;
+            self.instruction("pop", &[Operand::Register("rax")])?;
+            self.instruction("mov", &[argument_compiled, rax])?;
         }
 
         Ok(())
     }
 
-    fn generate_infix(
+    fn generate_infix<'src>(
         &mut self,
-        function: &Function,
-        dst: &Argument,
-        src: &Argument,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        src: &Argument<'src>,
         operation: &str,
     ) -> fmt::Result {
-        if let DataType::Struct(ref fields) = function.argument_data_type(src) {
-            let DataType::Struct(_) = function.argument_data_type(dst) else { unreachable!(); };
+        if let DataType::Struct(ref struct_type) = function.argument_data_type(src) {
+            let DataType::Struct(_) = function.argument_data_type(dst) else {
+                unreachable!();
+            };
 
-            for (name, _) in fields {
+            for (name, _) in struct_type.fields() {
                 self.generate_infix(
                     function,
                     &Argument::StructField {
@@ -271,179 +863,415 @@ impl Nasm {
                     operation,
                 )?;
             }
+        } else if let data_type @ DataType::Array { .. } = function.argument_data_type(src) {
+            // The biggest pieces that fit, so nothing past the end of the array is touched
+            let src_address = self.generate_address(function, src, NasmRegister::Rbx)?;
+            let dst_address = self.generate_address(function, dst, NasmRegister::Rcx)?;
+
+            let mut offset = 0;
+
+            while offset < data_type.size() {
+                let piece = match data_type.size() - offset {
+                    8.. => IntType::U64,
+                    4..=7 => IntType::U32,
+                    2..=3 => IntType::U16,
+                    _ => IntType::U8,
+                };
+
+                let rax = register(NasmRegister::Rax, &DataType::Int(piece));
+
+                self.instruction(
+                    "mov",
+                    &[
+                        rax.clone(),
+                        Operand::memory(src_address.clone().offset(offset as i64)),
+                    ],
+                )?;
+                self.instruction(
+                    operation,
+                    &[
+                        Operand::memory(dst_address.clone().offset(offset as i64)),
+                        rax,
+                    ],
+                )?;
+
+                offset += piece.size();
+            }
         } else {
-            let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
+            let rax = register(NasmRegister::Rax, function.argument_data_type(dst));
+
+            let src_compiled = self.generate_argument(function, src, NasmRegister::Rbx)?;
+            let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rcx)?;
 
-            let src_compiled = self.generate_argument(function, src, NasmRegister::Rbx, false)?;
-            let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rcx, false)?;
+            // x86 can't take two memory operands either
+            let both_memory = matches!(src_compiled, Operand::Memory { .. })
+                && matches!(dst_compiled, Operand::Memory { .. });
 
-            if is_argument_comparable(src) && is_argument_comparable(dst) {
-                writeln!(self.text, "    {operation} {dst_compiled}, {src_compiled}")?;
+            if is_argument_comparable(src) && is_argument_comparable(dst) && !both_memory {
+                self.instruction(operation, &[dst_compiled, src_compiled])?;
             } else {
-                writeln!(
-                    self.text,
-                    "    mov {rax}, {src_compiled}\n    {operation} {dst_compiled}, {rax}"
-                )?;
+                self.instruction("mov", &[rax.clone(), src_compiled])?;
+                self.instruction(operation, &[dst_compiled, rax])?;
             }
         }
 
         Ok(())
     }
 
-    fn generate_comparison(
+    // Floats can't be immediates, so constants go through `rax` on their way into an XMM register
+    fn generate_float_load<'src>(
+        &mut self,
+        function: &Function<'src>,
+        xmm: &'static str,
+        argument: &Argument<'src>,
+        scratch: NasmRegister,
+    ) -> fmt::Result {
+        let DataType::Float(float_type) = function.argument_data_type(argument) else {
+            unreachable!("Only floats can be loaded into XMM registers")
+        };
+
+        let argument_compiled = self.generate_argument(function, argument, scratch)?;
+
+        if let Argument::Constant { .. } = argument {
+            let rax = register(NasmRegister::Rax, function.argument_data_type(argument));
+
+            let mov = match float_type {
+                FloatType::F32 => "movd",
+                FloatType::F64 => "movq",
+            };
+
+            self.instruction("mov", &[rax.clone(), argument_compiled])?;
+            self.instruction(mov, &[Operand::Register(xmm), rax])
+        } else {
+            let suffix = float_suffix(float_type);
+
+            self.instruction(
+                &format!("mov{suffix}"),
+                &[Operand::Register(xmm), argument_compiled],
+            )
+        }
+    }
+
+    fn generate_float_store<'src>(
+        &mut self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+        xmm: &'static str,
+    ) -> fmt::Result {
+        let DataType::Float(float_type) = function.argument_data_type(argument) else {
+            unreachable!("Only floats can be stored from XMM registers")
+        };
+
+        let argument_compiled = self.generate_argument(function, argument, NasmRegister::Rcx)?;
+
+        self.instruction(
+            &format!("mov{}", float_suffix(float_type)),
+            &[argument_compiled, Operand::Register(xmm)],
+        )
+    }
+
+    fn generate_float_infix<'src>(
         &mut self,
-        function: &Function,
-        dst: &Argument,
-        lhs: &Argument,
-        rhs: &Argument,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        src: &Argument<'src>,
+        operation: &str,
+    ) -> fmt::Result {
+        let DataType::Float(float_type) = function.argument_data_type(dst) else {
+            unreachable!("Float operations should only be generated for floats")
+        };
+
+        let suffix = float_suffix(float_type);
+
+        self.generate_float_load(function, "xmm0", dst, NasmRegister::Rcx)?;
+        self.generate_float_load(function, "xmm1", src, NasmRegister::Rbx)?;
+
+        let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rcx)?;
+
+        self.instruction(
+            &format!("{operation}{suffix}"),
+            &[Operand::Register("xmm0"), Operand::Register("xmm1")],
+        )?;
+        self.instruction(
+            &format!("mov{suffix}"),
+            &[dst_compiled, Operand::Register("xmm0")],
+        )
+    }
+
+    fn generate_comparison<'src>(
+        &mut self,
+        function: &Function<'src>,
+        dst: &Argument<'src>,
+        lhs: &Argument<'src>,
+        rhs: &Argument<'src>,
         operation: &str,
     ) -> fmt::Result {
         assert_eq!(*function.argument_data_type(dst), DataType::Bool);
 
-        let rax = NasmRegister::Rax.generate(function.argument_data_type(lhs));
+        // Unsigned values and references compare with above/below instead of greater/less.
+        // `ucomiss`/`ucomisd` set the flags like an unsigned comparison would too
+        let operation = if function.argument_data_type(lhs).is_signed_integer() {
+            operation
+        } else {
+            match operation {
+                "setg" => "seta",
+                "setl" => "setb",
+                "setge" => "setae",
+                "setle" => "setbe",
+                other => other,
+            }
+        };
 
-        let lhs_compiled = self.generate_argument(function, lhs, NasmRegister::Rbx, false)?;
-        let rhs_compiled = self.generate_argument(function, rhs, NasmRegister::Rcx, false)?;
-        let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi, false)?;
+        if let DataType::Float(float_type) = function.argument_data_type(lhs) {
+            let suffix = float_suffix(float_type);
 
-        writeln!(self.text, "    mov {rax}, {lhs_compiled}\n    cmp {rax}, {rhs_compiled}\n    {operation} {dst_compiled}")?;
+            // A NaN sets every flag, which `<` and `<=` would take as true, so they are turned
+            // around into `>` and `>=`, and `==` and `!=` look at the parity flag it also sets
+            let (lhs, rhs, operation) = match operation {
+                "setb" => (rhs, lhs, "seta"),
+                "setbe" => (rhs, lhs, "setae"),
+                other => (lhs, rhs, other),
+            };
 
-        Ok(())
+            self.generate_float_load(function, "xmm0", lhs, NasmRegister::Rbx)?;
+            self.generate_float_load(function, "xmm1", rhs, NasmRegister::Rcx)?;
+
+            let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi)?;
+
+            self.instruction(
+                &format!("ucomi{suffix}"),
+                &[Operand::Register("xmm0"), Operand::Register("xmm1")],
+            )?;
+
+            let (parity, combine) = match operation {
+                "sete" => ("setnp", "and"),
+                "setne" => ("setp", "or"),
+                _ => return self.instruction(operation, &[dst_compiled]),
+            };
+
+            let al = register(NasmRegister::Rax, &DataType::Bool);
+            let cl = register(NasmRegister::Rcx, &DataType::Bool);
+
+            self.instruction(operation, std::slice::from_ref(&al))?;
+            self.instruction(parity, std::slice::from_ref(&cl))?;
+            self.instruction(combine, &[al.clone(), cl])?;
+
+            return self.instruction("mov", &[dst_compiled, al]);
+        }
+
+        let rax = register(NasmRegister::Rax, function.argument_data_type(lhs));
+
+        let lhs_compiled = self.generate_argument(function, lhs, NasmRegister::Rbx)?;
+        let rhs_compiled = self.generate_argument(function, rhs, NasmRegister::Rcx)?;
+        let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi)?;
+
+        self.instruction("mov", &[rax.clone(), lhs_compiled])?;
+        self.instruction("cmp", &[rax, rhs_compiled])?;
+        self.instruction(operation, &[dst_compiled])
     }
 
-    fn generate_opcode(&mut self, function: &Function, opcode: &OpCode) -> fmt::Result {
+    fn generate_opcode<'src>(
+        &mut self,
+        function: &Function<'src>,
+        opcode: &OpCode<'src>,
+    ) -> fmt::Result {
         match opcode {
             OpCode::Mov { dst, src } => {
                 if dst != src {
                     self.generate_infix(function, dst, src, "mov")?
                 }
             }
-            OpCode::Add { dst, src } => self.generate_infix(function, dst, src, "add")?,
-            OpCode::Sub { dst, src } => self.generate_infix(function, dst, src, "sub")?,
+            OpCode::Add { dst, src } if function.argument_data_type(dst).is_float() => {
+                self.generate_float_infix(function, dst, src, "add")?
+            }
+            OpCode::Sub { dst, src } if function.argument_data_type(dst).is_float() => {
+                self.generate_float_infix(function, dst, src, "sub")?
+            }
+            OpCode::Mul { dst, src } if function.argument_data_type(dst).is_float() => {
+                self.generate_float_infix(function, dst, src, "mul")?
+            }
+            OpCode::Div { dst, src } if function.argument_data_type(dst).is_float() => {
+                self.generate_float_infix(function, dst, src, "div")?
+            }
+            OpCode::Add { dst, src } => {
+                self.generate_infix(function, dst, src, "add")?;
+                self.generate_overflow_check(function, dst, "jc")?;
+            }
+            OpCode::Sub { dst, src } => {
+                self.generate_infix(function, dst, src, "sub")?;
+                self.generate_overflow_check(function, dst, "jc")?;
+            }
+            OpCode::And { dst, src } => self.generate_infix(function, dst, src, "and")?,
+            OpCode::Or { dst, src } => self.generate_infix(function, dst, src, "or")?,
+            OpCode::Xor { dst, src } => self.generate_infix(function, dst, src, "xor")?,
             OpCode::Mul { dst, src } => {
-                let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
-                let rbx = NasmRegister::Rbx.generate(function.argument_data_type(dst));
+                let rax = register(NasmRegister::Rax, function.argument_data_type(dst));
+                let rbx = register(NasmRegister::Rbx, function.argument_data_type(dst));
+
+                let src_compiled = self.generate_argument(function, src, NasmRegister::Rcx)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi)?;
 
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rcx, false)?;
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rdi, false)?;
+                let instruction = if function.argument_data_type(dst).is_signed_integer() {
+                    "imul"
+                } else {
+                    "mul"
+                };
 
-                writeln!(self.text, "    mov {rax}, {dst_compiled}\n    mov {rbx}, {src_compiled}\n    mul {rbx}\n    mov {dst_compiled}, {rax}")?;
+                self.instruction("mov", &[rax.clone(), dst_compiled.clone()])?;
+                self.instruction("mov", &[rbx.clone(), src_compiled])?;
+                self.instruction(instruction, &[rbx])?;
+                self.instruction("mov", &[dst_compiled, rax])?;
+                self.generate_overflow_check(function, dst, "jo")?;
             }
-            OpCode::Div { dst, src } => {
-                let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
-                let rbx = NasmRegister::Rbx.generate(function.argument_data_type(dst));
-                let rdx = NasmRegister::Rdx.generate(function.argument_data_type(dst));
+            OpCode::Div { dst, src } | OpCode::Mod { dst, src } => {
+                let data_type = function.argument_data_type(dst);
 
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rcx, false)?;
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rdi, false)?;
+                let rax = register(NasmRegister::Rax, data_type);
+                let rbx = register(NasmRegister::Rbx, data_type);
+                let rdx = register(NasmRegister::Rdx, data_type);
 
-                writeln!(self.text, "    xor {rdx}, {rdx}\n    mov {rax}, {dst_compiled}\n    mov {rbx}, {src_compiled}\n    div {rbx}\n    mov {dst_compiled}, {rax}")?;
-            }
-            OpCode::Mod { dst, src } => {
-                let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
-                let rbx = NasmRegister::Rbx.generate(function.argument_data_type(dst));
-                let rdx = NasmRegister::Rdx.generate(function.argument_data_type(dst));
+                let src_compiled = self.generate_argument(function, src, NasmRegister::Rcx)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi)?;
+
+                let result = match opcode {
+                    OpCode::Div { .. } => rax.clone(),
+                    // Byte division leaves the remainder in `ah` instead of `dl`
+                    _ if data_type.size() == 1 => Operand::Register("ah"),
+                    _ => rdx.clone(),
+                };
+
+                if data_type.is_signed_integer() {
+                    self.instruction("mov", &[rax.clone(), dst_compiled.clone()])?;
+                    self.instruction(sign_extension_generate(data_type), &[])?;
+                    self.instruction("mov", &[rbx.clone(), src_compiled])?;
+                    self.instruction("idiv", &[rbx])?;
+                } else {
+                    self.instruction("mov", &[rax.clone(), dst_compiled.clone()])?;
+
+                    // Bytes are divided out of all of `ax`, so it's `ah` that has to be cleared
+                    if data_type.size() == 1 {
+                        let ah = Operand::Register("ah");
 
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rcx, false)?;
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rdi, false)?;
+                        self.instruction("xor", &[ah.clone(), ah])?;
+                    } else {
+                        self.instruction("xor", &[rdx.clone(), rdx])?;
+                    }
 
-                writeln!(self.text, "    xor {rdx}, {rdx}\n    mov {rax}, {dst_compiled}\n    mov {rbx}, {src_compiled}\n    div {rbx}\n    mov {dst_compiled}, {rdx}")?;
+                    self.instruction("mov", &[rbx.clone(), src_compiled])?;
+                    self.instruction("div", &[rbx])?;
+                }
+
+                if result == Operand::Register("ah") {
+                    self.instruction("mov", &[rax.clone(), result])?;
+                    self.instruction("mov", &[dst_compiled, rax])?;
+                } else {
+                    self.instruction("mov", &[dst_compiled, result])?;
+                }
             }
             OpCode::Not { dst } => {
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rax, false)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rax)?;
 
-                writeln!(
-                    self.text,
-                    "    and {dst_compiled}, 0x1\n    xor {dst_compiled}, 0x1"
-                )?;
+                self.instruction("and", &[dst_compiled.clone(), Operand::Constant(1)])?;
+                self.instruction("xor", &[dst_compiled, Operand::Constant(1)])?;
+            }
+            OpCode::BitNot { dst } => {
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rax)?;
+
+                self.instruction("not", &[dst_compiled])?;
             }
             OpCode::Ref { dst, src } => {
-                let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
+                let rax = register(NasmRegister::Rax, function.argument_data_type(dst));
 
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rbx, true)?;
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rcx, false)?;
+                let src_address = self.generate_address(function, src, NasmRegister::Rbx)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rcx)?;
 
-                writeln!(
-                    self.text,
-                    "    lea {rax}, [{src_compiled}]\n    mov {dst_compiled}, {rax}"
-                )?;
+                self.instruction("lea", &[rax.clone(), Operand::memory(src_address)])?;
+                self.instruction("mov", &[dst_compiled, rax])?;
             }
             OpCode::Index { dst, src, index } => {
                 let rax = NasmRegister::Rax.generate(function.argument_data_type(src));
-                let rbx = NasmRegister::Rbx.generate(function.argument_data_type(dst));
+                let rbx = register(NasmRegister::Rbx, function.argument_data_type(dst));
 
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rdi, false)?;
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rsi, false)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi)?;
+                let src_compiled = self.generate_argument(function, src, NasmRegister::Rsi)?;
 
-                writeln!(self.text, "    mov {rax}, {src_compiled}")?;
+                self.instruction("mov", &[Operand::Register(rax), src_compiled])?;
 
                 if *index == Argument::VoidRegister {
-                    writeln!(self.text, "    mov {rbx}, [{rax}]")?;
+                    self.instruction(
+                        "mov",
+                        &[rbx.clone(), Operand::memory(Address::register(rax))],
+                    )?;
                 } else {
                     let rcx = NasmRegister::Rcx.generate(function.argument_data_type(index));
 
                     let index_compiled =
-                        self.generate_argument(function, index, NasmRegister::R8, false)?;
-
-                    writeln!(
-                        self.text,
-                        "    mov {rcx}, {index_compiled}\n    mov {rbx}, [{rax} + {rcx}]"
+                        self.generate_argument(function, index, NasmRegister::R8)?;
+
+                    self.instruction("mov", &[Operand::Register(rcx), index_compiled])?;
+                    self.instruction(
+                        "mov",
+                        &[
+                            rbx.clone(),
+                            Operand::memory(Address::register(rax).index(rcx)),
+                        ],
                     )?;
                 }
 
-                writeln!(self.text, "    mov {dst_compiled}, {rbx}")?;
+                self.instruction("mov", &[dst_compiled, rbx])?;
             }
             OpCode::SetIndex { dst, src, index } => {
                 let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
-                let rbx = NasmRegister::Rbx.generate(function.argument_data_type(src));
+                let rbx = register(NasmRegister::Rbx, function.argument_data_type(src));
 
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rdi, false)?;
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rsi, false)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rdi)?;
+                let src_compiled = self.generate_argument(function, src, NasmRegister::Rsi)?;
 
-                writeln!(self.text, "    mov {rax}, {dst_compiled}")?;
-                writeln!(self.text, "    mov {rbx}, {src_compiled}")?;
+                self.instruction("mov", &[Operand::Register(rax), dst_compiled])?;
+                self.instruction("mov", &[rbx.clone(), src_compiled])?;
 
                 if *index == Argument::VoidRegister {
-                    writeln!(self.text, "    mov [{rax}], {rbx}")?;
+                    self.instruction("mov", &[Operand::memory(Address::register(rax)), rbx])?;
                 } else {
                     let rcx = NasmRegister::Rcx.generate(function.argument_data_type(index));
 
                     let index_compiled =
-                        self.generate_argument(function, index, NasmRegister::R8, false)?;
+                        self.generate_argument(function, index, NasmRegister::R8)?;
 
-                    writeln!(
-                        self.text,
-                        "    mov {rcx}, {index_compiled}\n    mov [{rax} + {rcx}], {rbx}"
+                    self.instruction("mov", &[Operand::Register(rcx), index_compiled])?;
+                    self.instruction(
+                        "mov",
+                        &[Operand::memory(Address::register(rax).index(rcx)), rbx],
                     )?;
                 }
             }
             OpCode::SetField { dst, src, offset } => {
                 let rax = NasmRegister::Rax.generate(function.argument_data_type(dst));
-                let rbx = NasmRegister::Rbx.generate(function.argument_data_type(src));
-
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rcx, false)?;
-                let src_compiled =
-                    self.generate_argument(function, src, NasmRegister::Rdi, false)?;
-
-                writeln!(
-                    self.text,
-                    "    mov {rax}, {dst_compiled}\n    mov {rbx}, {src_compiled}\n    mov [{rax} - {offset}], {rbx}"
+                let rbx = register(NasmRegister::Rbx, function.argument_data_type(src));
+
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rcx)?;
+                let src_compiled = self.generate_argument(function, src, NasmRegister::Rdi)?;
+
+                self.instruction("mov", &[Operand::Register(rax), dst_compiled])?;
+                self.instruction("mov", &[rbx.clone(), src_compiled])?;
+                self.instruction(
+                    "mov",
+                    &[
+                        Operand::memory(Address::register(rax).offset(*offset as i64)),
+                        rbx,
+                    ],
                 )?;
             }
+            OpCode::Copy { dst, src, size } => {
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rbx)?;
+                let src_compiled = self.generate_argument(function, src, NasmRegister::Rdx)?;
+                let size_compiled = self.generate_argument(function, size, NasmRegister::R8)?;
+
+                self.instruction("mov", &[Operand::Register("rdi"), dst_compiled])?;
+                self.instruction("mov", &[Operand::Register("rsi"), src_compiled])?;
+                self.instruction("mov", &[Operand::Register("rcx"), size_compiled])?;
+                self.instruction("rep movsb", &[])?;
+            }
             OpCode::SetIfEqual { dst, lhs, rhs } => {
                 self.generate_comparison(function, dst, lhs, rhs, "sete")?
             }
@@ -462,74 +1290,193 @@ impl Nasm {
             OpCode::SetIfLessOrEqual { dst, lhs, rhs } => {
                 self.generate_comparison(function, dst, lhs, rhs, "setle")?
             }
+            // Flip the sign bit
+            OpCode::Negate { dst } if function.argument_data_type(dst).is_float() => {
+                let data_type = function.argument_data_type(dst);
+
+                let rax = register(NasmRegister::Rax, data_type);
+                let sign_bit = data_type.size() * 8 - 1;
+
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rbx)?;
+
+                self.instruction("mov", &[rax.clone(), dst_compiled.clone()])?;
+                self.instruction("btc", &[rax.clone(), Operand::Constant(sign_bit as u64)])?;
+                self.instruction("mov", &[dst_compiled, rax])?;
+            }
             OpCode::Negate { dst } => {
-                let dst_compiled =
-                    self.generate_argument(function, dst, NasmRegister::Rax, false)?;
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rax)?;
+
+                self.instruction("neg", &[dst_compiled])?;
 
-                writeln!(self.text, "neg {dst_compiled}")?;
+                // Only the smallest signed value doesn't fit once negated, unsigned masks wrap
+                if function.argument_data_type(dst).is_signed_integer() {
+                    self.generate_overflow_check(function, dst, "jo")?;
+                }
             }
-            OpCode::Label { label_id } => writeln!(self.text, ".L{label_id}:")?,
-            OpCode::Goto { label_id } => writeln!(self.text, "    jmp .L{label_id}")?,
+            OpCode::Label { label_id } => writeln!(
+                self.text,
+                "{}",
+                S::label(&S::local_label(&function.name, &format!("L{label_id}")))
+            )?,
+            OpCode::Goto { label_id } => self.instruction(
+                "jmp",
+                &[Self::local_label(function, &format!("L{label_id}"))],
+            )?,
+            OpCode::Return => self.instruction("jmp", &[Self::local_label(function, "return")])?,
             OpCode::GotoIfZero {
                 condition,
                 label_id,
-            } => {
-                let rax = NasmRegister::Rax.generate(function.argument_data_type(condition));
-
-                let condition_compiled =
-                    self.generate_argument(function, condition, NasmRegister::Rbx, false)?;
-
-                writeln!(self.text, "    mov {rax}, {condition_compiled}\n    test {rax}, {rax}\n    jz .L{label_id}")?;
             }
-            OpCode::GotoIfNotZero {
+            | OpCode::GotoIfNotZero {
                 condition,
                 label_id,
             } => {
-                let rax = NasmRegister::Rax.generate(function.argument_data_type(condition));
+                let rax = register(NasmRegister::Rax, function.argument_data_type(condition));
 
                 let condition_compiled =
-                    self.generate_argument(function, condition, NasmRegister::Rbx, false)?;
+                    self.generate_argument(function, condition, NasmRegister::Rbx)?;
 
-                writeln!(self.text, "    mov {rax}, {condition_compiled}\n    test {rax}, {rax}\n    jnz .L{label_id}\n")?;
+                let jump = match opcode {
+                    OpCode::GotoIfZero { .. } => "jz",
+                    _ => "jnz",
+                };
+
+                self.instruction("mov", &[rax.clone(), condition_compiled])?;
+                self.instruction("test", &[rax.clone(), rax])?;
+                self.instruction(
+                    jump,
+                    &[Self::local_label(function, &format!("L{label_id}"))],
+                )?;
             }
+            // The arguments that don't fit in registers are pushed last to first, then the rest are
+            // moved into theirs. Allocated registers are only read before `r9` is written.
+            // The stack is kept aligned to 16 bytes at the call, padding above what is pushed
             OpCode::Call {
                 dst,
                 lhs,
                 arguments,
             } => {
-                let DataType::Function { return_type, .. } = function.argument_data_type(lhs) else {
+                let DataType::Function {
+                    return_type,
+                    argument_types,
+                } = function.argument_data_type(lhs)
+                else {
                     unreachable!("This should be a function. If there was an error, it should have been caught in the typechecking phase.")
                 };
 
-                let lhs_compiled =
-                    self.generate_argument(function, lhs, NasmRegister::Rbx, false)?;
+                let (locations, stack_size) = argument_locations(argument_types);
 
-                if **return_type != DataType::Void {
+                // Stored last to first
+                let arguments = arguments.iter().rev().zip(locations).collect::<Vec<_>>();
+
+                let returned_in_register = is_returned_in_register(return_type);
+
+                let return_size = if **return_type != DataType::Void && !returned_in_register {
+                    return_type.size_aligned()
+                } else {
+                    0
+                };
+
+                let pushed_size = return_size + stack_size;
+                let padding = pushed_size.next_multiple_of(16) - pushed_size;
+
+                if padding != 0 {
+                    self.instruction(
+                        "sub",
+                        &[Operand::Register("rsp"), Operand::Constant(padding as u64)],
+                    )?;
+                }
+
+                if return_size != 0 {
                     self.generate_push_argument(function, dst)?;
                 }
 
-                for argument in arguments.iter().rev() {
-                    self.generate_push_argument(function, argument)?;
+                for (argument, location) in arguments.iter().rev() {
+                    if let ArgumentLocation::Stack(_) = location {
+                        self.generate_push_argument(function, argument)?;
+                    }
                 }
 
-                if let Argument::Symbol { .. } = lhs {
-                    writeln!(self.text, "    call {lhs_compiled}")?;
+                // Before `rax` holds the callee, since loading a constant goes through it
+                for (argument, location) in &arguments {
+                    if let ArgumentLocation::FloatRegister(xmm) = location {
+                        self.generate_float_load(function, xmm, argument, NasmRegister::Rbx)?;
+                    }
+                }
+
+                let lhs_compiled = self.generate_argument(function, lhs, NasmRegister::Rbx)?;
+
+                let lhs_compiled = if let Argument::Symbol { .. } = lhs {
+                    lhs_compiled
                 } else {
-                    let rax = NasmRegister::Rax.generate(function.argument_data_type(lhs));
+                    let rax = register(NasmRegister::Rax, function.argument_data_type(lhs));
 
-                    writeln!(self.text, "    mov {rax}, {lhs_compiled}\n    call {rax}")?;
+                    self.instruction("mov", &[rax.clone(), lhs_compiled])?;
+
+                    rax
                 };
 
-                let argument_stack_size = arguments
-                    .iter()
-                    .map(|argument| function.argument_data_type(argument).size_aligned())
-                    .sum::<usize>();
+                for (argument, location) in &arguments {
+                    if let ArgumentLocation::Register(nasm_register) = location {
+                        let argument_compiled =
+                            self.generate_argument(function, argument, NasmRegister::Rbx)?;
+
+                        self.instruction(
+                            "mov",
+                            &[
+                                register(*nasm_register, function.argument_data_type(argument)),
+                                argument_compiled,
+                            ],
+                        )?;
+                    }
+                }
+
+                self.instruction("call", &[lhs_compiled])?;
 
-                writeln!(self.text, "    add rsp, {argument_stack_size}")?;
+                self.instruction(
+                    "add",
+                    &[
+                        Operand::Register("rsp"),
+                        Operand::Constant(stack_size as u64),
+                    ],
+                )?;
 
-                if **return_type != DataType::Void {
+                if let (DataType::Float(_), false) =
+                    (&**return_type, *dst == Argument::VoidRegister)
+                {
+                    self.generate_float_store(function, dst, "xmm0")?;
+                } else if returned_in_register && *dst != Argument::VoidRegister {
+                    let rax = register(NasmRegister::Rax, return_type);
+
+                    let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rbx)?;
+
+                    self.instruction("mov", &[dst_compiled, rax])?;
+                } else if **return_type != DataType::Void {
                     self.generate_pop_argument(function, dst)?;
                 }
+
+                if padding != 0 {
+                    self.instruction(
+                        "add",
+                        &[Operand::Register("rsp"), Operand::Constant(padding as u64)],
+                    )?;
+                }
+            }
+            OpCode::SourceLine { line, text } => {
+                if self.options.comments {
+                    writeln!(self.text, "    {}", S::comment(&format!("{line}: {text}")))?;
+                }
+            }
+            OpCode::Breakpoint => self.instruction("int3", &[])?,
+            OpCode::Trap => self.instruction("ud2", &[])?,
+            // `rdtsc` splits the counter into edx:eax
+            OpCode::ReadCycleCounter { dst } => {
+                let dst_compiled = self.generate_argument(function, dst, NasmRegister::Rcx)?;
+
+                self.instruction("rdtsc", &[])?;
+                self.instruction("shl", &[Operand::Register("rdx"), Operand::Constant(32)])?;
+                self.instruction("or", &[Operand::Register("rax"), Operand::Register("rdx")])?;
+                self.instruction("mov", &[dst_compiled, Operand::Register("rax")])?;
             }
         };
 
@@ -537,44 +1484,441 @@ impl Nasm {
     }
 }
 
-impl<'src> CodeGenerator<'src> for Nasm {
+fn register(register: NasmRegister, data_type: &DataType) -> Operand {
+    Operand::Register(register.generate(data_type))
+}
+
+impl<'src, S: Syntax> CodeGenerator<'src> for Nasm<S> {
     fn generate(bytecode: &ByteCode<'src>) -> Result<String, fmt::Error> {
+        Self::generate_with(bytecode, NasmOptions::default())
+    }
+}
+
+impl<S: Syntax> Nasm<S> {
+    pub fn generate_with(bytecode: &ByteCode, options: NasmOptions) -> Result<String, fmt::Error> {
         let mut nasm = Self {
-            text: format!("[BITS 64]\nglobal _start\nsection .text\n{MALLOC_CODE}{FREE_CODE}{READ_CODE}{PRINT_CODE}{ENTRY_CODE}"),
+            text: String::from(S::prologue()),
+            options,
+            allocation: HashMap::new(),
+            syntax: PhantomData,
         };
 
-        for function in &bytecode.functions {
-            writeln!(
-                nasm.text,
-                "{}:\n    enter {}, 0",
-                function.name,
-                function.stack_size()
-            )?;
+        for extern_function in &bytecode.externs {
+            writeln!(nasm.text, "{}", S::external(extern_function.name))?;
+        }
 
-            for opcode in &function.opcodes {
-                nasm.generate_opcode(function, opcode)?;
-            }
+        for builtin in &bytecode.builtins {
+            nasm.text.push_str(S::builtin(builtin));
+        }
+
+        nasm.text.push_str(S::entry());
+
+        for function in bytecode.functions.iter().filter(|function| !function.cold) {
+            nasm.generate_function(function)?;
+        }
+
+        // Cold functions are kept together, so they don't take up space between the hot ones
+        if bytecode.functions.iter().any(|function| function.cold) {
+            writeln!(nasm.text, "{}", S::section(".text.unlikely", true))?;
 
-            writeln!(nasm.text, "    leave\n    ret")?;
+            for function in bytecode.functions.iter().filter(|function| function.cold) {
+                nasm.generate_function(function)?;
+            }
         }
 
-        writeln!(nasm.text, "section .data")?;
+        writeln!(nasm.text, "{}", S::section(".data", false))?;
 
         for (id, string) in bytecode.strings.iter().enumerate() {
             let bytes = string
-                .bytes()
+                .iter()
+                .copied()
                 .chain(std::iter::once(0))
-                .map(|byte| byte.to_string())
-                .collect::<Vec<String>>()
-                .join(", ");
+                .collect::<Vec<u8>>();
+
+            writeln!(
+                nasm.text,
+                "{}",
+                S::bytes(&ByteCode::string_symbol_name(id), &bytes)
+            )?;
+        }
+
+        // Every type is aligned to 8 bytes on the stack, so statics are too
+        let (initialized, zeroed): (Vec<_>, Vec<_>) = bytecode
+            .globals
+            .iter()
+            .enumerate()
+            .partition(|(_, global)| global.value.is_some());
+
+        if !initialized.is_empty() {
+            writeln!(nasm.text, "{}", S::align(8, false))?;
+        }
+
+        for (id, global) in initialized {
+            let mut bytes = global.bytes().expect("Only initialized globals are here");
+
+            bytes.resize(global.data_type.size_aligned(), 0);
 
             writeln!(
                 nasm.text,
-                "{}: db {bytes}",
-                ByteCode::string_symbol_name(id)
+                "{}",
+                S::bytes(&ByteCode::global_symbol_name(id), &bytes)
             )?;
         }
 
+        if !zeroed.is_empty() {
+            writeln!(nasm.text, "{}", S::section(".bss", false))?;
+            writeln!(nasm.text, "{}", S::align(8, true))?;
+
+            for (id, global) in zeroed {
+                writeln!(
+                    nasm.text,
+                    "{}",
+                    S::reserve(
+                        &ByteCode::global_symbol_name(id),
+                        global.data_type.size_aligned()
+                    )
+                )?;
+            }
+        }
+
         Ok(nasm.text)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compiler::{Compiler, CompilerOptions},
+        parser::Parser,
+        symbol_table::SymbolTable,
+        syntax::{Att, Intel},
+    };
+
+    fn generate(source: &str) -> String {
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+
+        Nasm::<Intel>::generate(&bytecode).unwrap()
+    }
+
+    #[test]
+    fn cold_functions_are_in_their_own_section() {
+        let text = generate(
+            "{
+                @cold fn fail() {
+                    @trap();
+                };
+
+                fn check(let x: U64) {
+                    if x == 0 {
+                        fail();
+                    };
+                };
+
+                check(1);
+                0;
+            }",
+        );
+
+        let section = text.find("section .text.unlikely").unwrap();
+
+        assert!(text.find("\ncheck:").unwrap() < section);
+        assert!(text.find("\nfail:").unwrap() > section);
+    }
+
+    fn division(int_type: &str) -> String {
+        generate(&format!(
+            "{{
+                let a: {int_type} = 7;
+                let b: {int_type} = 2;
+                let quotient: {int_type} = a / b;
+                0;
+            }}"
+        ))
+    }
+
+    #[test]
+    fn signed_division_sign_extends_into_rdx() {
+        let signed = division("S64");
+        let cqo = signed.find("    cqo\n").unwrap();
+
+        assert!(signed[cqo..].contains("    idiv "));
+
+        let unsigned = division("U64");
+
+        assert!(!unsigned.contains("cqo"));
+        assert!(!unsigned.contains("idiv"));
+    }
+
+    #[test]
+    fn floats_are_passed_and_returned_in_xmm_registers() {
+        let text = generate(
+            "{
+                extern fn sqrt(let x: F64): F64;
+
+                let root: F64 = sqrt(4.0);
+
+                0;
+            }",
+        );
+
+        let call = text.find("    call sqrt\n").unwrap();
+
+        assert!(text[..call].ends_with("    movq xmm0, rax\n"));
+        assert!(text[call..].contains("], xmm0\n"));
+    }
+
+    #[test]
+    fn the_stack_is_aligned_at_calls() {
+        let text = generate(
+            "{
+                @noinline fn seven(let a: U64, let b: U64, let c: U64, let d: U64, let e: U64,
+                    let f: U64, let g: U64): U64 {
+                    g;
+                };
+
+                seven(1, 2, 3, 4, 5, 6, 7);
+            }",
+        );
+
+        for line in text.lines().filter(|line| line.starts_with("    enter ")) {
+            let size = line["    enter ".len()..].split(',').next().unwrap();
+
+            assert_eq!(size.parse::<u64>().unwrap() % 16, 0);
+        }
+
+        // The seventh argument is pushed, so 8 more bytes keep the stack aligned
+        let call = text.find("    call seven\n").unwrap();
+        let padding = text[..call].rfind("    sub rsp, 8\n").unwrap();
+
+        assert!(text[padding..call].contains("    push "));
+        assert!(text[call..].contains("    add rsp, 8\n"));
+    }
+
+    #[test]
+    fn comments_show_the_source_lines() {
+        let source = "{
+            let x: U64 = 1;
+            x + 2;
+        }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let options = CompilerOptions {
+            source_lines: true,
+            ..CompilerOptions::default()
+        };
+        let bytecode = Compiler::compile_with(&ast, symbol_table, options);
+
+        let commented = Nasm::<Intel>::generate_with(
+            &bytecode,
+            NasmOptions {
+                comments: true,
+                ..NasmOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(commented.contains("; 2: let x: U64 = 1;"));
+        assert!(commented.contains("; 3: x + 2;"));
+
+        // Nothing but the comments is added
+        let uncommented: Vec<&str> = commented
+            .lines()
+            .filter(|line| !line.trim_start().starts_with(';'))
+            .collect();
+
+        assert_eq!(uncommented.join("\n") + "\n", generate(source));
+    }
+
+    #[test]
+    fn att_output_assembles() {
+        let source = "{
+            struct Point {
+                let x: U64;
+                let y: U64;
+            };
+
+            fn sum(let point: #Point): U64 {
+                (@point).x + (@point).y;
+            };
+
+            let point: Point;
+            point.x = 1;
+            point.y = 2;
+
+            let values: [U64; 3] = [1, 2, 3];
+            let total: U64 = sum(#point) + values[2] / 3;
+
+            println(\"{total}\");
+
+            total;
+        }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+        let assembly = Nasm::<Att>::generate(&bytecode).unwrap();
+
+        let directory = std::env::temp_dir();
+        let input = directory.join(format!("oil-att-{}.s", std::process::id()));
+        let output = input.with_extension("o");
+
+        std::fs::write(&input, assembly).unwrap();
+
+        let status = std::process::Command::new("as")
+            .arg("-o")
+            .arg(&output)
+            .arg(&input)
+            .status();
+
+        let _ = std::fs::remove_file(&input);
+        let _ = std::fs::remove_file(&output);
+
+        // Only where the GNU assembler is installed
+        match status {
+            Ok(status) => assert!(status.success()),
+            Err(error) => assert_eq!(error.kind(), std::io::ErrorKind::NotFound),
+        }
+    }
+
+    #[test]
+    fn branches_move_straight_into_the_result() {
+        let text = generate(
+            "{
+                let x: U64 = 7;
+                let y: U64 = x > 5 ? 10 : 20;
+                y;
+            }",
+        );
+
+        let main = &text[text.find("@main:").unwrap()..];
+        let moves: Vec<&str> = main
+            .lines()
+            .skip_while(|line| !line.starts_with("    jz "))
+            .take_while(|line| !line.starts_with(".L1:"))
+            .filter(|line| line.starts_with("    mov "))
+            .collect();
+
+        // The constant into `rax` and from there into the result, without another copy after it
+        assert_eq!(moves.len(), 4);
+        assert_eq!(moves[1], moves[3]);
+        assert!(main.contains("    jmp .L1\n.L0:"));
+    }
+
+    // Assembles, links and runs the program, unless the GNU tools aren't installed
+    fn run(assembly: &str, name: &str) -> Option<(Vec<u8>, Option<i32>)> {
+        let directory = std::env::temp_dir();
+        let source = directory.join(format!("oil-{name}-{}.s", std::process::id()));
+        let object = source.with_extension("o");
+        let binary = source.with_extension("");
+
+        std::fs::write(&source, assembly).unwrap();
+
+        let assembled = std::process::Command::new("as")
+            .arg("-o")
+            .arg(&object)
+            .arg(&source)
+            .status();
+
+        let linked = assembled.and_then(|status| {
+            assert!(status.success());
+
+            std::process::Command::new("ld")
+                .arg("-o")
+                .arg(&binary)
+                .arg(&object)
+                .status()
+        });
+
+        let output = linked.ok().map(|status| {
+            assert!(status.success());
+
+            std::process::Command::new(&binary).output().unwrap()
+        });
+
+        for path in [&source, &object, &binary] {
+            let _ = std::fs::remove_file(path);
+        }
+
+        output.map(|output| (output.stdout, output.status.code()))
+    }
+
+    #[test]
+    fn register_allocation_doesnt_change_what_programs_do() {
+        let sources = [
+            include_str!("../examples/register_allocation.oil"),
+            include_str!("../examples/interpolation.oil"),
+            include_str!("../examples/struct_return.oil"),
+            include_str!("../examples/float.oil"),
+        ];
+
+        for (n, source) in sources.into_iter().enumerate() {
+            let mut symbol_table = SymbolTable::new();
+            let ast = Parser::parse(source, &mut symbol_table).unwrap();
+            let bytecode = Compiler::compile(&ast, symbol_table);
+
+            let allocated = Nasm::<Att>::generate(&bytecode).unwrap();
+            let on_stack = Nasm::<Att>::generate_without_allocation(&bytecode).unwrap();
+
+            assert_ne!(allocated, on_stack);
+            assert_eq!(
+                run(&allocated, &format!("allocated-{n}")),
+                run(&on_stack, &format!("on-stack-{n}"))
+            );
+        }
+    }
+
+    #[test]
+    fn the_map_lists_every_symbol() {
+        let source = "{
+            static counter: U64 = 1;
+
+            @cold fn rarely(let n: U64): U64 {
+                n + counter;
+            };
+
+            let count: U64 = rarely(2);
+
+            println(\"count={count}\");
+
+            0;
+        }";
+
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+
+        let text = Nasm::<Intel>::generate(&bytecode).unwrap();
+        let map = bytecode.generate_map().unwrap();
+
+        let symbols: Vec<&str> = map
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().nth(2))
+            .collect();
+
+        // Labels start their line, local ones start with a `.` and `_start` isn't in the bytecode
+        for line in text.lines() {
+            let Some((label, _)) = line.split_once(':') else {
+                continue;
+            };
+
+            if !label.starts_with([' ', '.']) && label != "_start" {
+                assert!(symbols.contains(&label), "`{label}` isn't in the map");
+            }
+        }
+
+        for (id, global) in bytecode.globals.iter().enumerate() {
+            let entry = format!(
+                "{}  {}",
+                global.data_type.size_aligned(),
+                ByteCode::global_symbol_name(id)
+            );
+
+            assert!(map.lines().any(|line| line.ends_with(&entry)));
+        }
+    }
+}
@@ -1,16 +1,21 @@
 use crate::{
-    ast::{Ast, AstKind, VariableDeclaration},
-    symbol_table::{Symbol, SymbolTable},
-    types::{DataType, IntType},
+    ast::{Ast, AstKind, FunctionAttributes, Intrinsic, MatchArm, VariableDeclaration},
+    builtins::Extern,
+    symbol_table::{ScopeID, Symbol, SymbolTable},
+    types::{DataType, EnumType, FloatType, InferredType, IntType, StructType, TypeError},
     CompilerResult,
 };
-use std::{borrow::Cow, cmp::Eq, error::Error, fmt, iter::Peekable, str::CharIndices};
+use std::{
+    borrow::Cow, cell::RefCell, error::Error, fmt, iter::Peekable, rc::Rc, str::CharIndices,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind<'src> {
     Ident,
-    Number(u64),
-    Str(Cow<'src, str>),
+    Number(i128, Option<IntType>),
+    Float(f64),
+    Str(Cow<'src, [u8]>),
+    Char(u8),
     Add,
     Sub,
     Mul,
@@ -23,6 +28,13 @@ pub enum TokenKind<'src> {
     Less,
     GreaterOrEqual,
     LessOrEqual,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Question,
     LParen,
     RParen,
     LCurly,
@@ -33,45 +45,105 @@ pub enum TokenKind<'src> {
     AtSymbol,
     SemiColon,
     Colon,
+    DoubleColon,
     Comma,
     Dot,
     Assign,
+    FatArrow,
     Function,
+    Extern,
     Struct,
+    Enum,
+    Const,
+    Static,
     Let,
     If,
     Else,
     While,
+    For,
+    Match,
+    Return,
+    SizeOf,
     True,
     False,
 }
 
 impl<'src> TokenKind<'src> {
-    fn prefix_bp(&self) -> Option<usize> {
+    pub fn prefix_bp(&self) -> Option<usize> {
         match self {
-            Self::Not | Self::Hash | Self::AtSymbol | Self::Sub => Some(7),
+            Self::Not | Self::BitNot | Self::Hash | Self::AtSymbol | Self::Sub => Some(19),
             _ => None,
         }
     }
 
-    fn infix_bp(&self) -> Option<(usize, usize)> {
+    pub fn infix_bp(&self) -> Option<(usize, usize)> {
         match self {
+            // Right associative, so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`
+            Self::Question => Some((2, 1)),
+            Self::Or => Some((3, 4)),
+            Self::And => Some((5, 6)),
             Self::Equals
             | Self::NotEquals
             | Self::Greater
             | Self::Less
             | Self::GreaterOrEqual
-            | Self::LessOrEqual => Some((1, 2)),
-            Self::Add | Self::Sub => Some((3, 4)),
-            Self::Mul | Self::Div | Self::Mod => Some((5, 6)),
+            | Self::LessOrEqual => Some((7, 8)),
+            Self::BitOr => Some((9, 10)),
+            Self::BitXor => Some((11, 12)),
+            Self::BitAnd => Some((13, 14)),
+            Self::Add | Self::Sub => Some((15, 16)),
+            Self::Mul | Self::Div | Self::Mod => Some((17, 18)),
             _ => None,
         }
     }
 }
 
-#[derive(PartialEq, Eq, Clone)]
+// Where a token starts, both counted from 1 and the column in characters
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Position<'src> {
+    pub line: usize,
+    pub column: usize,
+    // The whole line, without the newline, so errors can show it
+    pub line_text: &'src str,
+}
+
+impl<'src> Position<'src> {
+    // The line with `^` under `width` characters starting at the position, like rustc does it
+    pub fn underline(&self, f: &mut fmt::Formatter, width: usize) -> fmt::Result {
+        let number = self.line.to_string();
+        let margin = " ".repeat(number.len());
+
+        // Tabs are kept, so the carets line up however wide they are shown
+        let indent = self
+            .line_text
+            .chars()
+            .take(self.column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let width = width
+            .min(self.line_text.chars().count() + 1 - self.column)
+            .max(1);
+
+        write!(
+            f,
+            "{margin} |\n{number} | {}\n{margin} | {indent}{}",
+            self.line_text,
+            "^".repeat(width)
+        )
+    }
+}
+
+impl<'src> fmt::Display for Position<'src> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(PartialEq, Clone)]
 pub struct Token<'src> {
     pub text: &'src str,
+    pub position: Position<'src>,
     pub kind: TokenKind<'src>,
 }
 
@@ -79,8 +151,13 @@ impl<'src> fmt::Debug for Token<'src> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
             TokenKind::Ident => write!(f, "{}", self.text),
-            TokenKind::Number(n) => write!(f, "{n}"),
-            TokenKind::Str(ref string) => write!(f, "{string:?}"),
+            // A negative literal keeps the text of the number after the `-`
+            TokenKind::Number(n, Some(_)) if n < 0 => write!(f, "-{}", self.text),
+            TokenKind::Number(_, Some(_)) => write!(f, "{}", self.text),
+            TokenKind::Number(n, None) => write!(f, "{n}"),
+            TokenKind::Float(n) => write!(f, "{n:?}"),
+            TokenKind::Str(ref string) => write!(f, "{:?}", String::from_utf8_lossy(string)),
+            TokenKind::Char(_) => write!(f, "{}", self.text),
             TokenKind::Add => write!(f, "+"),
             TokenKind::Sub => write!(f, "-"),
             TokenKind::Mul => write!(f, "*"),
@@ -93,6 +170,13 @@ impl<'src> fmt::Debug for Token<'src> {
             TokenKind::Less => write!(f, "<"),
             TokenKind::GreaterOrEqual => write!(f, ">="),
             TokenKind::LessOrEqual => write!(f, "<="),
+            TokenKind::And => write!(f, "&&"),
+            TokenKind::Or => write!(f, "||"),
+            TokenKind::BitAnd => write!(f, "&"),
+            TokenKind::BitOr => write!(f, "|"),
+            TokenKind::BitXor => write!(f, "^"),
+            TokenKind::BitNot => write!(f, "~"),
+            TokenKind::Question => write!(f, "?"),
             TokenKind::LParen => write!(f, "("),
             TokenKind::RParen => write!(f, ")"),
             TokenKind::LCurly => write!(f, "{{"),
@@ -103,15 +187,25 @@ impl<'src> fmt::Debug for Token<'src> {
             TokenKind::AtSymbol => write!(f, "@"),
             TokenKind::SemiColon => write!(f, ";"),
             TokenKind::Colon => write!(f, ":"),
+            TokenKind::DoubleColon => write!(f, "::"),
             TokenKind::Comma => write!(f, ","),
             TokenKind::Dot => write!(f, "."),
             TokenKind::Assign => write!(f, "="),
+            TokenKind::FatArrow => write!(f, "=>"),
             TokenKind::Function => write!(f, "fn"),
+            TokenKind::Extern => write!(f, "extern"),
             TokenKind::Struct => write!(f, "struct"),
+            TokenKind::Enum => write!(f, "enum"),
+            TokenKind::Const => write!(f, "const"),
+            TokenKind::Static => write!(f, "static"),
             TokenKind::Let => write!(f, "let"),
             TokenKind::If => write!(f, "if"),
             TokenKind::Else => write!(f, "else"),
             TokenKind::While => write!(f, "while"),
+            TokenKind::For => write!(f, "for"),
+            TokenKind::Match => write!(f, "match"),
+            TokenKind::Return => write!(f, "return"),
+            TokenKind::SizeOf => write!(f, "sizeof"),
             TokenKind::True => write!(f, "true"),
             TokenKind::False => write!(f, "false"),
         }
@@ -120,10 +214,19 @@ impl<'src> fmt::Debug for Token<'src> {
 
 #[derive(Clone)]
 pub enum ParseError<'src> {
-    InvalidChar(char),
+    InvalidChar(char, Position<'src>),
     UnclosedString,
+    UnclosedChar,
+    UnclosedBlockComment,
+    MalformedNumber(&'src str),
+    MalformedChar(&'src str),
+    MalformedEscape(&'src str),
+    MalformedInterpolation(&'src str),
+    MalformedMultilineString(&'src str),
     UnclosedParen(Token<'src>),
     UnexpectedToken(Option<Token<'src>>),
+    MissingDefaultArm(Token<'src>),
+    EmptyCommentMarker,
 }
 
 impl<'src> Error for ParseError<'src> {}
@@ -131,74 +234,553 @@ impl<'src> Error for ParseError<'src> {}
 impl<'src> fmt::Debug for ParseError<'src> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::InvalidChar(ch) => write!(f, "Unexpected character `{ch}`."),
+            Self::InvalidChar(ch, position) => {
+                write!(f, "Unexpected character `{ch}` at {position}.")
+            }
             Self::UnclosedString => write!(f, "Unclosed string."),
-            Self::UnclosedParen(_) => write!(f, "Unclosed parentheses."),
-            Self::UnexpectedToken(Some(token)) => write!(f, "Unexpected token `{token:?}`"),
+            Self::UnclosedChar => write!(f, "Unclosed character literal."),
+            Self::UnclosedBlockComment => write!(f, "Unclosed block comment."),
+            Self::MalformedNumber(text) => write!(f, "Malformed number `{text}`."),
+            Self::MalformedChar(text) => write!(
+                f,
+                "Malformed character literal `{text}`, it must contain exactly one byte."
+            ),
+            Self::MalformedEscape(text) => write!(
+                f,
+                "Malformed escape sequence `{text}`, `\\x` must be followed by two hex digits."
+            ),
+            Self::MalformedInterpolation(text) => write!(
+                f,
+                "Malformed interpolation `{text}`, braces must hold a variable name, use `{{{{` and `}}}}` for the braces themselves."
+            ),
+            Self::MalformedMultilineString(text) => write!(
+                f,
+                "Malformed multi-line string at `{text}`, its quotes must be on lines of their own, and its lines indented at least as much as the closing ones."
+            ),
+            Self::UnclosedParen(token) => {
+                write!(f, "Unclosed parentheses at {}.", token.position)
+            }
+            Self::UnexpectedToken(Some(token)) => {
+                write!(f, "Unexpected token `{token:?}` at {}.", token.position)
+            }
             Self::UnexpectedToken(None) => write!(f, "Unexpected EOF."),
+            Self::MissingDefaultArm(token) => {
+                write!(f, "Match at {} has no `_` arm.", token.position)
+            }
+            Self::EmptyCommentMarker => write!(f, "Comments can't start or end with nothing."),
         }
     }
 }
 
 impl<'src> fmt::Display for ParseError<'src> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{:?}", self)?;
+
+        match self {
+            Self::InvalidChar(_, position) => {
+                writeln!(f)?;
+                position.underline(f, 1)
+            }
+            Self::UnclosedParen(token)
+            | Self::UnexpectedToken(Some(token))
+            | Self::MissingDefaultArm(token) => {
+                writeln!(f)?;
+                token.position.underline(f, token.text.chars().count())
+            }
+            _ => Ok(()),
+        }
     }
 }
 
-fn parse_string(string: &'_ str) -> Cow<'_, str> {
-    let mut value = Cow::Borrowed(string);
+// Strings are kept as bytes, since `\x` escapes can produce ones that aren't valid UTF-8
+fn parse_string(string: &'_ str) -> Result<Cow<'_, [u8]>, ParseError<'_>> {
+    let mut value = Cow::Borrowed(string.as_bytes());
 
     let mut iter = string.char_indices();
 
     while let Some((pos, ch)) = iter.next() {
         if ch != '\\' {
-            if let Cow::Owned(ref mut string) = value {
-                string.push(ch);
+            if let Cow::Owned(ref mut bytes) = value {
+                bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
             }
             continue;
         }
 
-        let mut string = value.into_owned();
-        string.truncate(pos);
+        let mut bytes = value.into_owned();
+        bytes.truncate(pos);
 
         let Some((_, ch)) = iter.next() else {
             unreachable!("String should've been checked beforehand.");
         };
 
-        string.push(match ch {
-            'r' => '\r',
-            'n' => '\n',
-            't' => '\t',
-            '0' => '\0',
-            other => other,
+        match ch {
+            'r' => bytes.push(b'\r'),
+            'n' => bytes.push(b'\n'),
+            't' => bytes.push(b'\t'),
+            '0' => bytes.push(b'\0'),
+            'x' => {
+                let digits = (iter.next(), iter.next());
+                let end = iter.clone().next().map_or(string.len(), |(end, _)| end);
+
+                let (Some((_, high)), Some((_, low))) = digits else {
+                    return Err(ParseError::MalformedEscape(&string[pos..end]));
+                };
+
+                let (Some(high), Some(low)) = (high.to_digit(16), low.to_digit(16)) else {
+                    return Err(ParseError::MalformedEscape(&string[pos..end]));
+                };
+
+                bytes.push((high * 16 + low) as u8);
+            }
+            other => bytes.extend_from_slice(other.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+
+        value = Cow::Owned(bytes);
+    }
+
+    Ok(value)
+}
+
+// The inside of a string literal, as the pieces of source it is made of. A triple quoted one starts
+// on the line after its opening quotes and ends on the line before its closing ones, and every
+// line loses the indentation of the closing quotes. Only the newline between its last line and
+// the closing quotes is dropped, unless it is escaped in a string that isn't raw
+fn string_segments(text: &str, raw: bool) -> Result<Vec<&str>, ParseError<'_>> {
+    let Some(inside) = text
+        .strip_prefix(r#"""""#)
+        .and_then(|text| text.strip_suffix(r#"""""#))
+    else {
+        return Ok(vec![&text[1..text.len() - 1]]);
+    };
+
+    let is_indentation = |text: &str| text.chars().all(|ch| ch == ' ' || ch == '\t');
+
+    let (Some(first_end), Some(last_start)) = (inside.find('\n'), inside.rfind('\n')) else {
+        return Err(ParseError::MalformedMultilineString(text));
+    };
+
+    let opening = inside[..first_end].trim_end_matches('\r');
+    let indentation = &inside[last_start + 1..];
+
+    if !is_indentation(opening) {
+        return Err(ParseError::MalformedMultilineString(opening));
+    }
+
+    if !is_indentation(indentation) {
+        return Err(ParseError::MalformedMultilineString(indentation.trim()));
+    }
+
+    let mut body = &inside[(first_end + 1).min(last_start)..last_start];
+
+    let backslashes = body.len() - body.trim_end_matches('\\').len();
+
+    if !raw && backslashes % 2 == 1 {
+        body = &inside[first_end + 1..=last_start];
+    }
+
+    body.split_inclusive('\n')
+        .map(|line| match line.strip_prefix(indentation) {
+            Some(line) => Ok(line),
+            None if is_indentation(line.trim_end_matches(['\r', '\n'])) => {
+                Ok(line.trim_start_matches([' ', '\t']))
+            }
+            None => Err(ParseError::MalformedMultilineString(line.trim_end())),
+        })
+        .collect()
+}
+
+// A piece of a string literal, either bytes to keep as they are or a `{name}` to format
+enum StringPart<'src> {
+    Text(Vec<u8>),
+    Value(&'src str),
+}
+
+// Raw strings, prefixed with `r`, are kept as they are, without escapes or interpolation
+fn split_string_literal(text: &str) -> Result<Vec<StringPart<'_>>, ParseError<'_>> {
+    if let Some(text) = text.strip_prefix('r') {
+        let bytes = string_segments(text, true)?.concat().into_bytes();
+
+        return Ok(if bytes.is_empty() {
+            Vec::new()
+        } else {
+            vec![StringPart::Text(bytes)]
         });
+    }
+
+    let mut parts = Vec::new();
+
+    for segment in string_segments(text, false)? {
+        parts.extend(split_interpolation(segment)?);
+    }
+
+    Ok(parts)
+}
+
+// Splits the inside of a string literal around its `{name}`s, `{{` and `}}` stand for the braces themselves
+fn split_interpolation(string: &'_ str) -> Result<Vec<StringPart<'_>>, ParseError<'_>> {
+    let mut parts = Vec::new();
+    let mut text = Vec::new();
+    let mut start = 0;
+
+    let mut iter = string.char_indices().peekable();
+
+    while let Some((pos, ch)) = iter.next() {
+        match ch {
+            // Whatever is escaped is never a brace of its own
+            '\\' => {
+                iter.next();
+            }
+            '{' | '}' if iter.next_if(|&(_, next)| next == ch).is_some() => {
+                text.extend_from_slice(&parse_string(&string[start..=pos])?);
+                start = pos + 2;
+            }
+            '{' => {
+                let Some(end) = string[pos..].find('}').map(|end| pos + end) else {
+                    return Err(ParseError::MalformedInterpolation(&string[pos..]));
+                };
+
+                let name = &string[pos + 1..end];
+
+                if !name.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+                    || !name
+                        .chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+                {
+                    return Err(ParseError::MalformedInterpolation(&string[pos..=end]));
+                }
+
+                text.extend_from_slice(&parse_string(&string[start..pos])?);
 
-        value = Cow::Owned(string);
+                if !text.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut text)));
+                }
+
+                parts.push(StringPart::Value(name));
+
+                while iter.next_if(|&(next, _)| next <= end).is_some() {}
+
+                start = end + 1;
+            }
+            '}' => return Err(ParseError::MalformedInterpolation(&string[pos..=pos])),
+            _ => {}
+        }
+    }
+
+    text.extend_from_slice(&parse_string(&string[start..])?);
+
+    if !text.is_empty() {
+        parts.push(StringPart::Text(text));
     }
 
-    value
+    Ok(parts)
+}
+
+// Lets an embedder change which sequences start comments, e.g. adding `#` for shell-style comments.
+// Any sequence added here can no longer start a token, so `"#"` makes references unusable.
+#[derive(Clone, Copy)]
+pub struct ParserConfig<'src> {
+    pub line_comments: &'src [&'src str],
+    pub block_comments: &'src [(&'src str, &'src str)],
+    // Where the source came from, for `@source_location()`
+    pub file: &'src str,
+}
+
+impl<'src> Default for ParserConfig<'src> {
+    fn default() -> Self {
+        Self {
+            line_comments: &["//"],
+            block_comments: &[("/*", "*/")],
+            file: "<input>",
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Parser<'src> {
     string: &'src str,
     chars: Peekable<CharIndices<'src>>,
+    // The line `chars` is at and the byte offset it starts at
+    line: usize,
+    line_start: usize,
+    config: ParserConfig<'src>,
+    // Function declarations of generic instances, waiting to be put in the enclosing block
+    instances: Vec<Ast<'src>>,
+    // How many instances are being parsed inside of each other
+    instance_depth: usize,
+    // Of every function being parsed, innermost last
+    return_types: Vec<DataType<'src>>,
+    // Of the statements that were skipped, shared with every copy of the parser
+    errors: Rc<RefCell<Vec<Box<dyn Error + 'src>>>>,
+}
+
+pub const MAX_INSTANCE_DEPTH: usize = 16;
+
+// Generic functions are parsed again for every distinct list of type arguments they are called with,
+// so the parser is kept at the start of the argument list
+pub struct GenericFunction<'src> {
+    parser: Parser<'src>,
+    scope_id: ScopeID,
+    attributes: FunctionAttributes,
+    // Every type parameter stands for an undefined struct until it is instantiated
+    type_parameters: Vec<(&'src str, DataType<'src>)>,
+    argument_types: Vec<DataType<'src>>,
+    pub instances: Vec<(Vec<DataType<'src>>, DataType<'src>)>,
+}
+
+impl<'src> GenericFunction<'src> {
+    pub fn instance(&self, type_arguments: &[DataType<'src>]) -> Option<usize> {
+        self.instances
+            .iter()
+            .position(|(instance_type_arguments, _)| instance_type_arguments == type_arguments)
+    }
+
+    pub fn instance_name(name: &str, instance_id: usize) -> String {
+        format!("{name}.{instance_id}")
+    }
+
+    pub fn attributes(&self) -> FunctionAttributes {
+        self.attributes
+    }
+
+    pub fn type_parameter_names(&self) -> Vec<&'src str> {
+        self.type_parameters.iter().map(|(name, _)| *name).collect()
+    }
+
+    // From the argument list to the end of the body, as it was written, since the body is only
+    // parsed for each instance
+    pub fn source(&self) -> CompilerResult<'src, &'src str> {
+        let mut parser = self.parser.clone();
+
+        let start = parser.offset();
+
+        while !parser.peeking_token(TokenKind::LCurly)? {
+            parser
+                .next_token()?
+                .ok_or(ParseError::UnexpectedToken(None))?;
+        }
+
+        parser.skip_block()?;
+
+        Ok(parser.string[start..parser.offset()].trim_start())
+    }
+
+    fn type_parameter(&self, data_type: &DataType<'src>) -> Option<usize> {
+        self.type_parameters
+            .iter()
+            .position(|(_, placeholder)| placeholder == data_type)
+    }
+
+    fn uses(data_type: &DataType<'src>, placeholder: &DataType<'src>) -> bool {
+        match data_type {
+            DataType::Ref(deref) | DataType::Array { element: deref, .. } => {
+                Self::uses(deref, placeholder)
+            }
+            DataType::Function {
+                return_type,
+                argument_types,
+            } => std::iter::once(return_type.as_ref())
+                .chain(argument_types)
+                .any(|data_type| Self::uses(data_type, placeholder)),
+            _ => data_type == placeholder,
+        }
+    }
+
+    fn bind(
+        &self,
+        argument_type: &DataType<'src>,
+        data_type: &DataType<'src>,
+        type_arguments: &mut [Option<DataType<'src>>],
+    ) -> CompilerResult<'src, ()> {
+        match (argument_type, data_type) {
+            (_, DataType::Inferred(_)) => {}
+            (DataType::Ref(argument_deref), DataType::Ref(deref)) => {
+                self.bind(argument_deref, deref, type_arguments)?;
+            }
+            (
+                DataType::Array {
+                    element: argument_element,
+                    length: argument_length,
+                },
+                DataType::Array { element, length },
+            ) if argument_length == length => {
+                self.bind(argument_element, element, type_arguments)?;
+            }
+            (
+                DataType::Function {
+                    return_type: argument_return_type,
+                    argument_types: argument_argument_types,
+                },
+                DataType::Function {
+                    return_type,
+                    argument_types,
+                },
+            ) if argument_argument_types.len() == argument_types.len() => {
+                self.bind(argument_return_type, return_type, type_arguments)?;
+
+                for (argument_type, data_type) in argument_argument_types.iter().zip(argument_types)
+                {
+                    self.bind(argument_type, data_type, type_arguments)?;
+                }
+            }
+            _ => {
+                let Some(n) = self.type_parameter(argument_type) else {
+                    return Ok(());
+                };
+
+                match type_arguments[n] {
+                    Some(ref bound) if bound != data_type => {
+                        return Err(TypeError::TypeMismatch {
+                            first: bound.clone(),
+                            second: data_type.clone(),
+                        }
+                        .into());
+                    }
+                    Some(_) => {}
+                    None => type_arguments[n] = Some(data_type.clone()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn infer_type_arguments(
+        &self,
+        arguments: &[Ast<'src>],
+    ) -> CompilerResult<'src, Vec<DataType<'src>>> {
+        if arguments.len() != self.argument_types.len() {
+            return Err(TypeError::WrongNumberOfArguments.into());
+        }
+
+        let mut type_arguments = vec![None; self.type_parameters.len()];
+
+        for (argument_type, argument) in self.argument_types.iter().zip(arguments) {
+            self.bind(argument_type, &argument.data_type, &mut type_arguments)?;
+        }
+
+        // Literals only decide a type parameter when no other argument does
+        for (argument_type, argument) in self.argument_types.iter().zip(arguments) {
+            let Some(n) = self.type_parameter(argument_type) else {
+                continue;
+            };
+
+            if type_arguments[n].is_none() {
+                type_arguments[n] = match argument.data_type {
+                    DataType::Inferred(_) => DataType::default_of(argument),
+                    _ => None,
+                };
+            }
+        }
+
+        type_arguments
+            .into_iter()
+            .collect::<Option<Vec<DataType>>>()
+            .ok_or_else(|| TypeError::CannotInfer.into())
+    }
 }
 
 impl<'src> Parser<'src> {
+    // Every error found, in the order they appear in the source
     pub fn parse(
         string: &'src str,
         symbol_table: &mut SymbolTable<'src>,
-    ) -> CompilerResult<'src, Ast<'src>> {
-        let mut parser = Self {
+    ) -> Result<Ast<'src>, Vec<Box<dyn Error + 'src>>> {
+        Self::parse_with_config(string, symbol_table, ParserConfig::default())
+    }
+
+    pub fn parse_with_config(
+        string: &'src str,
+        symbol_table: &mut SymbolTable<'src>,
+        config: ParserConfig<'src>,
+    ) -> Result<Ast<'src>, Vec<Box<dyn Error + 'src>>> {
+        let mut parser = Self::new(string, config).map_err(|error| vec![error])?;
+
+        let result = parser.parse_program(symbol_table);
+
+        let mut errors = parser.errors.take();
+
+        match result {
+            Ok(ast) if errors.is_empty() => Ok(ast),
+            Ok(_) => Err(errors),
+            Err(error) => {
+                errors.push(error);
+                Err(errors)
+            }
+        }
+    }
+
+    // Every token up to the end, for tools that only need to know what the source is made of.
+    // Each keeps the text it was read from, so the source can be put back together
+    pub fn tokenize(string: &'src str) -> CompilerResult<'src, Vec<Token<'src>>> {
+        Self::tokenize_with_config(string, ParserConfig::default())
+    }
+
+    pub fn tokenize_with_config(
+        string: &'src str,
+        config: ParserConfig<'src>,
+    ) -> CompilerResult<'src, Vec<Token<'src>>> {
+        let mut parser = Self::new(string, config)?;
+        let mut tokens = Vec::new();
+
+        while let Some(token) = parser.next_token()? {
+            tokens.push(token);
+        }
+
+        Ok(tokens)
+    }
+
+    // Anything between the tokens that isn't whitespace can only have been a comment
+    pub fn has_comments(
+        string: &'src str,
+        config: ParserConfig<'src>,
+    ) -> CompilerResult<'src, bool> {
+        let mut end = 0;
+
+        for token in Self::tokenize_with_config(string, config)? {
+            let start = token.text.as_ptr() as usize - string.as_ptr() as usize;
+
+            if !string[end..start].trim().is_empty() {
+                return Ok(true);
+            }
+
+            end = start + token.text.len();
+        }
+
+        Ok(!string[end..].trim().is_empty())
+    }
+
+    // An empty marker would match everywhere without moving past anything
+    fn new(string: &'src str, config: ParserConfig<'src>) -> CompilerResult<'src, Self> {
+        let mut markers = config.line_comments.to_vec();
+
+        for &(start, end) in config.block_comments {
+            markers.extend([start, end]);
+        }
+
+        if markers.contains(&"") {
+            return Err(ParseError::EmptyCommentMarker.into());
+        }
+
+        Ok(Self {
             string,
             chars: string.char_indices().peekable(),
-        };
+            line: 1,
+            line_start: 0,
+            config,
+            instances: Vec::new(),
+            instance_depth: 0,
+            return_types: vec![DataType::Int(IntType::U64)], // The program itself
+            errors: Rc::default(),
+        })
+    }
 
-        let mut ast = parser.parse_expr_bp(symbol_table, 0)?;
+    fn parse_program(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let mut ast = self.parse_expr_bp(symbol_table, 0)?;
 
-        if let Some(token) = parser.next_token()? {
+        if let Some(token) = self.next_token()? {
             return Err(ParseError::UnexpectedToken(Some(token)).into());
         }
 
@@ -209,13 +791,63 @@ impl<'src> Parser<'src> {
 
     #[inline]
     fn peeking_char(&mut self, function: impl Fn(char) -> bool) -> bool {
-        self.chars.peek().map_or(false, |&(_, ch)| function(ch))
+        self.chars.peek().is_some_and(|&(_, ch)| function(ch))
+    }
+
+    #[inline]
+    fn peeking_second_char(&mut self, function: impl Fn(char) -> bool) -> bool {
+        self.chars
+            .clone()
+            .nth(1)
+            .is_some_and(|(_, ch)| function(ch))
+    }
+
+    #[inline]
+    fn peeking_str(&mut self, text: &str) -> bool {
+        self.chars
+            .peek()
+            .is_some_and(|&(pos, _)| self.string[pos..].starts_with(text))
+    }
+
+    #[inline]
+    fn peeking_any(&mut self, texts: &[&'src str]) -> Option<&'src str> {
+        texts.iter().copied().find(|text| self.peeking_str(text))
+    }
+
+    // Of the next character that hasn't been read yet
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.string.len(), |&(pos, _)| pos)
     }
 
     #[inline]
     fn advance(&mut self, pos: &mut usize) {
-        if let Some((new_pos, _)) = self.chars.next() {
+        if let Some((new_pos, ch)) = self.chars.next() {
             *pos = new_pos;
+
+            if ch == '\n' {
+                self.line += 1;
+                self.line_start = new_pos + 1;
+            }
+        }
+    }
+
+    fn position(&self, pos: usize) -> Position<'src> {
+        let line_text = self.string[self.line_start..]
+            .lines()
+            .next()
+            .unwrap_or_default();
+
+        Position {
+            line: self.line,
+            column: self.string[self.line_start..pos].chars().count() + 1,
+            line_text,
+        }
+    }
+
+    #[inline]
+    fn advance_str(&mut self, text: &str, pos: &mut usize) {
+        for _ in text.chars() {
+            self.advance(pos);
         }
     }
 
@@ -229,22 +861,83 @@ impl<'src> Parser<'src> {
                 continue;
             }
 
-            if ch.is_ascii_alphabetic() || ch == '_' {
+            // Skip line comments
+            if self.peeking_any(self.config.line_comments).is_some() {
+                while self.peeking_char(|ch| ch != '\n') {
+                    self.advance(&mut pos);
+                }
+
+                continue;
+            }
+
+            // Skip block comments, which may be nested
+            if let Some(&(start, end)) = self
+                .config
+                .block_comments
+                .iter()
+                .find(|(start, _)| self.peeking_str(start))
+            {
+                self.advance_str(start, &mut pos);
+
+                let mut depth = 1;
+
+                while depth > 0 {
+                    if self.chars.peek().is_none() {
+                        return Err(ParseError::UnclosedBlockComment.into());
+                    }
+
+                    if self.peeking_str(start) {
+                        self.advance_str(start, &mut pos);
+                        depth += 1;
+                    } else if self.peeking_str(end) {
+                        self.advance_str(end, &mut pos);
+                        depth -= 1;
+                    } else {
+                        self.advance(&mut pos);
+                    }
+                }
+
+                continue;
+            }
+
+            let position = self.position(start_pos);
+
+            let raw = ch == 'r' && self.peeking_second_char(|ch| ch == '"');
+
+            if (ch.is_ascii_alphabetic() || ch == '_') && !raw {
                 while self.peeking_char(|ch| ch.is_ascii_alphanumeric() || ch == '_') {
                     self.advance(&mut pos);
                 }
 
                 let text = &self.string[start_pos..=pos];
 
+                // Underscores only go between digits, so `_1` is a number written wrongly rather
+                // than a name
+                if text
+                    .trim_start_matches('_')
+                    .starts_with(|ch: char| ch.is_ascii_digit())
+                {
+                    return Err(ParseError::MalformedNumber(text).into());
+                }
+
                 return Ok(Some(Token {
                     text,
+                    position,
                     kind: match text {
                         "fn" => TokenKind::Function,
+                        "extern" => TokenKind::Extern,
                         "struct" => TokenKind::Struct,
+                        "enum" => TokenKind::Enum,
+                        "const" => TokenKind::Const,
+                        "static" => TokenKind::Static,
                         "let" => TokenKind::Let,
                         "if" => TokenKind::If,
                         "else" => TokenKind::Else,
                         "while" => TokenKind::While,
+                        "for" => TokenKind::For,
+                        "match" => TokenKind::Match,
+                        "return" => TokenKind::Return,
+                        "sizeof" => TokenKind::SizeOf,
                         "true" => TokenKind::True,
                         "false" => TokenKind::False,
                         _ => TokenKind::Ident,
@@ -253,23 +946,86 @@ impl<'src> Parser<'src> {
             }
 
             if ch.is_ascii_digit() {
-                while self.peeking_char(|ch| ch.is_ascii_digit()) {
+                while self.peeking_char(|ch| ch.is_ascii_digit() || ch == '_') {
+                    self.advance(&mut pos);
+                }
+
+                let is_float = self.peeking_char(|ch| ch == '.')
+                    && self.peeking_second_char(|ch| ch.is_ascii_digit());
+
+                if is_float {
                     self.advance(&mut pos);
+
+                    while self.peeking_char(|ch| ch.is_ascii_digit() || ch == '_') {
+                        self.advance(&mut pos);
+                    }
                 }
 
+                let digits_text = &self.string[start_pos..=pos];
+
+                // Integers can be followed by their type, like `255u8` or `1000s32`
+                let int_type = if !is_float && self.peeking_char(|ch| ch == 'u' || ch == 's') {
+                    let suffix_pos = pos + 1;
+
+                    while self.peeking_char(|ch| ch.is_ascii_alphanumeric()) {
+                        self.advance(&mut pos);
+                    }
+
+                    let Some(int_type) = IntType::from_suffix(&self.string[suffix_pos..=pos]) else {
+                        return Err(ParseError::MalformedNumber(&self.string[start_pos..=pos]).into());
+                    };
+
+                    Some(int_type)
+                } else {
+                    None
+                };
+
                 let text = &self.string[start_pos..=pos];
 
+                // Underscores may only separate digits, like `1_000_000`
+                if digits_text.ends_with('_')
+                    || digits_text.contains("__")
+                    || digits_text.contains("_.")
+                {
+                    return Err(ParseError::MalformedNumber(text).into());
+                }
+
+                let digits = digits_text.replace('_', "");
+
+                if is_float {
+                    return Ok(Some(Token {
+                        text,
+                        position,
+                        kind: TokenKind::Float(digits.parse()?),
+                    }));
+                }
+
+                // Whether it fits in its type is only checked once it is known if it's negated
+                let value: u64 = digits.parse()?;
+
                 return Ok(Some(Token {
                     text,
-                    kind: TokenKind::Number(text.parse()?),
+                    position,
+                    kind: TokenKind::Number(value.into(), int_type),
                 }));
             }
 
-            // Parse string
-            if ch == '"' {
-                self.advance(&mut pos);
+            // Parse string, which may span several lines. Triple quoted ones lose their indentation,
+            // and raw ones keep their backslashes
+            if ch == '"' || raw {
+                if raw {
+                    self.advance(&mut pos);
+                }
 
-                while !self.peeking_char(|ch| ch == '"') {
+                let delimiter = if self.peeking_str(r#"""""#) {
+                    r#"""""#
+                } else {
+                    "\""
+                };
+
+                self.advance_str(delimiter, &mut pos);
+
+                while !self.peeking_str(delimiter) {
                     if self.peeking_char(|ch| ch == '\\') {
                         self.advance(&mut pos);
                     }
@@ -281,86 +1037,151 @@ impl<'src> Parser<'src> {
                     }
                 }
 
-                self.advance(&mut pos);
+                self.advance_str(delimiter, &mut pos);
 
                 let text = &self.string[start_pos..=pos];
 
-                return Ok(Some(Token {
-                    text,
-                    kind: TokenKind::Str(parse_string(&text[1..text.len() - 1])),
-                }));
-            }
+                let segments = string_segments(text.strip_prefix('r').unwrap_or(text), raw)?;
 
-            self.advance(&mut pos);
-
-            let text = &self.string[start_pos..=pos];
+                let value = match *segments {
+                    _ if raw => Cow::Owned(segments.concat().into_bytes()),
+                    [segment] => parse_string(segment)?,
+                    _ => {
+                        let mut value = Vec::new();
 
-            return Ok(Some(Token {
-                text,
-                kind: match ch {
-                    '+' => TokenKind::Add,
-                    '-' => TokenKind::Sub,
-                    '*' => TokenKind::Mul,
-                    '/' => {
-                        if self.peeking_char(|ch| ch == '/') {
-                            self.advance(&mut pos);
-
-                            while self.peeking_char(|ch| ch != '\n') {
-                                self.advance(&mut pos);
-                            };
-
-                            continue;
-                        } else {
-                            TokenKind::Div
-                        }
-                    },
-                    '%' => TokenKind::Mod,
-                    '=' => {
-                        if self.peeking_char(|ch| ch == '=') {
-                            self.advance(&mut pos);
-                            TokenKind::Equals
-                        } else {
-                            TokenKind::Assign
+                        for segment in segments {
+                            value.extend_from_slice(&parse_string(segment)?);
                         }
+
+                        Cow::Owned(value)
                     }
-                    '!' => {
-                        if self.peeking_char(|ch| ch == '=') {
-                            self.advance(&mut pos);
-                            TokenKind::NotEquals
-                        } else {
-                            TokenKind::Not
-                        }
+                };
+
+                return Ok(Some(Token {
+                    text,
+                    position,
+                    kind: TokenKind::Str(value),
+                }));
+            }
+
+            // Parse character, which holds a single byte
+            if ch == '\'' {
+                self.advance(&mut pos);
+
+                while !self.peeking_char(|ch| ch == '\'') {
+                    if self.peeking_char(|ch| ch == '\\') {
+                        self.advance(&mut pos);
                     }
-                    '>' => {
-                        if self.peeking_char(|ch| ch == '=') {
-                            self.advance(&mut pos);
-                            TokenKind::GreaterOrEqual
-                        } else {
-                            TokenKind::Greater
-                        }
+
+                    self.advance(&mut pos);
+
+                    if self.chars.peek().is_none() {
+                        return Err(ParseError::UnclosedChar.into());
                     }
-                    '<' => {
-                        if self.peeking_char(|ch| ch == '=') {
-                            self.advance(&mut pos);
-                            TokenKind::LessOrEqual
-                        } else {
-                            TokenKind::Less
-                        }
+                }
+
+                self.advance(&mut pos);
+
+                let text = &self.string[start_pos..=pos];
+
+                let value = parse_string(&text[1..text.len() - 1])?;
+
+                let [byte] = *value else {
+                    return Err(ParseError::MalformedChar(text).into());
+                };
+
+                return Ok(Some(Token {
+                    text,
+                    position,
+                    kind: TokenKind::Char(byte),
+                }));
+            }
+
+            self.advance(&mut pos);
+
+            let kind = match ch {
+                '+' => TokenKind::Add,
+                '-' => TokenKind::Sub,
+                '*' => TokenKind::Mul,
+                '/' => TokenKind::Div,
+                '%' => TokenKind::Mod,
+                '=' => {
+                    if self.peeking_char(|ch| ch == '=') {
+                        self.advance(&mut pos);
+                        TokenKind::Equals
+                    } else if self.peeking_char(|ch| ch == '>') {
+                        self.advance(&mut pos);
+                        TokenKind::FatArrow
+                    } else {
+                        TokenKind::Assign
                     }
-                    '(' => TokenKind::LParen,
-                    ')' => TokenKind::RParen,
-                    '{' => TokenKind::LCurly,
-                    '}' => TokenKind::RCurly,
-                    '[' => TokenKind::LSquare,
-                    ']' => TokenKind::RSquare,
-                    '#' => TokenKind::Hash,
-                    '@' => TokenKind::AtSymbol,
-                    ';' => TokenKind::SemiColon,
-                    ':' => TokenKind::Colon,
-                    ',' => TokenKind::Comma,
-                    '.' => TokenKind::Dot,
-                    other => return Err(ParseError::InvalidChar(other).into()),
-                },
+                }
+                '!' => {
+                    if self.peeking_char(|ch| ch == '=') {
+                        self.advance(&mut pos);
+                        TokenKind::NotEquals
+                    } else {
+                        TokenKind::Not
+                    }
+                }
+                '>' => {
+                    if self.peeking_char(|ch| ch == '=') {
+                        self.advance(&mut pos);
+                        TokenKind::GreaterOrEqual
+                    } else {
+                        TokenKind::Greater
+                    }
+                }
+                '<' => {
+                    if self.peeking_char(|ch| ch == '=') {
+                        self.advance(&mut pos);
+                        TokenKind::LessOrEqual
+                    } else {
+                        TokenKind::Less
+                    }
+                }
+                '&' if self.peeking_char(|ch| ch == '&') => {
+                    self.advance(&mut pos);
+                    TokenKind::And
+                }
+                '|' if self.peeking_char(|ch| ch == '|') => {
+                    self.advance(&mut pos);
+                    TokenKind::Or
+                }
+                '&' => TokenKind::BitAnd,
+                '|' => TokenKind::BitOr,
+                '^' => TokenKind::BitXor,
+                '~' => TokenKind::BitNot,
+                '?' => TokenKind::Question,
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                '{' => TokenKind::LCurly,
+                '}' => TokenKind::RCurly,
+                '[' => TokenKind::LSquare,
+                ']' => TokenKind::RSquare,
+                '#' => TokenKind::Hash,
+                '@' => TokenKind::AtSymbol,
+                ';' => TokenKind::SemiColon,
+                ':' => {
+                    if self.peeking_char(|ch| ch == ':') {
+                        self.advance(&mut pos);
+                        TokenKind::DoubleColon
+                    } else {
+                        TokenKind::Colon
+                    }
+                }
+                ',' => TokenKind::Comma,
+                '.' => TokenKind::Dot,
+                other => return Err(ParseError::InvalidChar(other, position).into()),
+            };
+
+            // Taken once the whole token is read, operators can be two characters long
+            let text = &self.string[start_pos..=pos];
+
+            return Ok(Some(Token {
+                text,
+                position,
+                kind,
             }));
         }
 
@@ -368,6 +1189,14 @@ impl<'src> Parser<'src> {
     }
 
     #[inline(always)]
+    // Where the next token starts, which has to be there
+    fn next_position(&mut self) -> CompilerResult<'src, Position<'src>> {
+        match self.peek_token()? {
+            Some(token) => Ok(token.position),
+            None => Err(ParseError::UnexpectedToken(None).into()),
+        }
+    }
+
     fn peek_token(&mut self) -> CompilerResult<'src, Option<Token<'src>>> {
         let prev_self = self.clone(); // Fast, shallow copy
         let token = self.next_token();
@@ -390,7 +1219,7 @@ impl<'src> Parser<'src> {
 
     #[inline(always)]
     fn peeking_token(&mut self, kind: TokenKind<'src>) -> CompilerResult<'src, bool> {
-        Ok(self.peek_token()?.map_or(false, |token| token.kind == kind))
+        Ok(self.peek_token()?.is_some_and(|token| token.kind == kind))
     }
 
     fn parse_variable_declaration(
@@ -401,9 +1230,14 @@ impl<'src> Parser<'src> {
 
         let name = self.expect_token(TokenKind::Ident)?;
 
-        self.expect_token(TokenKind::Colon)?;
+        // Without a type there has to be a value to take it from
+        let data_type = if self.peeking_token(TokenKind::Assign)? {
+            None
+        } else {
+            self.expect_token(TokenKind::Colon)?;
 
-        let data_type = self.parse_data_type(symbol_table)?;
+            Some(self.parse_data_type(symbol_table)?)
+        };
 
         let value = if self.peeking_token(TokenKind::Assign)? {
             self.next_token()?;
@@ -412,21 +1246,146 @@ impl<'src> Parser<'src> {
             None
         };
 
+        let data_type = match (data_type, &value) {
+            (Some(data_type), _) => data_type,
+            (None, Some(value)) => DataType::default_of(value).ok_or(TypeError::CannotInfer)?,
+            (None, None) => unreachable!("A missing type is only allowed before a value"),
+        };
+
         Ok(VariableDeclaration {
             name: name.text,
             data_type,
             value,
+            alignment: None,
         })
     }
 
+    fn parse_let_statement(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        alignment: Option<usize>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let declaration = VariableDeclaration {
+            alignment,
+            ..self.parse_variable_declaration(symbol_table)?
+        };
+
+        if symbol_table.is_declared_in_scope(declaration.name) {
+            return Err(TypeError::AlreadyDeclared {
+                name: declaration.name,
+            }
+            .into());
+        }
+
+        symbol_table.add_symbol(
+            declaration.name,
+            Symbol::Variable(declaration.data_type.clone()),
+        );
+
+        Ast::new(symbol_table, AstKind::VariableDeclaration(declaration))
+    }
+
+    // `@align(n)` in front of a `let`
+    fn peeking_alignment(&mut self) -> CompilerResult<'src, bool> {
+        let mut lookahead = self.clone(); // Fast, shallow copy
+
+        if !lookahead.peeking_token(TokenKind::AtSymbol)? {
+            return Ok(false);
+        }
+
+        lookahead.next_token()?;
+
+        let Some(name) = lookahead.next_token()? else {
+            return Ok(false);
+        };
+
+        Ok(name.kind == TokenKind::Ident
+            && name.text == "align"
+            && lookahead.peeking_token(TokenKind::LParen)?)
+    }
+
+    fn parse_alignment(&mut self) -> CompilerResult<'src, usize> {
+        self.expect_token(TokenKind::AtSymbol)?;
+        self.expect_token(TokenKind::Ident)?;
+        self.expect_token(TokenKind::LParen)?;
+
+        let alignment = match self.next_token()? {
+            Some(Token {
+                kind: TokenKind::Number(alignment, _),
+                ..
+            }) => alignment as usize,
+            token => return Err(ParseError::UnexpectedToken(token).into()),
+        };
+
+        self.expect_token(TokenKind::RParen)?;
+
+        Ok(alignment)
+    }
+
+    // Any number of `@inline`, `@noinline` or `@cold` followed by `fn ...`
+    fn peeking_function_attribute(&mut self) -> CompilerResult<'src, bool> {
+        let mut lookahead = self.clone(); // Fast, shallow copy
+
+        if !lookahead.peeking_token(TokenKind::AtSymbol)? {
+            return Ok(false);
+        }
+
+        while lookahead.peeking_token(TokenKind::AtSymbol)? {
+            lookahead.next_token()?;
+
+            let Some(name) = lookahead.next_token()? else {
+                return Ok(false);
+            };
+
+            if name.kind != TokenKind::Ident || !FunctionAttributes::is_attribute(name.text) {
+                return Ok(false);
+            }
+        }
+
+        lookahead.peeking_token(TokenKind::Function)
+    }
+
     fn parse_function_declaration(
         &mut self,
         symbol_table: &mut SymbolTable<'src>,
     ) -> CompilerResult<'src, Ast<'src>> {
+        let mut attributes = FunctionAttributes::default();
+
+        while self.peeking_token(TokenKind::AtSymbol)? {
+            self.next_token()?;
+
+            let name = self.expect_token(TokenKind::Ident)?;
+
+            if !FunctionAttributes::is_attribute(name.text) {
+                return Err(ParseError::UnexpectedToken(Some(name)).into());
+            }
+
+            attributes.add(name.text);
+        }
+
         self.expect_token(TokenKind::Function)?;
 
         let name = self.expect_token(TokenKind::Ident)?;
 
+        if symbol_table.is_declared_in_scope(name.text) {
+            return Err(TypeError::AlreadyDeclared { name: name.text }.into());
+        }
+
+        if self.peeking_token(TokenKind::Less)? {
+            return self.parse_generic_declaration(symbol_table, name.text, attributes);
+        }
+
+        self.parse_function_definition(symbol_table, name.text, attributes, Vec::new())
+    }
+
+    // Everything after the name, an empty list of type arguments means it isn't an instance
+    fn parse_function_definition(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        name: &'src str,
+        attributes: FunctionAttributes,
+        type_arguments: Vec<DataType<'src>>,
+    ) -> CompilerResult<'src, Ast<'src>> {
         self.expect_token(TokenKind::LParen)?;
 
         let outer_scope_id = symbol_table.get_scope();
@@ -438,6 +1397,13 @@ impl<'src> Parser<'src> {
         while !self.peeking_token(TokenKind::RParen)? {
             let declaration = self.parse_variable_declaration(symbol_table)?;
 
+            if symbol_table.is_declared_in_scope(declaration.name) {
+                return Err(TypeError::AlreadyDeclared {
+                    name: declaration.name,
+                }
+                .into());
+            }
+
             symbol_table.add_symbol(
                 declaration.name,
                 Symbol::Variable(declaration.data_type.clone()),
@@ -469,24 +1435,40 @@ impl<'src> Parser<'src> {
 
         symbol_table.enter_scope(outer_scope_id);
 
-        symbol_table.add_symbol(
-            name.text,
-            Symbol::Variable(DataType::Function {
-                return_type: Box::new(return_type.clone()),
-                argument_types,
-            }),
-        );
+        let function_type = DataType::Function {
+            return_type: Box::new(return_type.clone()),
+            argument_types,
+        };
+
+        // Registered before the body, so that it can call itself
+        if type_arguments.is_empty() {
+            symbol_table.add_symbol(name, Symbol::Variable(function_type));
+        } else {
+            let Some(Symbol::Generic(generic)) = symbol_table.get_symbol_mut(name) else {
+                unreachable!();
+            };
+
+            generic
+                .instances
+                .push((type_arguments.clone(), function_type));
+        }
 
         symbol_table.enter_scope(scope_id);
 
+        self.return_types.push(return_type.clone());
+
         let body = self.parse_block(symbol_table)?;
 
+        self.return_types.pop();
+
         symbol_table.leave_scope();
 
         Ast::new(
             symbol_table,
             AstKind::FunctionDeclaration {
-                name: name.text,
+                name,
+                attributes,
+                type_arguments,
                 scope_id,
                 return_type,
                 arguments,
@@ -495,133 +1477,786 @@ impl<'src> Parser<'src> {
         )
     }
 
-    fn parse_structure_declaration(
+    // `extern fn name(...): R`, a function without a body that is linked in under its own name
+    fn parse_extern_declaration(
         &mut self,
         symbol_table: &mut SymbolTable<'src>,
     ) -> CompilerResult<'src, Ast<'src>> {
-        self.expect_token(TokenKind::Struct)?;
+        self.expect_token(TokenKind::Extern)?;
+        self.expect_token(TokenKind::Function)?;
 
         let name = self.expect_token(TokenKind::Ident)?;
 
-        let mut fields = Vec::new();
+        // A builtin is linked in under the same name, so it can't be shadowed by one
+        if symbol_table.is_declared_in_scope(name.text)
+            || symbol_table
+                .builtins
+                .iter()
+                .any(|builtin| builtin.name == name.text)
+        {
+            return Err(TypeError::AlreadyDeclared { name: name.text }.into());
+        }
 
-        self.expect_token(TokenKind::LCurly)?;
+        self.expect_token(TokenKind::LParen)?;
 
-        while !self.peeking_token(TokenKind::RCurly)? {
-            fields.push(self.parse_variable_declaration(symbol_table)?);
+        let mut argument_types = Vec::new();
 
-            if !self.peeking_token(TokenKind::SemiColon)? {
+        while !self.peeking_token(TokenKind::RParen)? {
+            argument_types.push(self.parse_variable_declaration(symbol_table)?.data_type);
+
+            if !self.peeking_token(TokenKind::Comma)? {
                 break;
             }
 
             self.next_token()?;
         }
 
-        self.expect_token(TokenKind::RCurly)?;
+        self.expect_token(TokenKind::RParen)?;
 
-        symbol_table.add_symbol(
-            name.text,
-            Symbol::Struct(DataType::Struct(
-                fields
-                    .iter()
-                    .map(|declaration| (declaration.name, declaration.data_type.clone()))
-                    .collect(),
-            )),
-        );
+        let return_type = if self.peeking_token(TokenKind::Colon)? {
+            self.next_token()?;
 
-        Ast::new(
-            symbol_table,
-            AstKind::StructDeclaration {
-                name: name.text,
-                fields,
-            },
-        )
+            self.parse_data_type(symbol_table)?
+        } else {
+            DataType::Void
+        };
+
+        let function_type = DataType::Function {
+            return_type: Box::new(return_type),
+            argument_types,
+        };
+
+        symbol_table.add_symbol(name.text, Symbol::Variable(function_type.clone()));
+        symbol_table.add_extern(Extern {
+            name: name.text,
+            data_type: function_type,
+        });
+
+        Ast::new(symbol_table, AstKind::ExternDeclaration { name: name.text })
     }
 
-    fn parse_statement(
+    // `fn name<T, ...>(...)`, the body is only checked once the type arguments are known
+    fn parse_generic_declaration(
         &mut self,
         symbol_table: &mut SymbolTable<'src>,
+        name: &'src str,
+        attributes: FunctionAttributes,
     ) -> CompilerResult<'src, Ast<'src>> {
-        match self
-            .peek_token()?
-            .ok_or(ParseError::UnexpectedToken(None))?
-            .kind
-        {
-            TokenKind::Let => {
-                let declaration = self.parse_variable_declaration(symbol_table)?;
+        self.expect_token(TokenKind::Less)?;
+
+        let mut type_parameters = Vec::new();
 
-                symbol_table.add_symbol(
-                    declaration.name,
-                    Symbol::Variable(declaration.data_type.clone()),
-                );
+        while !self.peeking_token(TokenKind::Greater)? {
+            let parameter = self.expect_token(TokenKind::Ident)?;
 
-                Ast::new(symbol_table, AstKind::VariableDeclaration(declaration))
+            type_parameters.push((
+                parameter.text,
+                DataType::Struct(StructType::new(parameter.text)),
+            ));
+
+            if !self.peeking_token(TokenKind::Comma)? {
+                break;
             }
-            TokenKind::Function => self.parse_function_declaration(symbol_table),
-            TokenKind::Struct => self.parse_structure_declaration(symbol_table),
-            _ => {
-                let lhs = self.parse_expr_bp(symbol_table, 0)?;
 
-                if !self.peeking_token(TokenKind::Assign)? {
-                    return Ok(lhs);
-                }
+            self.next_token()?;
+        }
 
-                self.next_token()?;
+        self.expect_token(TokenKind::Greater)?;
 
-                let rhs = self.parse_expr_bp(symbol_table, 0)?;
+        let parser = self.clone();
 
-                Ast::new(
-                    symbol_table,
-                    AstKind::Assign {
-                        lhs: Box::new(lhs),
-                        rhs: Box::new(rhs),
-                    },
-                )
-            }
+        let scope_id = symbol_table.get_scope();
+
+        symbol_table.add_scope();
+
+        for (parameter, placeholder) in &type_parameters {
+            symbol_table.add_symbol(parameter, Symbol::TypeParameter(placeholder.clone()));
         }
-    }
 
-    fn parse_block(
-        &mut self,
-        symbol_table: &mut SymbolTable<'src>,
-    ) -> CompilerResult<'src, Ast<'src>> {
-        let scope_id = symbol_table.add_scope();
+        self.expect_token(TokenKind::LParen)?;
 
-        let mut statements: Vec<Ast<'src>> = Vec::new();
+        let mut argument_types = Vec::new();
 
-        self.expect_token(TokenKind::LCurly)?;
+        while !self.peeking_token(TokenKind::RParen)? {
+            argument_types.push(self.parse_variable_declaration(symbol_table)?.data_type);
 
-        while !self.peeking_token(TokenKind::RCurly)? {
-            statements.push(self.parse_statement(symbol_table)?);
+            if !self.peeking_token(TokenKind::Comma)? {
+                break;
+            }
 
-            self.expect_token(TokenKind::SemiColon)?;
+            self.next_token()?;
         }
 
-        self.expect_token(TokenKind::RCurly)?;
+        self.expect_token(TokenKind::RParen)?;
 
-        symbol_table.leave_scope();
+        if self.peeking_token(TokenKind::Colon)? {
+            self.next_token()?;
 
-        Ast::new(
-            symbol_table,
-            AstKind::Block {
-                statements,
-                scope_id,
-            },
-        )
-    }
+            self.parse_data_type(symbol_table)?;
+        }
 
-    fn parse_data_type(
-        &mut self,
-        symbol_table: &mut SymbolTable<'src>,
-    ) -> CompilerResult<'src, DataType<'src>> {
-        let Some(token) = self.next_token()? else {
-            return Err(ParseError::UnexpectedToken(None).into());
-        };
+        symbol_table.enter_scope(scope_id);
+
+        self.skip_block()?;
+
+        // Type arguments are inferred from the arguments, so every parameter has to appear in one
+        for (parameter, placeholder) in &type_parameters {
+            if !argument_types
+                .iter()
+                .any(|argument_type| GenericFunction::uses(argument_type, placeholder))
+            {
+                return Err(TypeError::UnusedTypeParameter { name: parameter }.into());
+            }
+        }
+
+        symbol_table.add_symbol(
+            name,
+            Symbol::Generic(Box::new(GenericFunction {
+                parser,
+                scope_id,
+                attributes,
+                type_parameters,
+                argument_types,
+                instances: Vec::new(),
+            })),
+        );
+
+        Ast::new(symbol_table, AstKind::GenericFunctionDeclaration { name })
+    }
+
+    // `print`, `println` and `len` are called like functions, but they are intrinsics as long as
+    // nothing else has that name
+    fn peeking_named_intrinsic(
+        &mut self,
+        symbol_table: &SymbolTable<'src>,
+        name: &'src str,
+    ) -> CompilerResult<'src, bool> {
+        let is_intrinsic = match name {
+            "print" | "println" | "len" => symbol_table.get_symbol(name).is_none(),
+            _ => false,
+        };
+
+        if !is_intrinsic {
+            return Ok(false);
+        }
+
+        let mut lookahead = self.clone(); // Fast, shallow copy
+
+        lookahead.next_token()?;
+
+        lookahead.peeking_token(TokenKind::LParen)
+    }
+
+    // Prints every argument in turn, the `write` builtin takes a string and its length instead.
+    // A string literal that is an argument of its own has its pieces printed one after the other,
+    // so interpolating into it doesn't need to put the text together first
+    fn parse_print_call(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        name: Token<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let mut arguments = Vec::new();
+
+        self.expect_token(TokenKind::LParen)?;
+
+        while !self.peeking_token(TokenKind::RParen)? {
+            if self.peeking_lone_string_literal()? {
+                arguments.extend(self.parse_string_pieces(symbol_table)?);
+            } else {
+                arguments.push(self.parse_expr_bp(symbol_table, 0)?);
+            }
+
+            if !self.peeking_token(TokenKind::Comma)? {
+                break;
+            }
+
+            self.next_token()?;
+        }
+
+        self.expect_token(TokenKind::RParen)?;
+
+        let intrinsic = if name.text == "print" {
+            Intrinsic::Print
+        } else {
+            Intrinsic::PrintLine
+        };
+
+        Ast::new(
+            symbol_table,
+            AstKind::Intrinsic {
+                intrinsic,
+                arguments,
+            },
+        )
+    }
+
+    // String literals that make up a whole argument, and aren't the start of an expression
+    fn peeking_lone_string_literal(&mut self) -> CompilerResult<'src, bool> {
+        let mut lookahead = self.clone(); // Fast, shallow copy
+
+        let mut is_string = false;
+
+        while let Some(Token {
+            kind: TokenKind::Str(_),
+            ..
+        }) = lookahead.peek_token()?
+        {
+            lookahead.next_token()?;
+
+            is_string = true;
+        }
+
+        Ok(is_string
+            && (lookahead.peeking_token(TokenKind::Comma)?
+                || lookahead.peeking_token(TokenKind::RParen)?))
+    }
+
+    fn parse_generic_call(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        name: &'src str,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let arguments = self.parse_function_call_args(symbol_table)?;
+
+        let Some(Symbol::Generic(generic)) = symbol_table.get_symbol(name) else {
+            unreachable!();
+        };
+
+        let type_arguments = generic.infer_type_arguments(&arguments)?;
+
+        if generic.instance(&type_arguments).is_none() {
+            // A function that calls itself with a new type every time never runs out of instances
+            if self.instance_depth == MAX_INSTANCE_DEPTH {
+                return Err(TypeError::TooManyInstances { name }.into());
+            }
+
+            let mut parser = generic.parser.clone();
+
+            parser.instance_depth = self.instance_depth + 1;
+
+            let type_parameters = generic.type_parameters.clone();
+            let attributes = generic.attributes;
+
+            let call_scope_id = symbol_table.get_scope();
+
+            symbol_table.enter_scope(generic.scope_id);
+
+            symbol_table.add_scope();
+
+            for ((parameter, _), type_argument) in type_parameters.iter().zip(&type_arguments) {
+                symbol_table.add_symbol(parameter, Symbol::TypeParameter(type_argument.clone()));
+            }
+
+            let declaration = parser.parse_function_definition(
+                symbol_table,
+                name,
+                attributes,
+                type_arguments.clone(),
+            )?;
+
+            symbol_table.enter_scope(call_scope_id);
+
+            self.instances.push(declaration);
+        }
+
+        let lhs = Ast::new(
+            symbol_table,
+            AstKind::Instance {
+                name,
+                type_arguments,
+            },
+        )?;
+
+        Ast::new(
+            symbol_table,
+            AstKind::Call {
+                lhs: Box::new(lhs),
+                arguments,
+            },
+        )
+    }
+
+    // The variable, function, constant or static a statement starts declaring, if any
+    fn declared_name(&mut self) -> Option<&'src str> {
+        while self.peeking_token(TokenKind::AtSymbol).ok()? {
+            self.next_token().ok()?;
+            self.next_token().ok()?;
+
+            if self.peeking_token(TokenKind::LParen).ok()? {
+                while self.next_token().ok()??.kind != TokenKind::RParen {}
+            }
+        }
+
+        match self.next_token().ok()??.kind {
+            TokenKind::Let | TokenKind::Function | TokenKind::Const | TokenKind::Static => {}
+            _ => return None,
+        }
+
+        let name = self.next_token().ok()??;
+
+        (name.kind == TokenKind::Ident).then_some(name.text)
+    }
+
+    // Skips to after the next `;` outside of any brackets, or to the `}` closing the block,
+    // returns false at the end of the file
+    fn skip_statement(&mut self) -> bool {
+        let mut depth = 0;
+
+        loop {
+            let token = match self.peek_token() {
+                Ok(Some(token)) => token,
+                Ok(None) => return false,
+                // The lexer has moved past whatever it couldn't read
+                Err(_) => {
+                    self.next_token().ok();
+                    continue;
+                }
+            };
+
+            match token.kind {
+                TokenKind::RCurly if depth == 0 => return true,
+                TokenKind::SemiColon if depth == 0 => {
+                    self.next_token().ok();
+                    return true;
+                }
+                TokenKind::LCurly | TokenKind::LParen | TokenKind::LSquare => depth += 1,
+                TokenKind::RCurly | TokenKind::RParen | TokenKind::RSquare => depth -= 1,
+                _ => {}
+            }
+
+            self.next_token().ok();
+        }
+    }
+
+    fn skip_block(&mut self) -> CompilerResult<'src, ()> {
+        self.expect_token(TokenKind::LCurly)?;
+
+        let mut depth = 1;
+
+        while depth > 0 {
+            let token = self
+                .next_token()?
+                .ok_or(ParseError::UnexpectedToken(None))?;
+
+            match token.kind {
+                TokenKind::LCurly => depth += 1,
+                TokenKind::RCurly => depth -= 1,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_structure_declaration(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        self.expect_token(TokenKind::Struct)?;
+
+        let name = self.expect_token(TokenKind::Ident)?;
+
+        if symbol_table.is_declared_in_scope(name.text) {
+            return Err(TypeError::AlreadyDeclared { name: name.text }.into());
+        }
+
+        let struct_type = StructType::new(name.text);
+
+        // Declared before the fields, so that they can refer to it
+        symbol_table.add_symbol(
+            name.text,
+            Symbol::Struct(DataType::Struct(struct_type.clone())),
+        );
+
+        // A body that doesn't parse still defines the structure, without any fields, so that the
+        // rest of the block can be checked after recovering from it
+        let fields = match self.parse_structure_fields(symbol_table, name.text, &struct_type) {
+            Ok(fields) => fields,
+            Err(error) => {
+                struct_type.define(Vec::new());
+
+                return Err(error);
+            }
+        };
+
+        struct_type.define(
+            fields
+                .iter()
+                .map(|declaration| (declaration.name, declaration.data_type.clone()))
+                .collect(),
+        );
+
+        Ast::new(
+            symbol_table,
+            AstKind::StructDeclaration {
+                name: name.text,
+                fields,
+            },
+        )
+    }
+
+    fn parse_structure_fields(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        name: &'src str,
+        struct_type: &Rc<StructType<'src>>,
+    ) -> CompilerResult<'src, Vec<VariableDeclaration<'src>>> {
+        let mut fields = Vec::new();
+
+        self.expect_token(TokenKind::LCurly)?;
+
+        while !self.peeking_token(TokenKind::RCurly)? {
+            fields.push(self.parse_variable_declaration(symbol_table)?);
+
+            if !self.peeking_token(TokenKind::SemiColon)? {
+                break;
+            }
+
+            self.next_token()?;
+        }
+
+        self.expect_token(TokenKind::RCurly)?;
+
+        if fields
+            .iter()
+            .any(|declaration| {
+                *declaration.data_type.innermost_element() == DataType::Struct(struct_type.clone())
+            })
+        {
+            return Err(TypeError::InfiniteSize { name }.into());
+        }
+
+        Ok(fields)
+    }
+
+    // `enum Name { A, B, C }`, the variants are numbered from zero
+    fn parse_enum_declaration(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        self.expect_token(TokenKind::Enum)?;
+
+        let name = self.expect_token(TokenKind::Ident)?;
+
+        if symbol_table.is_declared_in_scope(name.text) {
+            return Err(TypeError::AlreadyDeclared { name: name.text }.into());
+        }
+
+        let mut variants = Vec::new();
+
+        self.expect_token(TokenKind::LCurly)?;
+
+        while !self.peeking_token(TokenKind::RCurly)? {
+            let variant = self.expect_token(TokenKind::Ident)?;
+
+            if variants.contains(&variant.text) {
+                return Err(TypeError::AlreadyDeclared { name: variant.text }.into());
+            }
+
+            variants.push(variant.text);
+
+            if !self.peeking_token(TokenKind::Comma)? {
+                break;
+            }
+
+            self.next_token()?;
+        }
+
+        self.expect_token(TokenKind::RCurly)?;
+
+        symbol_table.add_symbol(
+            name.text,
+            Symbol::Enum(DataType::Enum(EnumType::new(name.text, variants))),
+        );
+
+        Ast::new(symbol_table, AstKind::EnumDeclaration { name: name.text })
+    }
+
+    // `Name::Variant`
+    fn parse_enum_variant(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let name = self.expect_token(TokenKind::Ident)?;
+
+        self.expect_token(TokenKind::DoubleColon)?;
+
+        let variant = self.expect_token(TokenKind::Ident)?;
+
+        Ast::new(
+            symbol_table,
+            AstKind::EnumVariant {
+                name: name.text,
+                variant: variant.text,
+            },
+        )
+    }
+
+    // An integer or boolean worked out at compile time, for constants and the initial values of statics
+    fn parse_folded_value(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        data_type: &DataType<'src>,
+    ) -> CompilerResult<'src, i128> {
+        let mut value = self.parse_expr_bp(symbol_table, 0)?;
+
+        data_type.infer(&mut value)?;
+
+        if value.data_type != *data_type {
+            return Err(TypeError::ExpectedType {
+                expected: data_type.clone(),
+                found: value.data_type,
+            }
+            .into());
+        }
+
+        Ok(value.fold().ok_or(TypeError::NotConstant)?)
+    }
+
+    // `const NAME: Type = value`, where the value has to fold down to a literal
+    fn parse_constant_declaration(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        self.expect_token(TokenKind::Const)?;
+
+        let name = self.expect_token(TokenKind::Ident)?;
+
+        self.expect_token(TokenKind::Colon)?;
+
+        let data_type = self.parse_data_type(symbol_table)?;
+
+        self.expect_token(TokenKind::Assign)?;
+
+        let value = self.parse_folded_value(symbol_table, &data_type)?;
+
+        let kind = match (&data_type, value) {
+            (DataType::Int(int_type), value) => TokenKind::Number(value, Some(*int_type)),
+            (DataType::Bool, 0) => TokenKind::False,
+            (DataType::Bool, _) => TokenKind::True,
+            (DataType::Char, value) => TokenKind::Char(value as u8),
+            _ => unreachable!("Only integers, characters and booleans can be folded"),
+        };
+
+        if symbol_table.is_declared_in_scope(name.text) {
+            return Err(TypeError::AlreadyDeclared { name: name.text }.into());
+        }
+
+        symbol_table.add_symbol(name.text, Symbol::Constant(kind));
+
+        Ast::new(
+            symbol_table,
+            AstKind::ConstantDeclaration { name: name.text },
+        )
+    }
+
+    // `static name: Type`, which lives at a fixed address instead of on the stack.
+    // It starts out zeroed, unless it's given a value that can be folded
+    fn parse_static_declaration(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        self.expect_token(TokenKind::Static)?;
+
+        let name = self.expect_token(TokenKind::Ident)?;
+
+        self.expect_token(TokenKind::Colon)?;
+
+        let data_type = self.parse_data_type(symbol_table)?;
+
+        let value = if self.peeking_token(TokenKind::Assign)? {
+            self.next_token()?;
+
+            // Two's complement, the backends only write as many bytes as the type has
+            Some(self.parse_folded_value(symbol_table, &data_type)? as u64)
+        } else {
+            None
+        };
+
+        if symbol_table.is_declared_in_scope(name.text) {
+            return Err(TypeError::AlreadyDeclared { name: name.text }.into());
+        }
+
+        symbol_table.add_symbol(name.text, Symbol::Variable(data_type.clone()));
+
+        Ast::new(
+            symbol_table,
+            AstKind::StaticDeclaration {
+                name: name.text,
+                data_type,
+                value,
+            },
+        )
+    }
+
+    // Constants are replaced by the literal they were folded into
+    fn parse_constant(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+        token: Token<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let Some(Symbol::Constant(kind)) = symbol_table.get_symbol(token.text) else {
+            unreachable!("A constant should have been peeked");
+        };
+
+        let token = Token {
+            kind: kind.clone(),
+            ..token
+        };
+
+        Ast::new(symbol_table, AstKind::Node { token })
+    }
+
+    fn parse_statement(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        match self
+            .peek_token()?
+            .ok_or(ParseError::UnexpectedToken(None))?
+            .kind
+        {
+            TokenKind::Let => self.parse_let_statement(symbol_table, None),
+            TokenKind::AtSymbol if self.peeking_alignment()? => {
+                let alignment = self.parse_alignment()?;
+
+                self.parse_let_statement(symbol_table, Some(alignment))
+            }
+            TokenKind::Function => self.parse_function_declaration(symbol_table),
+            TokenKind::Extern => self.parse_extern_declaration(symbol_table),
+            TokenKind::AtSymbol if self.peeking_function_attribute()? => {
+                self.parse_function_declaration(symbol_table)
+            }
+            TokenKind::Struct => self.parse_structure_declaration(symbol_table),
+            TokenKind::Enum => self.parse_enum_declaration(symbol_table),
+            TokenKind::Const => self.parse_constant_declaration(symbol_table),
+            TokenKind::Static => self.parse_static_declaration(symbol_table),
+            TokenKind::Return => {
+                self.next_token()?;
+
+                let value = if self.peeking_token(TokenKind::SemiColon)? {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expr_bp(symbol_table, 0)?))
+                };
+
+                Ast::new(
+                    symbol_table,
+                    AstKind::Return {
+                        value,
+                        return_type: self
+                            .return_types
+                            .last()
+                            .expect("The program itself is always being parsed")
+                            .clone(),
+                    },
+                )
+            }
+            _ => {
+                let lhs = self.parse_expr_bp(symbol_table, 0)?;
+
+                if !self.peeking_token(TokenKind::Assign)? {
+                    return Ok(lhs);
+                }
+
+                self.next_token()?;
+
+                let rhs = self.parse_expr_bp(symbol_table, 0)?;
+
+                Ast::new(
+                    symbol_table,
+                    AstKind::Assign {
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    },
+                )
+            }
+        }
+    }
+
+    fn parse_block(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let scope_id = symbol_table.add_scope();
+
+        let mut statements: Vec<Ast<'src>> = Vec::new();
+        let mut positions = Vec::new();
+
+        self.expect_token(TokenKind::LCurly)?;
+
+        while !self.peeking_token(TokenKind::RCurly)? {
+            let statement_scope_id = symbol_table.get_scope();
+            let statement_start = self.clone(); // Fast, shallow copy
+            let position = self.next_position()?;
+
+            let statement = self.parse_statement(symbol_table).and_then(|statement| {
+                self.expect_token(TokenKind::SemiColon)?;
+                Ok(statement)
+            });
+
+            match statement {
+                Ok(statement) => {
+                    // Before the statement, so that it stays the last one
+                    positions.extend(self.instances.iter().map(|_| position));
+                    statements.append(&mut self.instances);
+                    statements.push(statement);
+                    positions.push(position);
+                }
+                // The error is kept and parsing goes on after the statement,
+                // unless it's the end of the file and there is nothing to go on with
+                Err(error) => {
+                    symbol_table.enter_scope(statement_scope_id);
+                    *self = statement_start;
+
+                    // Later uses of what it declared shouldn't be reported as undefined
+                    if let Some(name) = self.clone().declared_name() {
+                        if !symbol_table.is_declared_in_scope(name) {
+                            symbol_table.add_symbol(
+                                name,
+                                Symbol::Variable(DataType::Inferred(InferredType::Any)),
+                            );
+                        }
+                    }
+
+                    if !self.skip_statement() {
+                        return Err(error);
+                    }
+
+                    self.errors.borrow_mut().push(error);
+                    statements.push(Ast::new(symbol_table, AstKind::Error)?);
+                    positions.push(position);
+                }
+            }
+        }
+
+        self.expect_token(TokenKind::RCurly)?;
+
+        symbol_table.leave_scope();
+
+        Ast::new(
+            symbol_table,
+            AstKind::Block {
+                statements,
+                scope_id,
+                positions,
+            },
+        )
+    }
+
+    fn parse_data_type(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, DataType<'src>> {
+        let Some(token) = self.next_token()? else {
+            return Err(ParseError::UnexpectedToken(None).into());
+        };
 
         let data_type = match token.kind {
             TokenKind::Ident => match token.text {
                 "Void" => DataType::Void,
                 "Bool" => DataType::Bool,
+                "Char" => DataType::Char,
                 "S8" => DataType::Int(IntType::S8),
                 "S16" => DataType::Int(IntType::S16),
                 "S32" => DataType::Int(IntType::S32),
@@ -630,85 +2265,529 @@ impl<'src> Parser<'src> {
                 "U16" => DataType::Int(IntType::U16),
                 "U32" => DataType::Int(IntType::U32),
                 "U64" => DataType::Int(IntType::U64),
+                "F32" => DataType::Float(FloatType::F32),
+                "F64" => DataType::Float(FloatType::F64),
                 "String" => DataType::Ref(Box::new(DataType::Int(IntType::U8))),
                 other => {
-                    let Some(Symbol::Struct(ref fields)) = symbol_table.get_symbol(other) else {
+                    let Some(Symbol::Struct(ref struct_type) | Symbol::Enum(ref struct_type) | Symbol::TypeParameter(ref struct_type)) = symbol_table.get_symbol(other) else {
                         return Err(ParseError::UnexpectedToken(Some(token)).into());
                     };
 
-                    fields.clone()
-                }
-            },
-            TokenKind::Hash => DataType::Ref(Box::new(self.parse_data_type(symbol_table)?)),
-            _ => return Err(ParseError::UnexpectedToken(Some(token)).into()),
+                    struct_type.clone()
+                }
+            },
+            TokenKind::Hash => DataType::Ref(Box::new(self.parse_data_type(symbol_table)?)),
+            // `fn(A, B): R`, written like a declaration without the names
+            TokenKind::Function => {
+                self.expect_token(TokenKind::LParen)?;
+
+                let mut argument_types = Vec::new();
+
+                while !self.peeking_token(TokenKind::RParen)? {
+                    argument_types.push(self.parse_data_type(symbol_table)?);
+
+                    if !self.peeking_token(TokenKind::Comma)? {
+                        break;
+                    }
+
+                    self.next_token()?;
+                }
+
+                self.expect_token(TokenKind::RParen)?;
+
+                let return_type = if self.peeking_token(TokenKind::Colon)? {
+                    self.next_token()?;
+
+                    self.parse_data_type(symbol_table)?
+                } else {
+                    DataType::Void
+                };
+
+                DataType::Function {
+                    return_type: Box::new(return_type),
+                    argument_types,
+                }
+            }
+            // `[T; N]`
+            TokenKind::LSquare => {
+                let element = self.parse_data_type(symbol_table)?;
+
+                self.expect_token(TokenKind::SemiColon)?;
+
+                let length = match self.next_token()? {
+                    Some(Token {
+                        kind: TokenKind::Number(length, _),
+                        ..
+                    }) => length as usize,
+                    token => return Err(ParseError::UnexpectedToken(token).into()),
+                };
+
+                self.expect_token(TokenKind::RSquare)?;
+
+                DataType::Array {
+                    element: Box::new(element),
+                    length,
+                }
+            }
+            _ => return Err(ParseError::UnexpectedToken(Some(token)).into()),
+        };
+
+        Ok(data_type)
+    }
+
+    fn parse_if_statement(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        self.expect_token(TokenKind::If)?;
+
+        let condition = self.parse_expr_bp(symbol_table, 0)?;
+
+        let if_block = self.parse_block(symbol_table)?;
+
+        let else_block = if self.peeking_token(TokenKind::Else)? {
+            self.next_token()?;
+
+            let block = if self.peeking_token(TokenKind::If)? {
+                self.parse_if_statement(symbol_table)?
+            } else {
+                self.parse_block(symbol_table)?
+            };
+
+            Some(Box::new(block))
+        } else {
+            None
+        };
+
+        Ast::new(
+            symbol_table,
+            AstKind::IfStatement {
+                condition: Box::new(condition),
+                if_block: Box::new(if_block),
+                else_block,
+            },
+        )
+    }
+
+    // `match value { 0 => ..., 1 => ..., _ => ... }`, the arms are tried in order and the `_` one
+    // has to come last. Patterns are integers, or variants when matching on an enumeration
+    fn parse_match(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let keyword = self.expect_token(TokenKind::Match)?;
+
+        let scrutinee = self.parse_expr_bp(symbol_table, 0)?;
+
+        self.expect_token(TokenKind::LCurly)?;
+
+        let mut arms = Vec::new();
+        let mut default = None;
+
+        while !self.peeking_token(TokenKind::RCurly)? {
+            if default.is_some() {
+                return Err(ParseError::UnexpectedToken(self.next_token()?).into());
+            }
+
+            let pattern = match self.peek_token()? {
+                Some(token) if token.kind == TokenKind::Ident && token.text == "_" => {
+                    self.next_token()?;
+
+                    None
+                }
+                Some(token) if matches!(token.kind, TokenKind::Number(..) | TokenKind::Char(_)) => {
+                    self.next_token()?;
+
+                    Some(Ast::new(symbol_table, AstKind::Node { token })?)
+                }
+                Some(token)
+                    if matches!(symbol_table.get_symbol(token.text), Some(Symbol::Enum(_))) =>
+                {
+                    Some(self.parse_enum_variant(symbol_table)?)
+                }
+                Some(Token {
+                    kind: TokenKind::Sub,
+                    ..
+                }) if self.peeking_negative_number()? => {
+                    Some(self.parse_negative_number(symbol_table)?)
+                }
+                token => return Err(ParseError::UnexpectedToken(token).into()),
+            };
+
+            self.expect_token(TokenKind::FatArrow)?;
+
+            let body = self.parse_expr_bp(symbol_table, 0)?;
+
+            match pattern {
+                Some(pattern) => arms.push(MatchArm { pattern, body }),
+                None => default = Some(Box::new(body)),
+            }
+
+            if !self.peeking_token(TokenKind::Comma)? {
+                break;
+            }
+
+            self.next_token()?;
+        }
+
+        self.expect_token(TokenKind::RCurly)?;
+
+        let Some(default) = default else {
+            return Err(ParseError::MissingDefaultArm(keyword).into());
+        };
+
+        Ast::new(
+            symbol_table,
+            AstKind::Match {
+                scrutinee: Box::new(scrutinee),
+                arms,
+                default,
+            },
+        )
+    }
+
+    fn parse_while_loop(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        self.expect_token(TokenKind::While)?;
+
+        let condition = self.parse_expr_bp(symbol_table, 0)?;
+
+        let body = self.parse_block(symbol_table)?;
+
+        Ast::new(
+            symbol_table,
+            AstKind::WhileLoop {
+                condition: Box::new(condition),
+                body: Box::new(body),
+            },
+        )
+    }
+
+    // `for (init; condition; step) body` is `{ init; while condition { body; step; }; }`
+    fn parse_for_loop(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let for_position = self.next_position()?;
+
+        self.expect_token(TokenKind::For)?;
+
+        self.expect_token(TokenKind::LParen)?;
+
+        // Keeps the loop variable from leaking out of the loop
+        let scope_id = symbol_table.add_scope();
+
+        let init_position = self.next_position()?;
+        let init = self.parse_statement(symbol_table)?;
+
+        self.expect_token(TokenKind::SemiColon)?;
+
+        let condition = self.parse_expr_bp(symbol_table, 0)?;
+
+        self.expect_token(TokenKind::SemiColon)?;
+
+        let step_position = self.next_position()?;
+        let step = self.parse_statement(symbol_table)?;
+
+        self.expect_token(TokenKind::RParen)?;
+
+        // Blocks leave into their parent scope, so the body has to be nested in the one around it
+        let body_scope_id = symbol_table.add_scope();
+
+        let body_position = self.next_position()?;
+        let body = self.parse_block(symbol_table)?;
+
+        symbol_table.leave_scope();
+
+        let body = Ast::new(
+            symbol_table,
+            AstKind::Block {
+                statements: vec![body, step],
+                scope_id: body_scope_id,
+                positions: vec![body_position, step_position],
+            },
+        )?;
+
+        let while_loop = Ast::new(
+            symbol_table,
+            AstKind::WhileLoop {
+                condition: Box::new(condition),
+                body: Box::new(body),
+            },
+        )?;
+
+        symbol_table.leave_scope();
+
+        Ast::new(
+            symbol_table,
+            AstKind::Block {
+                statements: vec![init, while_loop],
+                scope_id,
+                positions: vec![init_position, for_position],
+            },
+        )
+    }
+
+    fn parse_function_call_args(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Vec<Ast<'src>>> {
+        let mut arguments = Vec::new();
+
+        self.expect_token(TokenKind::LParen)?;
+
+        while !self.peeking_token(TokenKind::RParen)? {
+            arguments.push(self.parse_expr_bp(symbol_table, 0)?);
+
+            if !self.peeking_token(TokenKind::Comma)? {
+                break;
+            }
+
+            self.next_token()?;
+        }
+
+        self.expect_token(TokenKind::RParen)?;
+
+        Ok(arguments)
+    }
+
+    // `@name(...)` is an intrinsic call when `name` is a known intrinsic, otherwise `@` is a dereference
+    fn peeking_intrinsic(&mut self) -> CompilerResult<'src, bool> {
+        let mut lookahead = self.clone(); // Fast, shallow copy
+
+        if !lookahead.peeking_token(TokenKind::AtSymbol)? {
+            return Ok(false);
+        }
+
+        lookahead.next_token()?;
+
+        let Some(name) = lookahead.next_token()? else {
+            return Ok(false);
         };
 
-        Ok(data_type)
+        Ok(name.kind == TokenKind::Ident
+            && Intrinsic::from_name(name.text).is_some()
+            && lookahead.peeking_token(TokenKind::LParen)?)
     }
 
-    fn parse_if_statement(
+    // `-` right before a number literal negates the literal itself instead of being an operator
+    fn peeking_negative_number(&mut self) -> CompilerResult<'src, bool> {
+        let mut lookahead = self.clone(); // Fast, shallow copy
+
+        if !lookahead.peeking_token(TokenKind::Sub)? {
+            return Ok(false);
+        }
+
+        lookahead.next_token()?;
+
+        Ok(matches!(
+            lookahead.next_token()?,
+            Some(Token {
+                kind: TokenKind::Number(..),
+                ..
+            })
+        ))
+    }
+
+    // `-128s8` is a single constant, so it fits even though `128s8` doesn't
+    fn parse_negative_number(
         &mut self,
         symbol_table: &mut SymbolTable<'src>,
     ) -> CompilerResult<'src, Ast<'src>> {
-        self.expect_token(TokenKind::If)?;
-
-        let condition = self.parse_expr_bp(symbol_table, 0)?;
+        let minus = self.expect_token(TokenKind::Sub)?;
+
+        let Some(Token {
+            kind: TokenKind::Number(value, int_type),
+            text,
+            ..
+        }) = self.next_token()?
+        else {
+            unreachable!("A number should have been peeked after the `-`");
+        };
 
-        let if_block = self.parse_block(symbol_table)?;
+        Ast::new(
+            symbol_table,
+            AstKind::Node {
+                token: Token {
+                    kind: TokenKind::Number(-value, int_type),
+                    text,
+                    position: minus.position,
+                },
+            },
+        )
+    }
 
-        let else_block = if self.peeking_token(TokenKind::Else)? {
-            self.next_token()?;
+    fn parse_intrinsic(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let at_symbol = self.expect_token(TokenKind::AtSymbol)?;
 
-            let block = if self.peeking_token(TokenKind::If)? {
-                self.parse_if_statement(symbol_table)?
-            } else {
-                self.parse_block(symbol_table)?
-            };
+        let name = self.expect_token(TokenKind::Ident)?;
 
-            Some(Box::new(block))
-        } else {
-            None
+        let Some(intrinsic) = Intrinsic::from_name(name.text) else {
+            return Err(ParseError::UnexpectedToken(Some(name)).into());
         };
 
+        let arguments = self.parse_function_call_args(symbol_table)?;
+
+        if intrinsic == Intrinsic::SourceLocation {
+            if !arguments.is_empty() {
+                return Err(TypeError::WrongNumberOfArguments.into());
+            }
+
+            let location = format!(
+                "{}:{}:{}",
+                self.config.file, at_symbol.position.line, at_symbol.position.column
+            );
+
+            return Ast::new(
+                symbol_table,
+                AstKind::Node {
+                    token: Token {
+                        kind: TokenKind::Str(Cow::Owned(location.into_bytes())),
+                        ..name
+                    },
+                },
+            );
+        }
+
         Ast::new(
             symbol_table,
-            AstKind::IfStatement {
-                condition: Box::new(condition),
-                if_block: Box::new(if_block),
-                else_block,
+            AstKind::Intrinsic {
+                intrinsic,
+                arguments,
             },
         )
     }
 
-    fn parse_while_loop(
+    // Adjacent string literals are joined at compile time, so `"foo" "bar"` is `"foobar"`,
+    // if any of them has a `{name}` in it the whole literal becomes an interpolation instead
+    fn parse_string_literal(
         &mut self,
         symbol_table: &mut SymbolTable<'src>,
     ) -> CompilerResult<'src, Ast<'src>> {
-        self.expect_token(TokenKind::While)?;
+        let mut pieces = self.parse_string_pieces(symbol_table)?;
 
-        let condition = self.parse_expr_bp(symbol_table, 0)?;
+        if let [piece] = &pieces[..] {
+            if piece.string_literal().is_some() {
+                return Ok(pieces.remove(0));
+            }
+        }
 
-        let body = self.parse_block(symbol_table)?;
+        Ast::new(symbol_table, AstKind::Interpolation { pieces })
+    }
+
+    // The texts and values a string literal is made of, or the literal itself if it has no
+    // `{name}` in it
+    fn parse_string_pieces(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Vec<Ast<'src>>> {
+        let mut token = self
+            .next_token()?
+            .ok_or(ParseError::UnexpectedToken(None))?;
+
+        let TokenKind::Str(ref mut string) = token.kind else {
+            return Err(ParseError::UnexpectedToken(Some(token)).into());
+        };
+
+        let mut texts = vec![token.text];
+
+        while let Some(
+            next @ Token {
+                kind: TokenKind::Str(_),
+                ..
+            },
+        ) = self.peek_token()?
+        {
+            self.next_token()?;
+
+            texts.push(next.text);
+        }
+
+        let mut parts = Vec::new();
+
+        for text in texts {
+            for part in split_string_literal(text)? {
+                match (parts.last_mut(), part) {
+                    (Some(StringPart::Text(text)), StringPart::Text(next_text)) => {
+                        text.extend_from_slice(&next_text)
+                    }
+                    (_, part) => parts.push(part),
+                }
+            }
+        }
+
+        if !parts
+            .iter()
+            .any(|part| matches!(part, StringPart::Value(_)))
+        {
+            *string = Cow::Owned(match parts.pop() {
+                Some(StringPart::Text(text)) => text,
+                _ => Vec::new(),
+            });
+
+            return Ok(vec![Ast::new(symbol_table, AstKind::Node { token })?]);
+        }
+
+        parts
+            .into_iter()
+            .map(|part| {
+                let token = match part {
+                    StringPart::Text(text) => Token {
+                        kind: TokenKind::Str(Cow::Owned(text)),
+                        ..token.clone()
+                    },
+                    StringPart::Value(name) => Token {
+                        text: name,
+                        kind: TokenKind::Ident,
+                        ..token.clone()
+                    },
+                };
+
+                Ast::new(symbol_table, AstKind::Node { token })
+            })
+            .collect()
+    }
+
+    // `sizeof(T)` is replaced by the size as a `U64` literal
+    fn parse_size_of(
+        &mut self,
+        symbol_table: &mut SymbolTable<'src>,
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let token = self.expect_token(TokenKind::SizeOf)?;
+
+        self.expect_token(TokenKind::LParen)?;
+
+        let data_type = self.parse_data_type(symbol_table)?;
+
+        self.expect_token(TokenKind::RParen)?;
 
         Ast::new(
             symbol_table,
-            AstKind::WhileLoop {
-                condition: Box::new(condition),
-                body: Box::new(body),
+            AstKind::Node {
+                token: Token {
+                    kind: TokenKind::Number(data_type.size() as i128, Some(IntType::U64)),
+                    ..token
+                },
             },
         )
     }
 
-    fn parse_function_call_args(
+    // `[a, b, c]`
+    fn parse_array_literal(
         &mut self,
         symbol_table: &mut SymbolTable<'src>,
-    ) -> CompilerResult<'src, Vec<Ast<'src>>> {
-        let mut arguments = Vec::new();
+    ) -> CompilerResult<'src, Ast<'src>> {
+        let mut elements = Vec::new();
 
-        self.expect_token(TokenKind::LParen)?;
+        self.expect_token(TokenKind::LSquare)?;
 
-        while !self.peeking_token(TokenKind::RParen)? {
-            arguments.push(self.parse_expr_bp(symbol_table, 0)?);
+        while !self.peeking_token(TokenKind::RSquare)? {
+            elements.push(self.parse_expr_bp(symbol_table, 0)?);
 
             if !self.peeking_token(TokenKind::Comma)? {
                 break;
@@ -717,9 +2796,9 @@ impl<'src> Parser<'src> {
             self.next_token()?;
         }
 
-        self.expect_token(TokenKind::RParen)?;
+        self.expect_token(TokenKind::RSquare)?;
 
-        Ok(arguments)
+        Ast::new(symbol_table, AstKind::ArrayLiteral { elements })
     }
 
     // Simple pratt parser
@@ -745,9 +2824,58 @@ impl<'src> Parser<'src> {
             TokenKind::LCurly => self.parse_block(symbol_table)?,
             TokenKind::If => self.parse_if_statement(symbol_table)?,
             TokenKind::While => self.parse_while_loop(symbol_table)?,
+            TokenKind::For => self.parse_for_loop(symbol_table)?,
+            TokenKind::Match => self.parse_match(symbol_table)?,
+            TokenKind::AtSymbol if self.peeking_intrinsic()? => self.parse_intrinsic(symbol_table)?,
+            TokenKind::Sub if self.peeking_negative_number()? => {
+                self.parse_negative_number(symbol_table)?
+            }
+            TokenKind::Str(_) => self.parse_string_literal(symbol_table)?,
+            TokenKind::LSquare => self.parse_array_literal(symbol_table)?,
+            TokenKind::SizeOf => self.parse_size_of(symbol_table)?,
+            TokenKind::Ident
+                if matches!(symbol_table.get_symbol(token.text), Some(Symbol::Enum(_))) =>
+            {
+                self.parse_enum_variant(symbol_table)?
+            }
+            TokenKind::Ident
+                if matches!(
+                    symbol_table.get_symbol(token.text),
+                    Some(Symbol::Constant(_))
+                ) =>
+            {
+                self.next_token()?;
+
+                self.parse_constant(symbol_table, token)?
+            }
+            TokenKind::Ident if self.peeking_named_intrinsic(symbol_table, token.text)? => {
+                self.next_token()?;
+
+                if token.text == "len" {
+                    let arguments = self.parse_function_call_args(symbol_table)?;
+
+                    Ast::new(
+                        symbol_table,
+                        AstKind::Intrinsic {
+                            intrinsic: Intrinsic::Length,
+                            arguments,
+                        },
+                    )?
+                } else {
+                    self.parse_print_call(symbol_table, token)?
+                }
+            }
+            TokenKind::Ident
+                if matches!(symbol_table.get_symbol(token.text), Some(Symbol::Generic(_))) =>
+            {
+                self.next_token()?;
+
+                self.parse_generic_call(symbol_table, token.text)?
+            }
             TokenKind::Ident
-            | TokenKind::Number(_)
-            | TokenKind::Str(_)
+            | TokenKind::Number(..)
+            | TokenKind::Float(_)
+            | TokenKind::Char(_)
             | TokenKind::True
             | TokenKind::False => {
                 self.next_token()?;
@@ -816,6 +2944,26 @@ impl<'src> Parser<'src> {
 
                     self.next_token()?;
 
+                    // `condition ? a : b` is the same as `if condition { a } else { b }`
+                    if oper.kind == TokenKind::Question {
+                        let if_block = self.parse_expr_bp(symbol_table, 0)?;
+
+                        self.expect_token(TokenKind::Colon)?;
+
+                        let else_block = self.parse_expr_bp(symbol_table, infix_right_bp)?;
+
+                        lhs = Ast::new(
+                            symbol_table,
+                            AstKind::IfStatement {
+                                condition: Box::new(lhs),
+                                if_block: Box::new(if_block),
+                                else_block: Some(Box::new(else_block)),
+                            },
+                        )?;
+
+                        continue;
+                    }
+
                     let rhs = self.parse_expr_bp(symbol_table, infix_right_bp)?;
 
                     AstKind::Infix {
@@ -832,3 +2980,338 @@ impl<'src> Parser<'src> {
         Ok(lhs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds_and_texts<'src>(tokens: Vec<Token<'src>>) -> Vec<(TokenKind<'src>, &'src str)> {
+        tokens
+            .into_iter()
+            .map(|token| (token.kind, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_returns_every_token() {
+        let tokens = Parser::tokenize("let x: U8 = 5u8;").unwrap();
+
+        assert_eq!(
+            kinds_and_texts(tokens),
+            vec![
+                (TokenKind::Let, "let"),
+                (TokenKind::Ident, "x"),
+                (TokenKind::Colon, ":"),
+                (TokenKind::Ident, "U8"),
+                (TokenKind::Assign, "="),
+                (TokenKind::Number(5, Some(IntType::U8)), "5u8"),
+                (TokenKind::SemiColon, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn token_texts_rebuild_the_source() {
+        let source = "a == b != c <= d >= e && f || g => h :: i = j < k > l ! m & n | o : p";
+        let tokens = Parser::tokenize(source).unwrap();
+
+        let texts: Vec<&str> = tokens.iter().map(|token| token.text).collect();
+
+        assert_eq!(texts.join(" "), source);
+    }
+
+    #[test]
+    fn parse_errors_underline_the_token() {
+        let mut symbol_table = SymbolTable::new();
+
+        let errors = Parser::parse("{\n    let x = ;\n    0;\n}", &mut symbol_table).unwrap_err();
+
+        let rendered = [
+            "Unexpected token `;` at line 2, column 13.",
+            "  |",
+            "2 |     let x = ;",
+            "  |             ^",
+        ];
+
+        assert_eq!(errors[0].to_string(), rendered.join("\n"));
+    }
+
+    #[test]
+    fn custom_line_comments_are_skipped() {
+        let config = ParserConfig {
+            line_comments: &["#"],
+            ..ParserConfig::default()
+        };
+
+        let tokens = Parser::tokenize_with_config("x # y\nz // w", config).unwrap();
+
+        assert_eq!(
+            kinds_and_texts(tokens),
+            vec![
+                (TokenKind::Ident, "x"),
+                (TokenKind::Ident, "z"),
+                (TokenKind::Div, "/"),
+                (TokenKind::Div, "/"),
+                (TokenKind::Ident, "w"),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_comments_end_at_the_newline() {
+        let tokens = Parser::tokenize("1 + 2 // ignored\n+ 3").unwrap();
+
+        assert_eq!(
+            kinds_and_texts(tokens),
+            vec![
+                (TokenKind::Number(1, None), "1"),
+                (TokenKind::Add, "+"),
+                (TokenKind::Number(2, None), "2"),
+                (TokenKind::Add, "+"),
+                (TokenKind::Number(3, None), "3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_are_found_between_tokens() {
+        let config = ParserConfig::default();
+
+        assert!(Parser::has_comments("x // y", config).unwrap());
+        assert!(Parser::has_comments("x /* y */ z", config).unwrap());
+        assert!(!Parser::has_comments("x \"// y\" z", config).unwrap());
+        assert!(!Parser::has_comments("a == b != c <= d >= e && f || g :: h", config).unwrap());
+    }
+
+    #[test]
+    fn let_literals_default_to_the_program_type() {
+        let mut symbol_table = SymbolTable::new();
+
+        assert!(Parser::parse("{ let x = 5; x; }", &mut symbol_table).is_ok());
+        assert!(Parser::parse("{ let x = -5; 0; }", &mut symbol_table).is_ok());
+    }
+
+    #[test]
+    fn empty_comment_markers_are_rejected() {
+        let config = ParserConfig {
+            line_comments: &[""],
+            ..ParserConfig::default()
+        };
+
+        assert!(Parser::tokenize_with_config("x", config).is_err());
+    }
+
+    #[test]
+    fn broken_structures_are_still_defined() {
+        let mut symbol_table = SymbolTable::new();
+
+        let source = "{ struct Pair { let a U64; }; let p: Pair; p.a; }";
+        let errors = Parser::parse(source, &mut symbol_table).unwrap_err();
+
+        assert!(errors[0].to_string().starts_with("Unexpected token `U64`"));
+    }
+
+    #[test]
+    fn generic_functions_are_instantiated_per_type() {
+        let mut symbol_table = SymbolTable::new();
+
+        let source = "{
+            fn max<T>(let a: T, let b: T): T {
+                if a > b { a; } else { b; };
+            };
+
+            let small: U32 = max(3u32, 7u32);
+            let signed: S64 = max(-5s64, -2s64);
+            let again: U32 = max(small, 9);
+
+            0;
+        }";
+
+        let ast = Parser::parse(source, &mut symbol_table).unwrap();
+
+        let AstKind::Block { ref statements, .. } = ast.kind else {
+            panic!("A program is a block");
+        };
+
+        let instances: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement.kind {
+                AstKind::FunctionDeclaration {
+                    name: "max",
+                    ref type_arguments,
+                    ..
+                } => Some(type_arguments.clone()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            instances,
+            [
+                vec![DataType::Int(IntType::U32)],
+                vec![DataType::Int(IntType::S64)]
+            ]
+        );
+    }
+
+    #[test]
+    fn generic_functions_that_cant_be_instantiated_fail() {
+        let error = |source| {
+            let mut symbol_table = SymbolTable::new();
+
+            Parser::parse(source, &mut symbol_table).unwrap_err()[0].to_string()
+        };
+
+        assert_eq!(
+            error("{ fn f<T>(let x: U64): U64 { x; }; f(1); }"),
+            "type parameter `T` isn't used by any argument"
+        );
+        assert!(!error("{ fn f<T>(let x: T): T { -x; }; f(true); }").is_empty());
+        assert_eq!(
+            error("{ fn f<T>(let x: T, let n: U64): U64 { n == 0 ? 0 : f(#x, n - 1); }; f(1, 3); }"),
+            format!("generic function `f` needs more than {MAX_INSTANCE_DEPTH} instances inside of each other")
+        );
+    }
+
+    #[test]
+    fn names_declared_twice_in_a_scope_fail() {
+        let error = |source| {
+            let mut symbol_table = SymbolTable::new();
+
+            Parser::parse(source, &mut symbol_table).unwrap_err()[0].to_string()
+        };
+
+        for source in [
+            "{ let f: U64 = 1; fn f(): U64 { 2; }; f(); }",
+            "{ let f: U64 = 1; fn f<T>(let x: T): T { x; }; 0; }",
+            "{ let f: U64 = 1; struct f { let x: U64; }; 0; }",
+            "{ let f: U64 = 1; enum f { A }; 0; }",
+            "{ let f: U64 = 1; const f: U64 = 2; 0; }",
+            "{ let f: U64 = 1; static f: U64 = 2; 0; }",
+            "{ fn f(): U64 { 2; }; let f: U64 = 1; 0; }",
+            "{ struct f { let x: U64; }; struct f { let y: U64; }; 0; }",
+        ] {
+            assert_eq!(error(source), "`f` is already declared in this scope");
+        }
+
+        let mut symbol_table = SymbolTable::new();
+
+        let source = "{ let f: U64 = 1; if true { fn f(): U64 { 2; }; let g: U64 = f(); }; f; }";
+
+        assert!(Parser::parse(source, &mut symbol_table).is_ok());
+    }
+
+    #[test]
+    fn division_by_a_constant_zero_fails() {
+        let error = |source| {
+            let mut symbol_table = SymbolTable::new();
+
+            Parser::parse(source, &mut symbol_table).unwrap_err()[0].to_string()
+        };
+
+        assert!(error("{ 5 / 0; }").starts_with("`/` by zero at line 1, column 5"));
+        assert!(error("{ let x: U64 = 5; x % (2 - 2); }").starts_with("`%` by zero"));
+
+        let mut symbol_table = SymbolTable::new();
+
+        assert!(Parser::parse("{ let x: U64 = 5; x / 1; }", &mut symbol_table).is_ok());
+    }
+
+    #[test]
+    fn match_arms_with_the_same_value_fail() {
+        let error = |source| {
+            let mut symbol_table = SymbolTable::new();
+
+            Parser::parse(source, &mut symbol_table).unwrap_err()[0].to_string()
+        };
+
+        let unreachable = "this match arm is never taken";
+
+        assert!(error("{ match 3 { 1 => 2, 1 => 3, _ => 4 }; }").starts_with(unreachable));
+        assert!(error("{ match 'a' { 'a' => 2, 'a' => 3, _ => 4 }; }").starts_with(unreachable));
+        assert!(error("{ match 3 { -1 => 2, -1 => 3, _ => 4 }; }").starts_with(unreachable));
+
+        let source = "{ enum Color { Red, Blue }; match Color::Red { Color::Blue => 1, Color::Blue => 2, _ => 3 }; }";
+        assert!(error(source).starts_with(unreachable));
+
+        let mut symbol_table = SymbolTable::new();
+        let source = "{ match 3 { 1 => 2, -1 => 3, _ => 4 }; }";
+
+        assert!(Parser::parse(source, &mut symbol_table).is_ok());
+    }
+
+    #[test]
+    fn void_results_cant_be_used_as_values() {
+        for source in [
+            "{ fn f() {}; let x: U64 = f(); x; }",
+            "{ fn f() {}; f() + 1; }",
+        ] {
+            let mut symbol_table = SymbolTable::new();
+            let errors = Parser::parse(source, &mut symbol_table).unwrap_err();
+
+            assert_eq!(
+                errors[0].to_string(),
+                "cannot use the result of a `Void` expression as a value"
+            );
+        }
+
+        let mut symbol_table = SymbolTable::new();
+
+        assert!(Parser::parse("{ fn f() {}; f(); 0; }", &mut symbol_table).is_ok());
+    }
+
+    #[test]
+    fn negative_literals_fit_down_to_the_minimum() {
+        let mut symbol_table = SymbolTable::new();
+
+        assert!(Parser::parse("{ let x: S8 = -128s8; 0; }", &mut symbol_table).is_ok());
+
+        for (source, message) in [
+            (
+                "{ let x: S8 = -129s8; 0; }",
+                "number `-129` doesn't fit in `S8`",
+            ),
+            (
+                "{ let x: S8 = 128s8; 0; }",
+                "number `128` doesn't fit in `S8`",
+            ),
+        ] {
+            let mut symbol_table = SymbolTable::new();
+            let errors = Parser::parse(source, &mut symbol_table).unwrap_err();
+
+            assert_eq!(errors[0].to_string(), message);
+        }
+    }
+
+    #[test]
+    fn misplaced_underscores_in_numbers_fail() {
+        for text in ["1__0", "10_", "_1", "_1_000"] {
+            let source = format!("{{ {text}; }}");
+            let mut symbol_table = SymbolTable::new();
+            let errors = Parser::parse(&source, &mut symbol_table).unwrap_err();
+
+            assert_eq!(errors[0].to_string(), format!("Malformed number `{text}`."));
+        }
+
+        let mut symbol_table = SymbolTable::new();
+
+        assert!(Parser::parse("{ let _x: U64 = 1_000; 0; }", &mut symbol_table).is_ok());
+    }
+
+    #[test]
+    fn alignments_have_to_be_powers_of_two() {
+        for alignment in ["24", "8 * 3", "0"] {
+            let source =
+                format!("{{ let x: U8 = 1; let p: #U8 = @align_up(#x, {alignment}); 0; }}");
+            let mut symbol_table = SymbolTable::new();
+            let errors = Parser::parse(&source, &mut symbol_table).unwrap_err();
+
+            assert_eq!(errors[0].to_string(), "this number isn't a power of two");
+        }
+
+        let mut symbol_table = SymbolTable::new();
+        let source = "{ let x: U8 = 1; let p: #U8 = @align_up(#x, 8 * 2); 0; }";
+
+        assert!(Parser::parse(source, &mut symbol_table).is_ok());
+    }
+}
@@ -0,0 +1,1156 @@
+use crate::{
+    ast::InlineHint,
+    builtins::{Builtins, Extern},
+    bytecode::{Argument, ByteCode, Function, Global, OpCode},
+    types::{DataType, EnumType, FloatType, InferredType, IntType, StructType},
+};
+use std::{collections::HashSet, error::Error, fmt, ptr, rc::Rc, str};
+
+// Files start with this, followed by the version as a little endian U16
+const MAGIC: &[u8; 4] = b"OILB";
+
+// Bumped whenever the encoding of anything changes, like an opcode being added, so files written
+// by an older compiler are rejected instead of being read wrongly
+pub const VERSION: u16 = 3;
+
+pub enum DeserializeError {
+    NotByteCode,
+    UnsupportedVersion { version: u16 },
+    UnexpectedEnd,
+    Invalid { kind: &'static str, value: u64 },
+    InvalidString,
+    UnknownBuiltin { name: String },
+    UnknownSymbol { name: String },
+    UnknownField { name: String },
+    WrongType { operand: &'static str },
+    TrailingBytes,
+}
+
+impl Error for DeserializeError {}
+
+impl fmt::Debug for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotByteCode => write!(f, "not a bytecode file"),
+            Self::UnsupportedVersion { version } => write!(
+                f,
+                "bytecode version {version} isn't supported, only version {VERSION} is"
+            ),
+            Self::UnexpectedEnd => write!(f, "bytecode ended unexpectedly"),
+            Self::Invalid { kind, value } => write!(f, "invalid {kind} {value} in bytecode"),
+            Self::InvalidString => write!(f, "name in bytecode isn't valid UTF-8"),
+            Self::UnknownBuiltin { name } => write!(f, "unknown builtin `{name}` in bytecode"),
+            Self::UnknownSymbol { name } => write!(f, "unknown symbol `{name}` in bytecode"),
+            Self::UnknownField { name } => write!(f, "unknown field `{name}` in bytecode"),
+            Self::WrongType { operand } => write!(f, "{operand} in bytecode has the wrong type"),
+            Self::TrailingBytes => write!(f, "bytecode continues after its end"),
+        }
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+// Builtins are only stored by name, as their code comes with the compiler reading them back.
+// Numbers are LEB128, and lists are prefixed with their length
+pub fn serialize(bytecode: &ByteCode) -> Vec<u8> {
+    let mut writer = Writer {
+        bytes: MAGIC.to_vec(),
+        structs: Vec::new(),
+        enums: Vec::new(),
+    };
+
+    writer.bytes.extend(VERSION.to_le_bytes());
+
+    writer.number(bytecode.builtins.len() as u64);
+
+    for builtin in &bytecode.builtins {
+        writer.string(builtin.name);
+    }
+
+    writer.number(bytecode.externs.len() as u64);
+
+    for extern_function in &bytecode.externs {
+        writer.string(extern_function.name);
+        writer.data_type(&extern_function.data_type);
+    }
+
+    writer.number(bytecode.strings.len() as u64);
+
+    for string in &bytecode.strings {
+        writer.slice(string);
+    }
+
+    writer.number(bytecode.globals.len() as u64);
+
+    for global in &bytecode.globals {
+        writer.data_type(&global.data_type);
+
+        match global.value {
+            Some(value) => {
+                writer.bytes.push(1);
+                writer.number(value);
+            }
+            None => writer.bytes.push(0),
+        }
+    }
+
+    writer.number(bytecode.functions.len() as u64);
+
+    for function in &bytecode.functions {
+        writer.function(function);
+    }
+
+    writer.bytes
+}
+
+// Names and strings point into `bytes`, so it has to outlive the bytecode
+pub fn deserialize(bytes: &[u8]) -> Result<ByteCode<'_>, DeserializeError> {
+    let mut reader = Reader {
+        bytes,
+        position: 0,
+        structs: Vec::new(),
+        enums: Vec::new(),
+    };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(DeserializeError::NotByteCode);
+    }
+
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+
+    if version != VERSION {
+        return Err(DeserializeError::UnsupportedVersion { version });
+    }
+
+    let mut bytecode = ByteCode::new();
+    let hosted = Builtins::hosted();
+
+    for _ in 0..reader.number()? {
+        let name = reader.string()?;
+
+        let builtin = hosted
+            .functions
+            .iter()
+            .find(|builtin| builtin.name == name)
+            .ok_or_else(|| DeserializeError::UnknownBuiltin {
+                name: name.to_string(),
+            })?;
+
+        bytecode.builtins.push(builtin.clone());
+    }
+
+    for _ in 0..reader.number()? {
+        let name = reader.string()?;
+        let data_type = reader.data_type()?;
+
+        bytecode.externs.push(Extern { name, data_type });
+    }
+
+    for _ in 0..reader.number()? {
+        let string = reader.slice()?;
+
+        bytecode.add_string(string);
+    }
+
+    for _ in 0..reader.number()? {
+        let data_type = reader.data_type()?;
+
+        let value = match reader.byte()? {
+            0 => None,
+            1 => Some(reader.number()?),
+            tag => return Err(reader.invalid("global", tag)),
+        };
+
+        bytecode.add_global(Global { data_type, value });
+    }
+
+    for _ in 0..reader.number()? {
+        let function = reader.function()?;
+
+        bytecode.add_function(function);
+    }
+
+    check_symbols(&bytecode)?;
+
+    if reader.position != bytes.len() {
+        return Err(DeserializeError::TrailingBytes);
+    }
+
+    Ok(bytecode)
+}
+
+// Functions are called through symbols, and strings and globals are read through them, so every
+// symbol has to be one of those with a type that fits
+fn check_symbols(bytecode: &ByteCode) -> Result<(), DeserializeError> {
+    let functions: HashSet<&str> = bytecode
+        .builtins
+        .iter()
+        .map(|builtin| builtin.name)
+        .chain(
+            bytecode
+                .externs
+                .iter()
+                .map(|extern_function| extern_function.name),
+        )
+        .chain(
+            bytecode
+                .functions
+                .iter()
+                .map(|function| function.name.as_str()),
+        )
+        .collect();
+
+    let data: HashSet<String> = (0..bytecode.strings.len())
+        .map(ByteCode::string_symbol_name)
+        .chain((0..bytecode.globals.len()).map(ByteCode::global_symbol_name))
+        .collect();
+
+    fn check(
+        functions: &HashSet<&str>,
+        data: &HashSet<String>,
+        argument: &Argument,
+    ) -> Result<(), DeserializeError> {
+        match argument {
+            Argument::Symbol { name, data_type } => match data_type {
+                DataType::Function { .. } if functions.contains(name.as_str()) => Ok(()),
+                DataType::Ref(_) if data.contains(name) => Ok(()),
+                _ if functions.contains(name.as_str()) => Err(DeserializeError::WrongType {
+                    operand: "function symbol",
+                }),
+                _ if data.contains(name) => Err(DeserializeError::WrongType {
+                    operand: "data symbol",
+                }),
+                _ => Err(DeserializeError::UnknownSymbol { name: name.clone() }),
+            },
+            Argument::Deref(data_argument)
+            | Argument::StructField {
+                data: data_argument,
+                ..
+            } => check(functions, data, data_argument),
+            _ => Ok(()),
+        }
+    }
+
+    for function in &bytecode.functions {
+        for opcode in &function.opcodes {
+            for argument in opcode.arguments() {
+                check(&functions, &data, argument)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The tags are part of the format, so changing any of them needs a new version
+fn opcode_tag(opcode: &OpCode) -> u8 {
+    match opcode {
+        OpCode::Mov { .. } => 0,
+        OpCode::Add { .. } => 1,
+        OpCode::Sub { .. } => 2,
+        OpCode::Mul { .. } => 3,
+        OpCode::Div { .. } => 4,
+        OpCode::Mod { .. } => 5,
+        OpCode::And { .. } => 6,
+        OpCode::Or { .. } => 7,
+        OpCode::Xor { .. } => 8,
+        OpCode::Not { .. } => 9,
+        OpCode::BitNot { .. } => 10,
+        OpCode::Ref { .. } => 11,
+        OpCode::Index { .. } => 12,
+        OpCode::SetIndex { .. } => 13,
+        OpCode::SetField { .. } => 14,
+        OpCode::Copy { .. } => 15,
+        OpCode::SetIfEqual { .. } => 16,
+        OpCode::SetIfNotEqual { .. } => 17,
+        OpCode::SetIfGreater { .. } => 18,
+        OpCode::SetIfLess { .. } => 19,
+        OpCode::SetIfGreaterOrEqual { .. } => 20,
+        OpCode::SetIfLessOrEqual { .. } => 21,
+        OpCode::Negate { .. } => 22,
+        OpCode::Label { .. } => 23,
+        OpCode::Goto { .. } => 24,
+        OpCode::GotoIfZero { .. } => 25,
+        OpCode::GotoIfNotZero { .. } => 26,
+        OpCode::Call { .. } => 27,
+        OpCode::Return => 28,
+        OpCode::Breakpoint => 29,
+        OpCode::Trap => 30,
+        OpCode::ReadCycleCounter { .. } => 31,
+        OpCode::SourceLine { .. } => 32,
+    }
+}
+
+fn int_type_tag(int_type: IntType) -> u8 {
+    match int_type {
+        IntType::S8 => 0,
+        IntType::S16 => 1,
+        IntType::S32 => 2,
+        IntType::S64 => 3,
+        IntType::U8 => 4,
+        IntType::U16 => 5,
+        IntType::U32 => 6,
+        IntType::U64 => 7,
+    }
+}
+
+struct Writer<'a, 'src> {
+    bytes: Vec<u8>,
+    // Written out in full where they are first used, and only by index after that
+    structs: Vec<&'a StructType<'src>>,
+    enums: Vec<&'a EnumType<'src>>,
+}
+
+impl<'a, 'src> Writer<'a, 'src> {
+    fn number(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+
+            value >>= 7;
+
+            if value == 0 {
+                self.bytes.push(byte);
+
+                return;
+            }
+
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    fn slice(&mut self, slice: &[u8]) {
+        self.number(slice.len() as u64);
+        self.bytes.extend_from_slice(slice);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.slice(string.as_bytes());
+    }
+
+    fn data_type(&mut self, data_type: &'a DataType<'src>) {
+        match data_type {
+            DataType::Void => self.bytes.push(0),
+            DataType::Bool => self.bytes.push(1),
+            DataType::Char => self.bytes.push(2),
+            DataType::Inferred(inferred_type) => {
+                self.bytes.push(3);
+                self.bytes.push(match inferred_type {
+                    InferredType::Int => 0,
+                    InferredType::Float => 1,
+                    InferredType::Ref => 2,
+                    InferredType::Any => 3,
+                });
+            }
+            DataType::Int(int_type) => {
+                self.bytes.push(4);
+                self.bytes.push(int_type_tag(*int_type));
+            }
+            DataType::Float(float_type) => {
+                self.bytes.push(5);
+                self.bytes.push(match float_type {
+                    FloatType::F32 => 0,
+                    FloatType::F64 => 1,
+                });
+            }
+            DataType::Ref(deref) => {
+                self.bytes.push(6);
+                self.data_type(deref);
+            }
+            // Added before the fields, as one of them can refer back to the structure
+            DataType::Struct(struct_type) => {
+                self.bytes.push(7);
+
+                match self
+                    .structs
+                    .iter()
+                    .position(|known| ptr::eq(*known, &**struct_type))
+                {
+                    Some(index) => self.number(index as u64),
+                    None => {
+                        self.number(self.structs.len() as u64);
+                        self.structs.push(struct_type);
+                        self.string(struct_type.name);
+                        self.number(struct_type.fields().len() as u64);
+
+                        for (name, data_type) in struct_type.fields() {
+                            self.string(name);
+                            self.data_type(data_type);
+                        }
+                    }
+                }
+            }
+            DataType::Enum(enum_type) => {
+                self.bytes.push(8);
+
+                match self
+                    .enums
+                    .iter()
+                    .position(|known| ptr::eq(*known, &**enum_type))
+                {
+                    Some(index) => self.number(index as u64),
+                    None => {
+                        self.number(self.enums.len() as u64);
+                        self.enums.push(enum_type);
+                        self.string(enum_type.name);
+                        self.number(enum_type.variants.len() as u64);
+
+                        for variant in &enum_type.variants {
+                            self.string(variant);
+                        }
+                    }
+                }
+            }
+            DataType::Array { element, length } => {
+                self.bytes.push(9);
+                self.data_type(element);
+                self.number(*length as u64);
+            }
+            DataType::Function {
+                return_type,
+                argument_types,
+            } => {
+                self.bytes.push(10);
+                self.data_type(return_type);
+                self.number(argument_types.len() as u64);
+
+                for argument_type in argument_types {
+                    self.data_type(argument_type);
+                }
+            }
+        }
+    }
+
+    fn argument(&mut self, argument: &'a Argument<'src>) {
+        match argument {
+            Argument::ReturnValue => self.bytes.push(0),
+            Argument::Register(register_id) => {
+                self.bytes.push(1);
+                self.number(*register_id as u64);
+            }
+            Argument::Argument(argument_id) => {
+                self.bytes.push(2);
+                self.number(*argument_id as u64);
+            }
+            Argument::Deref(data) => {
+                self.bytes.push(3);
+                self.argument(data);
+            }
+            Argument::StructField { data, name } => {
+                self.bytes.push(4);
+                self.argument(data);
+                self.string(name);
+            }
+            Argument::Constant { value, data_type } => {
+                self.bytes.push(5);
+                self.number(*value);
+                self.data_type(data_type);
+            }
+            Argument::Symbol { name, data_type } => {
+                self.bytes.push(6);
+                self.string(name);
+                self.data_type(data_type);
+            }
+            Argument::VoidRegister => self.bytes.push(7),
+        }
+    }
+
+    // The tag, then whatever isn't an argument, then the arguments in order
+    fn opcode(&mut self, opcode: &'a OpCode<'src>) {
+        self.bytes.push(opcode_tag(opcode));
+
+        match opcode {
+            OpCode::SetField { offset, .. } => self.number(*offset as u64),
+            OpCode::Label { label_id }
+            | OpCode::Goto { label_id }
+            | OpCode::GotoIfZero { label_id, .. }
+            | OpCode::GotoIfNotZero { label_id, .. } => self.number(*label_id as u64),
+            OpCode::Call { arguments, .. } => self.number(arguments.len() as u64),
+            OpCode::SourceLine { line, text } => {
+                self.number(*line as u64);
+                self.string(text);
+            }
+            _ => {}
+        }
+
+        for argument in opcode.arguments() {
+            self.argument(argument);
+        }
+    }
+
+    // The sizes aren't stored, they follow from the types
+    fn function(&mut self, function: &'a Function<'src>) {
+        self.string(&function.name);
+        self.bytes.push(match function.inline {
+            InlineHint::Auto => 0,
+            InlineHint::Always => 1,
+            InlineHint::Never => 2,
+        });
+        self.bytes.push(function.cold as u8);
+        self.number(function.labels as u64);
+        self.data_type(&function.return_type);
+        self.number(function.argument_types.len() as u64);
+
+        for argument_type in &function.argument_types {
+            self.data_type(argument_type);
+        }
+
+        self.number(function.register_types.len() as u64);
+
+        for (register_id, register_type) in function.register_types.iter().enumerate() {
+            self.data_type(register_type);
+            self.number(function.register_alignment(register_id) as u64);
+        }
+
+        self.number(function.opcodes.len() as u64);
+
+        for opcode in &function.opcodes {
+            self.opcode(opcode);
+        }
+    }
+}
+
+struct Reader<'src> {
+    bytes: &'src [u8],
+    position: usize,
+    // In the order they were first written
+    structs: Vec<Rc<StructType<'src>>>,
+    enums: Vec<Rc<EnumType<'src>>>,
+}
+
+impl<'src> Reader<'src> {
+    fn invalid(&self, kind: &'static str, value: impl Into<u64>) -> DeserializeError {
+        DeserializeError::Invalid {
+            kind,
+            value: value.into(),
+        }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'src [u8], DeserializeError> {
+        let bytes = self
+            .bytes
+            .get(self.position..)
+            .and_then(|rest| rest.get(..length))
+            .ok_or(DeserializeError::UnexpectedEnd)?;
+
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, DeserializeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn number(&mut self) -> Result<u64, DeserializeError> {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+
+            value |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(self.invalid("number", value))
+    }
+
+    fn index(&mut self) -> Result<usize, DeserializeError> {
+        let value = self.number()?;
+
+        usize::try_from(value).map_err(|_| self.invalid("index", value))
+    }
+
+    fn slice(&mut self) -> Result<&'src [u8], DeserializeError> {
+        let length = self.index()?;
+
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<&'src str, DeserializeError> {
+        str::from_utf8(self.slice()?).map_err(|_| DeserializeError::InvalidString)
+    }
+
+    fn int_type(&mut self) -> Result<IntType, DeserializeError> {
+        Ok(match self.byte()? {
+            0 => IntType::S8,
+            1 => IntType::S16,
+            2 => IntType::S32,
+            3 => IntType::S64,
+            4 => IntType::U8,
+            5 => IntType::U16,
+            6 => IntType::U32,
+            7 => IntType::U64,
+            tag => return Err(self.invalid("integer type", tag)),
+        })
+    }
+
+    fn data_type(&mut self) -> Result<DataType<'src>, DeserializeError> {
+        Ok(match self.byte()? {
+            0 => DataType::Void,
+            1 => DataType::Bool,
+            2 => DataType::Char,
+            3 => DataType::Inferred(match self.byte()? {
+                0 => InferredType::Int,
+                1 => InferredType::Float,
+                2 => InferredType::Ref,
+                3 => InferredType::Any,
+                tag => return Err(self.invalid("inferred type", tag)),
+            }),
+            4 => DataType::Int(self.int_type()?),
+            5 => DataType::Float(match self.byte()? {
+                0 => FloatType::F32,
+                1 => FloatType::F64,
+                tag => return Err(self.invalid("float type", tag)),
+            }),
+            6 => DataType::Ref(Box::new(self.data_type()?)),
+            7 => {
+                let index = self.index()?;
+
+                if let Some(struct_type) = self.structs.get(index) {
+                    return Ok(DataType::Struct(Rc::clone(struct_type)));
+                }
+
+                if index != self.structs.len() {
+                    return Err(self.invalid("structure", index as u64));
+                }
+
+                let struct_type = StructType::new(self.string()?);
+
+                self.structs.push(Rc::clone(&struct_type));
+
+                let mut fields = Vec::new();
+
+                for _ in 0..self.number()? {
+                    fields.push((self.string()?, self.data_type()?));
+                }
+
+                struct_type.define(fields);
+
+                DataType::Struct(struct_type)
+            }
+            8 => {
+                let index = self.index()?;
+
+                if let Some(enum_type) = self.enums.get(index) {
+                    return Ok(DataType::Enum(Rc::clone(enum_type)));
+                }
+
+                if index != self.enums.len() {
+                    return Err(self.invalid("enumeration", index as u64));
+                }
+
+                let name = self.string()?;
+                let mut variants = Vec::new();
+
+                for _ in 0..self.number()? {
+                    variants.push(self.string()?);
+                }
+
+                let enum_type = EnumType::new(name, variants);
+
+                self.enums.push(Rc::clone(&enum_type));
+
+                DataType::Enum(enum_type)
+            }
+            9 => DataType::Array {
+                element: Box::new(self.data_type()?),
+                length: self.index()?,
+            },
+            10 => {
+                let return_type = Box::new(self.data_type()?);
+                let mut argument_types = Vec::new();
+
+                for _ in 0..self.number()? {
+                    argument_types.push(self.data_type()?);
+                }
+
+                DataType::Function {
+                    return_type,
+                    argument_types,
+                }
+            }
+            tag => return Err(self.invalid("type", tag)),
+        })
+    }
+
+    fn argument(&mut self) -> Result<Argument<'src>, DeserializeError> {
+        Ok(match self.byte()? {
+            0 => Argument::ReturnValue,
+            1 => Argument::Register(self.index()?),
+            2 => Argument::Argument(self.index()?),
+            3 => Argument::Deref(Box::new(self.argument()?)),
+            4 => Argument::StructField {
+                data: Box::new(self.argument()?),
+                name: self.string()?,
+            },
+            5 => Argument::Constant {
+                value: self.number()?,
+                data_type: self.data_type()?,
+            },
+            6 => Argument::Symbol {
+                name: self.string()?.to_string(),
+                data_type: self.data_type()?,
+            },
+            7 => Argument::VoidRegister,
+            tag => return Err(self.invalid("argument", tag)),
+        })
+    }
+
+    // Fields are read in the order they are written in, which is the order of `arguments`
+    fn opcode(&mut self) -> Result<OpCode<'src>, DeserializeError> {
+        Ok(match self.byte()? {
+            0 => OpCode::Mov {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            1 => OpCode::Add {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            2 => OpCode::Sub {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            3 => OpCode::Mul {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            4 => OpCode::Div {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            5 => OpCode::Mod {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            6 => OpCode::And {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            7 => OpCode::Or {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            8 => OpCode::Xor {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            9 => OpCode::Not {
+                dst: self.argument()?,
+            },
+            10 => OpCode::BitNot {
+                dst: self.argument()?,
+            },
+            11 => OpCode::Ref {
+                dst: self.argument()?,
+                src: self.argument()?,
+            },
+            12 => OpCode::Index {
+                dst: self.argument()?,
+                src: self.argument()?,
+                index: self.argument()?,
+            },
+            13 => OpCode::SetIndex {
+                dst: self.argument()?,
+                src: self.argument()?,
+                index: self.argument()?,
+            },
+            14 => {
+                let offset = self.index()?;
+
+                OpCode::SetField {
+                    dst: self.argument()?,
+                    src: self.argument()?,
+                    offset,
+                }
+            }
+            15 => OpCode::Copy {
+                dst: self.argument()?,
+                src: self.argument()?,
+                size: self.argument()?,
+            },
+            16 => OpCode::SetIfEqual {
+                dst: self.argument()?,
+                lhs: self.argument()?,
+                rhs: self.argument()?,
+            },
+            17 => OpCode::SetIfNotEqual {
+                dst: self.argument()?,
+                lhs: self.argument()?,
+                rhs: self.argument()?,
+            },
+            18 => OpCode::SetIfGreater {
+                dst: self.argument()?,
+                lhs: self.argument()?,
+                rhs: self.argument()?,
+            },
+            19 => OpCode::SetIfLess {
+                dst: self.argument()?,
+                lhs: self.argument()?,
+                rhs: self.argument()?,
+            },
+            20 => OpCode::SetIfGreaterOrEqual {
+                dst: self.argument()?,
+                lhs: self.argument()?,
+                rhs: self.argument()?,
+            },
+            21 => OpCode::SetIfLessOrEqual {
+                dst: self.argument()?,
+                lhs: self.argument()?,
+                rhs: self.argument()?,
+            },
+            22 => OpCode::Negate {
+                dst: self.argument()?,
+            },
+            23 => OpCode::Label {
+                label_id: self.index()?,
+            },
+            24 => OpCode::Goto {
+                label_id: self.index()?,
+            },
+            25 => {
+                let label_id = self.index()?;
+
+                OpCode::GotoIfZero {
+                    condition: self.argument()?,
+                    label_id,
+                }
+            }
+            26 => {
+                let label_id = self.index()?;
+
+                OpCode::GotoIfNotZero {
+                    condition: self.argument()?,
+                    label_id,
+                }
+            }
+            27 => {
+                let count = self.number()?;
+                let dst = self.argument()?;
+                let lhs = self.argument()?;
+                let mut arguments = Vec::new();
+
+                for _ in 0..count {
+                    arguments.push(self.argument()?);
+                }
+
+                OpCode::Call {
+                    dst,
+                    lhs,
+                    arguments,
+                }
+            }
+            28 => OpCode::Return,
+            29 => OpCode::Breakpoint,
+            30 => OpCode::Trap,
+            31 => OpCode::ReadCycleCounter {
+                dst: self.argument()?,
+            },
+            32 => OpCode::SourceLine {
+                line: self.index()?,
+                text: self.string()?,
+            },
+            tag => return Err(self.invalid("opcode", tag)),
+        })
+    }
+
+    fn function(&mut self) -> Result<Function<'src>, DeserializeError> {
+        let name = self.string()?.to_string();
+
+        let inline = match self.byte()? {
+            0 => InlineHint::Auto,
+            1 => InlineHint::Always,
+            2 => InlineHint::Never,
+            tag => return Err(self.invalid("inline hint", tag)),
+        };
+
+        let cold = match self.byte()? {
+            0 => false,
+            1 => true,
+            tag => return Err(self.invalid("cold flag", tag)),
+        };
+
+        let labels = self.index()?;
+        let return_type = self.data_type()?;
+        let mut argument_types = Vec::new();
+
+        for _ in 0..self.number()? {
+            argument_types.push(self.data_type()?);
+        }
+
+        let mut function = Function::new(name, return_type, argument_types);
+
+        function.inline = inline;
+        function.cold = cold;
+        function.labels = labels;
+
+        for _ in 0..self.number()? {
+            let register_type = self.data_type()?;
+            let alignment = self.number()?;
+
+            if alignment < 8 || !alignment.is_power_of_two() {
+                return Err(self.invalid("register alignment", alignment));
+            }
+
+            function.add_aligned_register(register_type, alignment as usize);
+        }
+
+        for _ in 0..self.number()? {
+            let opcode = self.opcode()?;
+
+            function.add_opcode(opcode);
+        }
+
+        // The backends look registers, arguments and labels up by their IDs, and trust the types
+        // of operands
+        for opcode in &function.opcodes {
+            for argument in opcode.arguments() {
+                self.check_argument(&function, argument)?;
+            }
+
+            Self::check_operands(&function, opcode)?;
+
+            let label_id = match *opcode {
+                OpCode::Label { label_id } => Some(label_id),
+                _ => opcode.jump_target(),
+            };
+
+            if let Some(label_id) = label_id.filter(|&label_id| label_id >= function.labels) {
+                return Err(self.invalid("label", label_id as u64));
+            }
+        }
+
+        Ok(function)
+    }
+
+    fn check_argument(
+        &self,
+        function: &Function<'src>,
+        argument: &Argument<'src>,
+    ) -> Result<(), DeserializeError> {
+        match *argument {
+            Argument::Register(register_id) if register_id >= function.register_types.len() => {
+                Err(self.invalid("register", register_id as u64))
+            }
+            Argument::Argument(n) if n >= function.argument_types.len() => {
+                Err(self.invalid("argument", n as u64))
+            }
+            Argument::Deref(ref deref) => {
+                self.check_argument(function, deref)?;
+
+                match Self::operand_type(function, deref) {
+                    Some(DataType::Ref(_)) => Ok(()),
+                    _ => Err(DeserializeError::WrongType {
+                        operand: "dereferenced operand",
+                    }),
+                }
+            }
+            Argument::StructField { ref data, name } => {
+                self.check_argument(function, data)?;
+
+                let Some(DataType::Struct(struct_type)) = Self::operand_type(function, data) else {
+                    return Err(DeserializeError::WrongType {
+                        operand: "operand with a field",
+                    });
+                };
+
+                match struct_type.field(name) {
+                    Some(_) => Ok(()),
+                    None => Err(DeserializeError::UnknownField {
+                        name: name.to_string(),
+                    }),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Comparisons write a `Bool`, and calls need a function taking as many arguments as are given
+    fn check_operands(
+        function: &Function<'src>,
+        opcode: &OpCode<'src>,
+    ) -> Result<(), DeserializeError> {
+        match opcode {
+            OpCode::SetIfEqual { dst, .. }
+            | OpCode::SetIfNotEqual { dst, .. }
+            | OpCode::SetIfGreater { dst, .. }
+            | OpCode::SetIfLess { dst, .. }
+            | OpCode::SetIfGreaterOrEqual { dst, .. }
+            | OpCode::SetIfLessOrEqual { dst, .. }
+                if Self::operand_type(function, dst) != Some(&DataType::Bool) =>
+            {
+                Err(DeserializeError::WrongType {
+                    operand: "comparison result",
+                })
+            }
+            OpCode::Call { lhs, arguments, .. } => match Self::operand_type(function, lhs) {
+                Some(DataType::Function { argument_types, .. })
+                    if argument_types.len() == arguments.len() =>
+                {
+                    Ok(())
+                }
+                _ => Err(DeserializeError::WrongType {
+                    operand: "called operand",
+                }),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // Only for arguments that were checked already, the void register has no type
+    fn operand_type<'a>(
+        function: &'a Function<'src>,
+        argument: &'a Argument<'src>,
+    ) -> Option<&'a DataType<'src>> {
+        match argument {
+            Argument::VoidRegister => None,
+            _ => Some(function.argument_data_type(argument)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler::Compiler, parser::Parser, symbol_table::SymbolTable};
+
+    const SOURCE: &str = "{
+        struct Point {
+            let x: U64;
+            let y: U64;
+        };
+
+        fn sum(let point: #Point): U64 {
+            (@point).x + (@point).y;
+        };
+
+        let point: Point;
+        point.x = 1;
+        point.y = 2;
+
+        let total: U64 = sum(#point);
+
+        println(\"{total}\");
+
+        total;
+    }";
+
+    #[test]
+    fn round_trip() {
+        let mut symbol_table = SymbolTable::new();
+        let ast = Parser::parse(SOURCE, &mut symbol_table).unwrap();
+        let bytecode = Compiler::compile(&ast, symbol_table);
+
+        let bytes = serialize(&bytecode);
+        let read = deserialize(&bytes).unwrap();
+
+        assert_eq!(format!("{read:?}"), format!("{bytecode:?}"));
+        assert_eq!(serialize(&read), bytes);
+    }
+
+    #[test]
+    fn missing_registers_are_rejected() {
+        let mut bytecode = ByteCode::new();
+        let mut function = Function::new("@main".to_string(), DataType::Void, Vec::new());
+
+        function.add_opcode(OpCode::Negate {
+            dst: Argument::Register(0),
+        });
+
+        bytecode.add_function(function);
+
+        let error = deserialize(&serialize(&bytecode)).unwrap_err();
+
+        assert_eq!(error.to_string(), "invalid register 0 in bytecode");
+    }
+
+    fn rejection<'src>(opcode: OpCode<'src>, register_types: Vec<DataType<'src>>) -> String {
+        let mut bytecode = ByteCode::new();
+        let mut function = Function::new("@main".to_string(), DataType::Void, Vec::new());
+
+        for register_type in register_types {
+            function.add_register(register_type);
+        }
+
+        function.add_opcode(opcode);
+        bytecode.add_function(function);
+
+        deserialize(&serialize(&bytecode)).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unknown_symbols_are_rejected() {
+        let error = rejection(
+            OpCode::Mov {
+                dst: Argument::Register(0),
+                src: Argument::Symbol {
+                    name: "str_0".to_string(),
+                    data_type: DataType::Ref(Box::new(DataType::Int(IntType::U8))),
+                },
+            },
+            vec![DataType::Ref(Box::new(DataType::Int(IntType::U8)))],
+        );
+
+        assert_eq!(error, "unknown symbol `str_0` in bytecode");
+
+        let error = rejection(
+            OpCode::Mov {
+                dst: Argument::Register(0),
+                src: Argument::Symbol {
+                    name: "@main".to_string(),
+                    data_type: DataType::Int(IntType::U64),
+                },
+            },
+            vec![DataType::Int(IntType::U64)],
+        );
+
+        assert_eq!(error, "function symbol in bytecode has the wrong type");
+    }
+
+    #[test]
+    fn operands_of_the_wrong_type_are_rejected() {
+        let error = rejection(
+            OpCode::Negate {
+                dst: Argument::Deref(Box::new(Argument::Register(0))),
+            },
+            vec![DataType::Int(IntType::U64)],
+        );
+
+        assert_eq!(error, "dereferenced operand in bytecode has the wrong type");
+
+        let error = rejection(
+            OpCode::SetIfEqual {
+                dst: Argument::Register(0),
+                lhs: Argument::Register(0),
+                rhs: Argument::Register(0),
+            },
+            vec![DataType::Int(IntType::U64)],
+        );
+
+        assert_eq!(error, "comparison result in bytecode has the wrong type");
+
+        let error = rejection(
+            OpCode::Call {
+                dst: Argument::VoidRegister,
+                lhs: Argument::Symbol {
+                    name: "@main".to_string(),
+                    data_type: DataType::Function {
+                        return_type: Box::new(DataType::Void),
+                        argument_types: Vec::new(),
+                    },
+                },
+                arguments: vec![Argument::Register(0)],
+            },
+            vec![DataType::Int(IntType::U64)],
+        );
+
+        assert_eq!(error, "called operand in bytecode has the wrong type");
+    }
+}
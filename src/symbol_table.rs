@@ -1,17 +1,29 @@
-use crate::types::{DataType, IntType};
-use std::collections::HashMap;
-
-#[derive(Hash, PartialEq, Eq)]
-pub struct SymbolID<'src> {
-    name: &'src str,
+use crate::{
+    builtins::{Builtin, Builtins, Extern},
+    parser::{GenericFunction, TokenKind},
+    types::DataType,
+};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+pub struct SymbolID {
+    name: NameID,
     scope_id: ScopeID,
 }
 
 pub type ScopeID = usize;
 
+// Names are interned, so looking through the scopes compares integers instead of hashing text
+pub type NameID = usize;
+
 pub enum Symbol<'src> {
     Variable(DataType<'src>),
     Struct(DataType<'src>),
+    Enum(DataType<'src>),
+    // Folded when it's declared, every use is replaced with this literal
+    Constant(TokenKind<'src>),
+    TypeParameter(DataType<'src>),
+    Generic(Box<GenericFunction<'src>>),
 }
 
 // Symbol Table:
@@ -20,11 +32,16 @@ pub enum Symbol<'src> {
 // To achieve this, every scope has a mapping to the previous scope, going back to the first scope, which maps recursively to itself.
 // If the table cannot find your symbol in the first scope, the global scope,
 // then it cannot find your symbol in any scope, and throws a runtime error.
-// Every symbol in the hashmap contains both its name, and the ID number of its scope.
+// Every symbol in the hashmap contains both its interned name, and the ID number of its scope.
+// Every name also has the set of scopes it's defined in, so each scope is checked in constant time.
 pub struct SymbolTable<'src> {
     pub scope_id: ScopeID,
-    symbols: HashMap<SymbolID<'src>, Symbol<'src>>,
+    names: HashMap<&'src str, NameID>,
+    definitions: Vec<HashSet<ScopeID>>,
+    symbols: HashMap<SymbolID, Symbol<'src>>,
     pub scopes: Vec<ScopeID>,
+    pub builtins: Vec<Builtin<'src>>,
+    pub externs: Vec<Extern<'src>>,
 }
 
 impl<'src> SymbolTable<'src> {
@@ -32,7 +49,40 @@ impl<'src> SymbolTable<'src> {
         Self::default()
     }
 
+    pub fn with_builtins(builtins: Builtins<'src>) -> Self {
+        let mut symbol_table = Self {
+            scope_id: 0,
+            names: HashMap::new(),
+            definitions: Vec::new(),
+            symbols: HashMap::new(),
+            scopes: vec![0],
+            builtins: Vec::new(),
+            externs: Vec::new(),
+        };
+
+        for builtin in &builtins.functions {
+            symbol_table.add_symbol(builtin.name, Symbol::Variable(builtin.data_type.clone()));
+        }
+
+        symbol_table.builtins = builtins.functions;
+
+        symbol_table
+    }
+
+    fn intern(&mut self, name: &'src str) -> NameID {
+        let next_id = self.names.len();
+
+        *self.names.entry(name).or_insert_with(|| {
+            self.definitions.push(HashSet::new());
+            next_id
+        })
+    }
+
     pub fn add_symbol(&mut self, name: &'src str, symbol: Symbol<'src>) {
+        let name = self.intern(name);
+
+        self.definitions[name].insert(self.scope_id);
+
         self.symbols.insert(
             SymbolID {
                 name,
@@ -42,31 +92,57 @@ impl<'src> SymbolTable<'src> {
         );
     }
 
-    pub fn get_symbol(&self, name: &'src str) -> Option<&Symbol<'src>> {
-        let mut scope_id = self.scope_id;
+    // Declaring the same function in another scope still links to just one symbol
+    pub fn add_extern(&mut self, extern_function: Extern<'src>) {
+        if !self
+            .externs
+            .iter()
+            .any(|declared| declared.name == extern_function.name)
+        {
+            self.externs.push(extern_function);
+        }
+    }
 
-        loop {
-            if let Some(symbol) = self.symbols.get(&SymbolID { name, scope_id }) {
-                return Some(symbol);
-            }
+    // Only the current scope, declaring it again in a nested one shadows it instead
+    pub fn is_declared_in_scope(&self, name: &'src str) -> bool {
+        self.names
+            .get(name)
+            .is_some_and(|name| self.definitions[*name].contains(&self.scope_id))
+    }
 
-            if scope_id == 0 {
-                break;
-            }
+    // What the runtime provides under that name, even if the program shadows it
+    pub fn get_builtin(&self, name: &str) -> Option<&Builtin<'src>> {
+        self.builtins.iter().find(|builtin| builtin.name == name)
+    }
 
-            scope_id = self.scopes[scope_id];
-        }
+    // Builtins are the only symbols of the outermost scope, the program itself starts a new one
+    pub fn is_builtin(&self, name: &'src str) -> bool {
+        self.get_symbol_id(name)
+            .is_some_and(|symbol_id| symbol_id.scope_id == 0)
+    }
 
-        None
+    pub fn get_symbol(&self, name: &'src str) -> Option<&Symbol<'src>> {
+        let symbol_id = self.get_symbol_id(name)?;
+
+        self.symbols.get(&symbol_id)
     }
 
-    pub fn get_symbol_id(&self, name: &'src str) -> Option<SymbolID<'src>> {
+    pub fn get_symbol_mut(&mut self, name: &'src str) -> Option<&mut Symbol<'src>> {
+        let symbol_id = self.get_symbol_id(name)?;
+
+        self.symbols.get_mut(&symbol_id)
+    }
+
+    pub fn get_symbol_id(&self, name: &'src str) -> Option<SymbolID> {
+        // A name that was never interned can't have been defined anywhere
+        let name = *self.names.get(name)?;
+        let definitions = &self.definitions[name];
+
         let mut scope_id = self.scope_id;
 
         loop {
-            let symbol_id = SymbolID { name, scope_id };
-            if self.symbols.get(&symbol_id).is_some() {
-                return Some(symbol_id);
+            if definitions.contains(&scope_id) {
+                return Some(SymbolID { name, scope_id });
             }
 
             if scope_id == 0 {
@@ -104,53 +180,50 @@ impl<'src> SymbolTable<'src> {
 
 impl<'src> Default for SymbolTable<'src> {
     fn default() -> Self {
-        let mut symbol_table = Self {
-            scope_id: 0,
-            symbols: HashMap::new(),
-            scopes: vec![0],
-        };
+        Self::with_builtins(Builtins::hosted())
+    }
+}
 
-        symbol_table.add_symbol(
-            "read",
-            Symbol::Variable(DataType::Function {
-                return_type: Box::new(DataType::Void),
-                argument_types: vec![
-                    DataType::Ref(Box::new(DataType::Int(IntType::U8))),
-                    DataType::Int(IntType::U64),
-                ],
-            }),
-        );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::IntType;
 
-        symbol_table.add_symbol(
-            "print",
-            Symbol::Variable(DataType::Function {
-                return_type: Box::new(DataType::Void),
-                argument_types: vec![
-                    DataType::Ref(Box::new(DataType::Int(IntType::U8))),
-                    DataType::Int(IntType::U64),
-                ],
-            }),
-        );
+    #[test]
+    fn the_innermost_definition_is_found() {
+        let mut symbol_table = SymbolTable::new();
 
-        symbol_table.add_symbol(
-            "malloc",
-            Symbol::Variable(DataType::Function {
-                return_type: Box::new(DataType::Ref(Box::new(DataType::Int(IntType::U8)))),
-                argument_types: vec![DataType::Int(IntType::U64)],
-            }),
-        );
+        symbol_table.add_symbol("x", Symbol::Variable(DataType::Bool));
 
-        symbol_table.add_symbol(
-            "free",
-            Symbol::Variable(DataType::Function {
-                return_type: Box::new(DataType::Void),
-                argument_types: vec![
-                    DataType::Ref(Box::new(DataType::Int(IntType::U8))),
-                    DataType::Int(IntType::U64),
-                ],
-            }),
-        );
+        let outer_scope_id = symbol_table.add_scope();
 
-        symbol_table
+        symbol_table.add_symbol("x", Symbol::Variable(DataType::Int(IntType::U64)));
+
+        let inner_scope_id = symbol_table.add_scope();
+
+        assert!(matches!(
+            symbol_table.get_symbol("x"),
+            Some(Symbol::Variable(DataType::Int(IntType::U64)))
+        ));
+        assert!(!symbol_table.is_declared_in_scope("x"));
+
+        symbol_table.leave_scope();
+        symbol_table.leave_scope();
+
+        assert!(matches!(
+            symbol_table.get_symbol("x"),
+            Some(Symbol::Variable(DataType::Bool))
+        ));
+
+        // A sibling scope doesn't see what the other one declared
+        symbol_table.add_scope();
+        symbol_table.add_symbol("y", Symbol::Variable(DataType::Bool));
+        symbol_table.enter_scope(inner_scope_id);
+
+        assert!(symbol_table.get_symbol("y").is_none());
+
+        symbol_table.enter_scope(outer_scope_id);
+
+        assert!(symbol_table.is_declared_in_scope("x"));
     }
 }
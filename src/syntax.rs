@@ -0,0 +1,360 @@
+use crate::builtins::Builtin;
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum Base {
+    Register(&'static str),
+    Symbol(String),
+}
+
+// `base + index + displacement`
+#[derive(PartialEq, Eq, Clone)]
+pub struct Address {
+    pub base: Base,
+    pub index: Option<&'static str>,
+    pub displacement: i64,
+}
+
+impl Address {
+    pub fn register(register: &'static str) -> Self {
+        Self {
+            base: Base::Register(register),
+            index: None,
+            displacement: 0,
+        }
+    }
+
+    pub fn symbol(name: String) -> Self {
+        Self {
+            base: Base::Symbol(name),
+            index: None,
+            displacement: 0,
+        }
+    }
+
+    pub fn index(self, index: &'static str) -> Self {
+        Self {
+            index: Some(index),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            displacement: self.displacement + offset,
+            ..self
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum Operand {
+    Register(&'static str),
+    Constant(u64),
+    // The address of a symbol, either as a value or as the target of a jump
+    Symbol(String),
+    // The size is only needed when no register operand gives it away
+    Memory {
+        size: Option<usize>,
+        address: Address,
+    },
+}
+
+impl Operand {
+    pub fn memory(address: Address) -> Self {
+        Self::Memory {
+            size: None,
+            address,
+        }
+    }
+}
+
+// How the instructions chosen by the backend are written down.
+// Operands are always given destination first, like Intel syntax does
+pub trait Syntax {
+    // Everything before the builtins, up to the start of the text section
+    fn prologue() -> &'static str;
+
+    // Calls `@main` and exits with its result
+    fn entry() -> &'static str;
+
+    fn builtin(builtin: &Builtin) -> &'static str;
+
+    // Declares a symbol that is defined by something else the program is linked with
+    fn external(name: &str) -> String;
+
+    fn symbol(name: &str) -> String;
+
+    fn label(name: &str) -> String {
+        format!("{}:", Self::symbol(name))
+    }
+
+    // The name of a label that is only visible inside of a function
+    fn local_label(function: &str, name: &str) -> String;
+
+    fn section(name: &str, executable: bool) -> String;
+
+    fn bytes(name: &str, bytes: &[u8]) -> String;
+
+    // Pads up to a multiple of `bytes`, sections that are only reserved can't be filled with zeros
+    fn align(bytes: usize, reserved: bool) -> String;
+
+    // Space that is zeroed when the program starts, without taking up any in the file
+    fn reserve(name: &str, size: usize) -> String;
+
+    fn instruction(mnemonic: &str, operands: &[Operand]) -> String;
+
+    fn comment(text: &str) -> String;
+}
+
+fn join_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| byte.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn is_jump(mnemonic: &str) -> bool {
+    mnemonic == "call" || mnemonic.starts_with('j')
+}
+
+// NASM flavoured Intel syntax
+pub struct Intel;
+
+impl Intel {
+    fn size(size: usize) -> &'static str {
+        match size {
+            1 => "byte",
+            2 => "word",
+            4 => "dword",
+            8 => "qword",
+            _ => unreachable!(),
+        }
+    }
+
+    fn operand(operand: &Operand) -> String {
+        match operand {
+            Operand::Register(register) => register.to_string(),
+            Operand::Constant(value) => value.to_string(),
+            Operand::Symbol(name) => Self::symbol(name),
+            Operand::Memory { size, address } => {
+                let mut text = match address.base {
+                    Base::Register(register) => register.to_string(),
+                    Base::Symbol(ref name) => Self::symbol(name),
+                };
+
+                if let Some(index) = address.index {
+                    text = format!("{text} + {index}");
+                }
+
+                if address.displacement > 0 {
+                    text = format!("{text} + {}", address.displacement);
+                } else if address.displacement < 0 {
+                    text = format!("{text} - {}", -address.displacement);
+                }
+
+                match size {
+                    Some(size) => format!("{} [{text}]", Self::size(*size)),
+                    None => format!("[{text}]"),
+                }
+            }
+        }
+    }
+}
+
+impl Syntax for Intel {
+    fn prologue() -> &'static str {
+        "[BITS 64]\nglobal _start\nsection .text\n"
+    }
+
+    fn entry() -> &'static str {
+        crate::nasm::ENTRY_CODE
+    }
+
+    fn builtin(builtin: &Builtin) -> &'static str {
+        builtin.nasm
+    }
+
+    fn external(name: &str) -> String {
+        format!("extern {name}")
+    }
+
+    fn symbol(name: &str) -> String {
+        name.to_string()
+    }
+
+    // Labels starting with a dot belong to the last label without one
+    fn local_label(_function: &str, name: &str) -> String {
+        format!(".{name}")
+    }
+
+    fn section(name: &str, executable: bool) -> String {
+        if executable {
+            format!("section {name} progbits alloc exec nowrite")
+        } else {
+            format!("section {name}")
+        }
+    }
+
+    fn bytes(name: &str, bytes: &[u8]) -> String {
+        format!("{}: db {}", Self::symbol(name), join_bytes(bytes))
+    }
+
+    fn align(bytes: usize, reserved: bool) -> String {
+        if reserved {
+            format!("alignb {bytes}")
+        } else {
+            format!("align {bytes}, db 0")
+        }
+    }
+
+    fn reserve(name: &str, size: usize) -> String {
+        format!("{}: resb {size}", Self::symbol(name))
+    }
+
+    fn instruction(mnemonic: &str, operands: &[Operand]) -> String {
+        if operands.is_empty() {
+            return mnemonic.to_string();
+        }
+
+        let operands = operands
+            .iter()
+            .map(Self::operand)
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        format!("{mnemonic} {operands}")
+    }
+
+    fn comment(text: &str) -> String {
+        format!("; {text}")
+    }
+}
+
+// GAS flavoured AT&T syntax
+pub struct Att;
+
+impl Att {
+    fn suffix(size: usize) -> &'static str {
+        match size {
+            1 => "b",
+            2 => "w",
+            4 => "l",
+            8 => "q",
+            _ => unreachable!(),
+        }
+    }
+
+    fn operand(operand: &Operand, jump: bool) -> String {
+        match operand {
+            Operand::Register(register) if jump => format!("*%{register}"),
+            Operand::Register(register) => format!("%{register}"),
+            Operand::Constant(value) => format!("${value}"),
+            Operand::Symbol(name) if jump => Self::symbol(name),
+            Operand::Symbol(name) => format!("${}", Self::symbol(name)),
+            Operand::Memory { address, .. } => {
+                let displacement = match address.displacement {
+                    0 => String::new(),
+                    displacement => displacement.to_string(),
+                };
+
+                match address.base {
+                    Base::Register(register) => match address.index {
+                        Some(index) => format!("{displacement}(%{register},%{index})"),
+                        None => format!("{displacement}(%{register})"),
+                    },
+                    Base::Symbol(ref name) if address.displacement > 0 => {
+                        format!("{}+{displacement}", Self::symbol(name))
+                    }
+                    Base::Symbol(ref name) => format!("{}{displacement}", Self::symbol(name)),
+                }
+            }
+        }
+    }
+}
+
+impl Syntax for Att {
+    fn prologue() -> &'static str {
+        ".att_syntax\n.globl _start\n.text\n"
+    }
+
+    fn entry() -> &'static str {
+        crate::nasm::ENTRY_CODE_GAS
+    }
+
+    fn builtin(builtin: &Builtin) -> &'static str {
+        builtin.gas
+    }
+
+    // Undefined symbols are external anyway, but it documents where the name comes from
+    fn external(name: &str) -> String {
+        format!(".extern {}", Self::symbol(name))
+    }
+
+    // Quoted, so names like `@main` or `name.0` are fine
+    fn symbol(name: &str) -> String {
+        format!("\"{name}\"")
+    }
+
+    // Local labels are visible in the whole file, so they are prefixed with the function
+    fn local_label(function: &str, name: &str) -> String {
+        format!(".L{function}.{name}")
+    }
+
+    fn section(name: &str, executable: bool) -> String {
+        if executable {
+            format!(".section {name},\"ax\",@progbits")
+        } else {
+            format!(".section {name}")
+        }
+    }
+
+    fn bytes(name: &str, bytes: &[u8]) -> String {
+        format!("{}: .byte {}", Self::symbol(name), join_bytes(bytes))
+    }
+
+    fn align(bytes: usize, _reserved: bool) -> String {
+        format!(".balign {bytes}")
+    }
+
+    fn reserve(name: &str, size: usize) -> String {
+        format!("{}: .zero {size}", Self::symbol(name))
+    }
+
+    fn instruction(mnemonic: &str, operands: &[Operand]) -> String {
+        if operands.is_empty() {
+            return mnemonic.to_string();
+        }
+
+        // Without a register, the size is taken from the memory operand
+        let suffix = operands
+            .iter()
+            .all(|operand| !matches!(operand, Operand::Register(_)))
+            .then(|| {
+                operands.iter().find_map(|operand| match operand {
+                    Operand::Memory {
+                        size: Some(size), ..
+                    } => Some(Self::suffix(*size)),
+                    _ => None,
+                })
+            })
+            .flatten()
+            .unwrap_or_default();
+
+        // `enter` is the one instruction GAS doesn't reverse
+        let mut operands = operands
+            .iter()
+            .map(|operand| Self::operand(operand, is_jump(mnemonic)))
+            .collect::<Vec<String>>();
+
+        if mnemonic != "enter" {
+            operands.reverse();
+        }
+
+        format!("{mnemonic}{suffix} {}", operands.join(", "))
+    }
+
+    fn comment(text: &str) -> String {
+        format!("# {text}")
+    }
+}
@@ -1,11 +1,11 @@
 use crate::{
-    ast::{Ast, AstKind, VariableDeclaration},
+    ast::{Ast, AstKind, InlineHint, Intrinsic, VariableDeclaration},
     div_round_up,
-    parser::TokenKind,
+    parser::{Token, TokenKind, MAX_INSTANCE_DEPTH},
     symbol_table::{Symbol, SymbolTable},
     CompilerResult,
 };
-use std::{cmp::Eq, error::Error, fmt};
+use std::{cell::OnceCell, cmp::Eq, collections::HashSet, error::Error, fmt, ptr, rc::Rc};
 
 pub enum TypeError<'src> {
     TypeMismatch {
@@ -19,17 +19,61 @@ pub enum TypeError<'src> {
     NotANumber,
     NotSigned,
     NotAssignable,
+    NotConstant,
     NotAFunction,
     NotAReference,
     NotAStruct,
+    NotAPowerOfTwo,
+    NotAnArray,
+    UnderAligned {
+        alignment: usize,
+    },
+    InfiniteSize {
+        name: &'src str,
+    },
+    RecursiveInline {
+        name: &'src str,
+    },
+    UnusedTypeParameter {
+        name: &'src str,
+    },
+    TooManyInstances {
+        name: &'src str,
+    },
     WrongNumberOfArguments,
     NotDefined {
         name: &'src str,
     },
+    AlreadyDeclared {
+        name: &'src str,
+    },
     FieldNotDefined {
         name: &'src str,
+        structure: &'src str,
+    },
+    VariantNotDefined {
+        name: &'src str,
+        enumeration: &'src str,
+    },
+    CannotInterpolate {
+        found: DataType<'src>,
+    },
+    UnknownLength {
+        name: &'src str,
     },
+    CannotPrint {
+        found: DataType<'src>,
+    },
+    NumberOutOfRange {
+        value: i128,
+        int_type: IntType,
+    },
+    VoidValue,
     CannotInfer,
+    UnreachableArm,
+    DivisionByZero {
+        oper: Token<'src>,
+    },
 }
 
 impl Error for TypeError<'_> {}
@@ -46,28 +90,91 @@ impl fmt::Debug for TypeError<'_> {
             Self::NotANumber => write!(f, "this is not a number, so you can't do that with it :/"),
             Self::NotSigned => write!(f, "this is not a signed number"),
             Self::NotAssignable => write!(f, "you can't assign to this expression"),
+            Self::NotConstant => {
+                write!(
+                    f,
+                    "this has to be an integer, character or boolean known at compile time"
+                )
+            }
             Self::NotAFunction => write!(f, "this expression isn't a function"),
             Self::NotAReference => write!(f, "this expression isn't a reference"),
             Self::NotAStruct => write!(f, "this expression isn't a struct"),
+            Self::NotAPowerOfTwo => write!(f, "this number isn't a power of two"),
+            Self::NotAnArray => write!(f, "this expression isn't an array"),
+            Self::UnderAligned { alignment } => write!(
+                f,
+                "an alignment of {alignment} is less than the 8 bytes variables are already aligned to"
+            ),
+            Self::UnusedTypeParameter { name } => {
+                write!(f, "type parameter `{name}` isn't used by any argument")
+            }
+            Self::TooManyInstances { name } => write!(
+                f,
+                "generic function `{name}` needs more than {MAX_INSTANCE_DEPTH} instances inside of each other"
+            ),
+            Self::RecursiveInline { name } => {
+                write!(f, "function `{name}` calls itself, so it can't always be inlined")
+            }
+            Self::InfiniteSize { name } => {
+                write!(f, "structure `{name}` contains itself, put it behind a reference")
+            }
             Self::WrongNumberOfArguments => {
                 write!(f, "wrong number of arguments passed into function")
             }
             Self::NotDefined { name } => write!(f, "variable `{name}` was not defined"),
-            Self::FieldNotDefined { name } => write!(f, "structure has no defined field `{name}`"),
+            Self::AlreadyDeclared { name } => {
+                write!(f, "`{name}` is already declared in this scope")
+            }
+            Self::FieldNotDefined { name, structure } => {
+                write!(f, "structure `{structure}` has no defined field `{name}`")
+            }
+            Self::VariantNotDefined { name, enumeration } => {
+                write!(f, "enumeration `{enumeration}` has no variant `{name}`")
+            }
+            Self::CannotInterpolate { found } => {
+                write!(f, "`{found:?}` can't be formatted inside of a string")
+            }
+            Self::UnknownLength { name } => write!(
+                f,
+                "`{name}` could be any length, so it can only be formatted inside of a string that is printed right away"
+            ),
+            Self::CannotPrint { found } => write!(f, "`{found:?}` can't be printed"),
+            Self::NumberOutOfRange { value, int_type } => {
+                write!(f, "number `{value}` doesn't fit in `{int_type:?}`")
+            }
+            Self::VoidValue => write!(f, "cannot use the result of a `Void` expression as a value"),
             Self::CannotInfer => write!(f, "cannot infer type of expression"),
+            Self::UnreachableArm => {
+                write!(f, "this match arm is never taken, an earlier arm has the same value")
+            }
+            Self::DivisionByZero { oper } => write!(
+                f,
+                "`{oper:?}` by zero at {}, which always fails",
+                oper.position
+            ),
         }
     }
 }
 
 impl fmt::Display for TypeError<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{self:?}")
+        write!(f, "{self:?}")?;
+
+        match self {
+            Self::DivisionByZero { oper } => {
+                writeln!(f)?;
+                oper.position.underline(f, oper.text.chars().count())
+            }
+            _ => Ok(()),
+        }
     }
 }
 
 #[derive(PartialEq, Eq, Copy, Clone)]
 pub enum InferredType {
     Int,
+    Float,
+    Ref,
     Any,
 }
 
@@ -75,6 +182,8 @@ impl fmt::Debug for InferredType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Int => write!(f, "{{integer}}"),
+            Self::Float => write!(f, "{{float}}"),
+            Self::Ref => write!(f, "{{reference}}"),
             Self::Any => write!(f, "{{any}}"),
         }
     }
@@ -106,6 +215,59 @@ impl IntType {
     pub fn is_signed(&self) -> bool {
         matches!(self, Self::S8 | Self::S16 | Self::S32 | Self::S64)
     }
+
+    // The type of the same size without a sign
+    pub fn unsigned(&self) -> Self {
+        match self {
+            Self::S8 | Self::U8 => Self::U8,
+            Self::S16 | Self::U16 => Self::U16,
+            Self::S32 | Self::U32 => Self::U32,
+            Self::S64 | Self::U64 => Self::U64,
+        }
+    }
+
+    pub fn max_value(&self) -> u64 {
+        let bits = self.size() as u32 * 8;
+
+        if self.is_signed() {
+            (1 << (bits - 1)) - 1
+        } else {
+            u64::MAX >> (64 - bits)
+        }
+    }
+
+    pub fn contains(&self, value: i128) -> bool {
+        let max_value = i128::from(self.max_value());
+        let min_value = if self.is_signed() { -max_value - 1 } else { 0 };
+
+        (min_value..=max_value).contains(&value)
+    }
+
+    // Cuts a value down to the bits of this type, like the machine would
+    pub fn wrap(&self, value: i128) -> i128 {
+        let bits = self.size() as u32 * 8;
+        let value = value & (i128::from(u64::MAX) >> (64 - bits));
+
+        if self.is_signed() && value > i128::from(self.max_value()) {
+            value - (1 << bits)
+        } else {
+            value
+        }
+    }
+
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "s8" => Some(Self::S8),
+            "s16" => Some(Self::S16),
+            "s32" => Some(Self::S32),
+            "s64" => Some(Self::S64),
+            "u8" => Some(Self::U8),
+            "u16" => Some(Self::U16),
+            "u32" => Some(Self::U32),
+            "u64" => Some(Self::U64),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for IntType {
@@ -124,14 +286,133 @@ impl fmt::Debug for IntType {
     }
 }
 
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum FloatType {
+    F32,
+    F64,
+}
+
+impl FloatType {
+    pub fn size(&self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+impl fmt::Debug for FloatType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::F32 => write!(f, "F32"),
+            Self::F64 => write!(f, "F64"),
+        }
+    }
+}
+
+// Structures are named, and their fields are filled in after the structure is declared,
+// so that a field can refer back to the structure it is in through a reference.
+pub struct StructType<'src> {
+    pub name: &'src str,
+    fields: OnceCell<Vec<(&'src str, DataType<'src>)>>,
+}
+
+impl<'src> StructType<'src> {
+    pub fn new(name: &'src str) -> Rc<Self> {
+        Rc::new(Self {
+            name,
+            fields: OnceCell::new(),
+        })
+    }
+
+    pub fn define(&self, fields: Vec<(&'src str, DataType<'src>)>) {
+        assert!(
+            self.fields.set(fields).is_ok(),
+            "Structure `{}` was defined twice",
+            self.name
+        );
+    }
+
+    pub fn fields(&self) -> &[(&'src str, DataType<'src>)] {
+        self.fields
+            .get()
+            .map_or_else(|| panic!("Structure `{}` was never defined", self.name), Vec::as_slice)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&DataType<'src>> {
+        self.fields()
+            .iter()
+            .find(|(field_name, _)| *field_name == name)
+            .map(|(_, data_type)| data_type)
+    }
+
+    pub fn field_offset(&self, name: &str) -> usize {
+        self.fields()
+            .iter()
+            .take_while(|(field_name, _)| *field_name != name)
+            .map(|(_, data_type)| data_type.size_aligned())
+            .sum()
+    }
+}
+
+impl PartialEq for StructType<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl Eq for StructType<'_> {}
+
+// The variants of an enumeration are numbered in the order they are declared, and stored in the
+// smallest unsigned integer they fit in. Like structures, two enumerations are only equal if they
+// are the same declaration
+pub struct EnumType<'src> {
+    pub name: &'src str,
+    pub variants: Vec<&'src str>,
+}
+
+impl<'src> EnumType<'src> {
+    pub fn new(name: &'src str, variants: Vec<&'src str>) -> Rc<Self> {
+        Rc::new(Self { name, variants })
+    }
+
+    pub fn variant(&self, name: &str) -> Option<usize> {
+        self.variants.iter().position(|variant| *variant == name)
+    }
+
+    pub fn int_type(&self) -> IntType {
+        let last = self.variants.len().saturating_sub(1) as i128;
+
+        [IntType::U8, IntType::U16, IntType::U32, IntType::U64]
+            .into_iter()
+            .find(|int_type| int_type.contains(last))
+            .unwrap_or(IntType::U64)
+    }
+}
+
+impl PartialEq for EnumType<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl Eq for EnumType<'_> {}
+
 #[derive(PartialEq, Eq, Clone)]
 pub enum DataType<'src> {
     Void,
     Bool,
+    Char,
     Inferred(InferredType),
     Int(IntType),
+    Float(FloatType),
     Ref(Box<Self>),
-    Struct(Vec<(&'src str, Self)>),
+    Struct(Rc<StructType<'src>>),
+    Enum(Rc<EnumType<'src>>),
+    Array {
+        element: Box<Self>,
+        length: usize,
+    },
     Function {
         return_type: Box<Self>,
         argument_types: Vec<Self>,
@@ -143,9 +424,31 @@ impl<'src> DataType<'src> {
         symbol_table: &mut SymbolTable<'src>,
         kind: &mut AstKind<'src>,
     ) -> CompilerResult<'src, Self> {
+        // Statements and bodies are the only places where nothing uses the result
+        if kind
+            .values()
+            .iter()
+            .any(|value| value.data_type == Self::Void)
+        {
+            return Err(TypeError::VoidValue.into());
+        }
+
+        // The declaration's error has already been reported, checking what it's used in would only add more
+        if kind.values().into_iter().any(Ast::is_recovered) {
+            return Ok(Self::Inferred(InferredType::Any));
+        }
+
         let data_type = match kind {
             AstKind::Node { ref token } => match token.kind {
-                TokenKind::Number(_) => Self::Inferred(InferredType::Int),
+                TokenKind::Number(value, Some(int_type)) => {
+                    if !int_type.contains(value) {
+                        return Err(TypeError::NumberOutOfRange { value, int_type }.into());
+                    }
+
+                    Self::Int(int_type)
+                }
+                TokenKind::Number(_, None) => Self::Inferred(InferredType::Int),
+                TokenKind::Float(_) => Self::Inferred(InferredType::Float),
                 TokenKind::Ident => {
                     let Some(Symbol::Variable(ref data_type)) = symbol_table.get_symbol(token.text) else {
                         return Err(TypeError::NotDefined { name: token.text }.into());
@@ -155,6 +458,7 @@ impl<'src> DataType<'src> {
                 }
                 TokenKind::Str(_) => Self::Ref(Box::new(Self::Int(IntType::U8))),
                 TokenKind::True | TokenKind::False => Self::Bool,
+                TokenKind::Char(_) => Self::Char,
                 _ => unreachable!(),
             },
             AstKind::Prefix {
@@ -172,7 +476,9 @@ impl<'src> DataType<'src> {
 
                             node_data_type
                         }
-                        Self::Inferred(InferredType::Int) => node_data_type,
+                        Self::Float(_)
+                        | Self::Inferred(InferredType::Int)
+                        | Self::Inferred(InferredType::Float) => node_data_type,
                         _ => return Err(TypeError::NotANumber.into()),
                     },
                     TokenKind::Not => {
@@ -186,6 +492,13 @@ impl<'src> DataType<'src> {
 
                         node_data_type
                     }
+                    TokenKind::BitNot => {
+                        if !node_data_type.is_integer() {
+                            return Err(TypeError::NotANumber.into());
+                        }
+
+                        node_data_type
+                    }
                     TokenKind::Hash => {
                         if !node.kind.assignable() {
                             return Err(TypeError::NotAssignable.into());
@@ -203,11 +516,44 @@ impl<'src> DataType<'src> {
                     _ => unreachable!(),
                 }
             }
+            // `pointer - pointer` is how many elements apart they are
+            AstKind::Infix {
+                oper:
+                    Token {
+                        kind: TokenKind::Sub,
+                        ..
+                    },
+                ref lhs,
+                ref rhs,
+            } if matches!(lhs.data_type, Self::Ref(_)) && lhs.data_type == rhs.data_type => {
+                Self::Int(IntType::S64)
+            }
+            // `pointer + n` and `pointer - n` move by whole elements, like `@offset`
+            AstKind::Infix {
+                oper:
+                    Token {
+                        kind: TokenKind::Add | TokenKind::Sub,
+                        ..
+                    },
+                ref lhs,
+                ref mut rhs,
+            } if matches!(lhs.data_type, Self::Ref(_)) && rhs.data_type.is_integer() => {
+                Self::Int(IntType::U64).infer(rhs)?;
+
+                lhs.data_type.clone()
+            }
             AstKind::Infix {
                 ref oper,
                 ref mut lhs,
                 ref mut rhs,
             } => {
+                // A character literal compared with a byte stands for that byte
+                match (&lhs.data_type, &rhs.data_type) {
+                    (Self::Int(IntType::U8), Self::Char) => lhs.data_type.infer(rhs)?,
+                    (Self::Char, Self::Int(IntType::U8)) => rhs.data_type.infer(lhs)?,
+                    _ => {}
+                }
+
                 rhs.data_type.infer(lhs)?;
                 lhs.data_type.infer(rhs)?;
 
@@ -219,12 +565,43 @@ impl<'src> DataType<'src> {
                     .into());
                 }
 
+                // The result of a comparison doesn't say anything about two literals, so they
+                // get the widest type that also fits negative numbers
+                let is_comparison = matches!(
+                    oper.kind,
+                    TokenKind::Equals
+                        | TokenKind::NotEquals
+                        | TokenKind::Greater
+                        | TokenKind::Less
+                        | TokenKind::GreaterOrEqual
+                        | TokenKind::LessOrEqual
+                );
+
+                let literal_type = match lhs.data_type {
+                    Self::Inferred(InferredType::Int) => Some(Self::Int(IntType::U64)),
+                    Self::Inferred(InferredType::Float) => Some(Self::Float(FloatType::F64)),
+                    _ => None,
+                };
+
+                if let Some(literal_type) = literal_type.filter(|_| is_comparison) {
+                    literal_type.infer(lhs)?;
+                    literal_type.infer(rhs)?;
+                }
+
+                // Known at compile time, so it would only ever fault
+                if matches!(oper.kind, TokenKind::Div | TokenKind::Mod) && rhs.fold() == Some(0) {
+                    return Err(TypeError::DivisionByZero { oper: oper.clone() }.into());
+                }
+
                 match oper.kind {
-                    TokenKind::Add
-                    | TokenKind::Sub
-                    | TokenKind::Mul
-                    | TokenKind::Div
-                    | TokenKind::Mod => {
+                    TokenKind::Add | TokenKind::Sub | TokenKind::Mul | TokenKind::Div => {
+                        if !lhs.data_type.is_number() {
+                            return Err(TypeError::NotANumber.into());
+                        }
+
+                        lhs.data_type.clone()
+                    }
+                    TokenKind::Mod | TokenKind::BitAnd | TokenKind::BitOr | TokenKind::BitXor => {
                         if !lhs.data_type.is_integer() {
                             return Err(TypeError::NotANumber.into());
                         }
@@ -232,11 +609,23 @@ impl<'src> DataType<'src> {
                         lhs.data_type.clone()
                     }
                     TokenKind::Equals | TokenKind::NotEquals => Self::Bool,
+                    TokenKind::And | TokenKind::Or => {
+                        if lhs.data_type != Self::Bool {
+                            return Err(TypeError::ExpectedType {
+                                expected: Self::Bool,
+                                found: lhs.data_type.clone(),
+                            }
+                            .into());
+                        }
+
+                        Self::Bool
+                    }
                     TokenKind::Greater
                     | TokenKind::Less
                     | TokenKind::GreaterOrEqual
                     | TokenKind::LessOrEqual => {
-                        if !lhs.data_type.is_integer() {
+                        // Characters are ordered by their byte, but can't be used as numbers
+                        if !lhs.data_type.is_number() && lhs.data_type != Self::Char {
                             return Err(TypeError::NotANumber.into());
                         };
 
@@ -251,18 +640,83 @@ impl<'src> DataType<'src> {
             } => {
                 Self::Int(IntType::U64).infer(index)?;
 
-                let Self::Ref(ref deref) = lhs.data_type else {
-                    return Err(TypeError::NotAReference.into());
-                };
+                match lhs.data_type {
+                    Self::Ref(ref element) | Self::Array { ref element, .. } => *element.clone(),
+                    _ => return Err(TypeError::NotAReference.into()),
+                }
+            }
+            // Integers are written in decimal, booleans as `true` or `false`, and characters and
+            // strings as they are. The text is put together in a buffer of its own, which
+            // has to fit whatever the values turn into, so strings can only be literals
+            AstKind::Interpolation { ref pieces } => {
+                let string = Self::Ref(Box::new(Self::Int(IntType::U8)));
+
+                for piece in pieces {
+                    if !matches!(piece.data_type, Self::Int(_) | Self::Bool | Self::Char)
+                        && piece.data_type != string
+                    {
+                        return Err(TypeError::CannotInterpolate {
+                            found: piece.data_type.clone(),
+                        }
+                        .into());
+                    }
 
-                *deref.clone()
+                    if let AstKind::Node {
+                        token:
+                            Token {
+                                kind: TokenKind::Ident,
+                                text: name,
+                                ..
+                            },
+                    } = piece.kind
+                    {
+                        if piece.data_type == string {
+                            return Err(TypeError::UnknownLength { name }.into());
+                        }
+                    }
+                }
+
+                string
+            }
+            // The first element that isn't inferred decides the type of the others,
+            // an empty literal only gets its element type from where it is used
+            AstKind::ArrayLiteral { ref mut elements } => {
+                let element = elements
+                    .iter()
+                    .map(|element| &element.data_type)
+                    .find(|data_type| !data_type.is_inferred())
+                    .or(elements.first().map(|element| &element.data_type))
+                    .cloned()
+                    .unwrap_or(Self::Inferred(InferredType::Any));
+
+                for other in elements.iter_mut() {
+                    element.infer(other)?;
+
+                    if other.data_type != element {
+                        return Err(TypeError::TypeMismatch {
+                            first: element,
+                            second: other.data_type.clone(),
+                        }
+                        .into());
+                    }
+                }
+
+                Self::Array {
+                    element: Box::new(element),
+                    length: elements.len(),
+                }
             }
             AstKind::Assign {
                 ref mut lhs,
                 ref mut rhs,
             } => {
-                rhs.data_type.infer(lhs)?;
+                if lhs.is_recovered() {
+                    return Ok(Self::Void);
+                }
+
+                // The place decides the type, so a mismatch reports the value as the wrong one
                 lhs.data_type.infer(rhs)?;
+                rhs.data_type.infer(lhs)?;
 
                 if lhs.data_type != rhs.data_type {
                     return Err(TypeError::TypeMismatch {
@@ -280,18 +734,19 @@ impl<'src> DataType<'src> {
             }
             AstKind::GetField {
                 ref mut lhs,
-                ref name,
+                name,
             } => {
-                let Self::Struct(ref fields) = lhs.data_type else {
+                let Self::Struct(ref struct_type) = lhs.data_type else {
                     return Err(TypeError::NotAStruct.into());
                 };
 
-                let (_, data_type) = fields
-                    .iter()
-                    .find(|(field_name, _)| field_name == name)
-                    .ok_or(TypeError::FieldNotDefined { name })?;
-
-                data_type.clone()
+                struct_type
+                    .field(name)
+                    .ok_or(TypeError::FieldNotDefined {
+                        name,
+                        structure: struct_type.name,
+                    })?
+                    .clone()
             }
             AstKind::Block {
                 ref mut statements, ..
@@ -311,24 +766,106 @@ impl<'src> DataType<'src> {
             AstKind::VariableDeclaration(VariableDeclaration {
                 ref data_type,
                 ref mut value,
+                alignment,
                 ..
             }) => {
                 if let Some(ref mut value) = value {
                     data_type.infer(value)?;
                 }
 
+                // Only arrays, which are what can be used with SIMD, and the stack is already
+                // aligned to 8 bytes
+                if let Some(alignment) = *alignment {
+                    let Self::Array { .. } = data_type else {
+                        return Err(TypeError::NotAnArray.into());
+                    };
+
+                    if !alignment.is_power_of_two() {
+                        return Err(TypeError::NotAPowerOfTwo.into());
+                    }
+
+                    if alignment < 8 {
+                        return Err(TypeError::UnderAligned { alignment }.into());
+                    }
+                }
+
                 Self::Void
             }
             AstKind::FunctionDeclaration {
+                name,
+                attributes,
                 ref return_type,
                 ref mut body,
                 ..
             } => {
                 return_type.infer(body)?;
 
+                if attributes.inline == InlineHint::Always && body.calls(name) {
+                    return Err(TypeError::RecursiveInline { name }.into());
+                }
+
                 Self::Void
             }
-            AstKind::StructDeclaration { .. } => Self::Void,
+            // Control never continues past it, so it fits in wherever a value of any type is expected
+            AstKind::Return {
+                ref mut value,
+                ref return_type,
+            } => {
+                match value {
+                    Some(value) => return_type.infer(value)?,
+                    None if *return_type != Self::Void => {
+                        return Err(TypeError::ExpectedType {
+                            expected: return_type.clone(),
+                            found: Self::Void,
+                        }
+                        .into());
+                    }
+                    None => {}
+                }
+
+                Self::Inferred(InferredType::Any)
+            }
+            // Its error has already been reported, anything it's used as would only add more
+            AstKind::Error => Self::Inferred(InferredType::Any),
+            AstKind::StructDeclaration { .. }
+            | AstKind::EnumDeclaration { .. }
+            | AstKind::ConstantDeclaration { .. }
+            | AstKind::StaticDeclaration { .. }
+            | AstKind::GenericFunctionDeclaration { .. }
+            | AstKind::ExternDeclaration { .. } => Self::Void,
+            AstKind::EnumVariant { name, variant } => {
+                let Some(Symbol::Enum(data_type)) = symbol_table.get_symbol(name) else {
+                    return Err(TypeError::NotDefined { name }.into());
+                };
+
+                let Self::Enum(ref enum_type) = data_type else {
+                    unreachable!("Enumeration symbols always hold an enumeration type");
+                };
+
+                if enum_type.variant(variant).is_none() {
+                    return Err(TypeError::VariantNotDefined {
+                        name: variant,
+                        enumeration: name,
+                    }
+                    .into());
+                }
+
+                data_type.clone()
+            }
+            AstKind::Instance {
+                name,
+                ref type_arguments,
+            } => {
+                let Some(Symbol::Generic(generic)) = symbol_table.get_symbol(name) else {
+                    return Err(TypeError::NotDefined { name }.into());
+                };
+
+                let instance_id = generic
+                    .instance(type_arguments)
+                    .expect("Instances are created before they are referred to");
+
+                generic.instances[instance_id].1.clone()
+            }
             AstKind::IfStatement {
                 ref mut condition,
                 ref mut if_block,
@@ -353,6 +890,67 @@ impl<'src> DataType<'src> {
 
                 if_block.data_type.clone()
             }
+            // Every arm has to give the same type, like `if` and `else` do
+            AstKind::Match {
+                ref mut scrutinee,
+                ref mut arms,
+                ref mut default,
+            } => {
+                if let Self::Inferred(InferredType::Int) = scrutinee.data_type {
+                    Self::Int(IntType::S64).infer(scrutinee)?;
+                }
+
+                if !scrutinee.data_type.is_integer()
+                    && !matches!(scrutinee.data_type, Self::Enum(_) | Self::Char)
+                {
+                    return Err(TypeError::NotANumber.into());
+                }
+
+                let mut values = HashSet::new();
+
+                for arm in arms.iter_mut() {
+                    scrutinee.data_type.infer(&mut arm.pattern)?;
+
+                    let value = match (&arm.pattern.kind, &arm.pattern.data_type) {
+                        (AstKind::EnumVariant { variant, .. }, Self::Enum(enum_type)) => {
+                            enum_type.variant(variant).map(|value| value as i128)
+                        }
+                        _ => arm.pattern.fold(),
+                    };
+
+                    if let Some(value) = value {
+                        if !values.insert(value) {
+                            return Err(TypeError::UnreachableArm.into());
+                        }
+                    }
+                }
+
+                let data_type = arms
+                    .iter()
+                    .map(|arm| &arm.body.data_type)
+                    .chain([&default.data_type])
+                    .find(|data_type| !data_type.is_inferred())
+                    .unwrap_or(&default.data_type)
+                    .clone();
+
+                for body in arms
+                    .iter_mut()
+                    .map(|arm| &mut arm.body)
+                    .chain([default.as_mut()])
+                {
+                    data_type.infer(body)?;
+
+                    if body.data_type != data_type {
+                        return Err(TypeError::TypeMismatch {
+                            first: data_type,
+                            second: body.data_type.clone(),
+                        }
+                        .into());
+                    }
+                }
+
+                data_type
+            }
             AstKind::Call {
                 ref mut lhs,
                 ref mut arguments,
@@ -381,17 +979,317 @@ impl<'src> DataType<'src> {
 
                 body.data_type.clone()
             }
+            AstKind::Intrinsic {
+                intrinsic,
+                ref mut arguments,
+            } => match intrinsic {
+                Intrinsic::Breakpoint | Intrinsic::Trap => {
+                    if !arguments.is_empty() {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    }
+
+                    Self::Void
+                }
+                Intrinsic::Rdtsc => {
+                    if !arguments.is_empty() {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    }
+
+                    Self::Int(IntType::U64)
+                }
+                Intrinsic::SourceLocation => {
+                    unreachable!("The location should have been turned into a string while parsing")
+                }
+                // Strings and characters are written as they are, integers in decimal and booleans
+                // as `true` or `false`
+                Intrinsic::Print | Intrinsic::PrintLine => {
+                    let string = Self::Ref(Box::new(Self::Int(IntType::U8)));
+
+                    // Everything ends up being written by the runtime, which might not provide it
+                    if symbol_table.get_builtin("write").is_none() {
+                        return Err(TypeError::NotDefined { name: "write" }.into());
+                    }
+
+                    for argument in arguments.iter_mut() {
+                        if let Some(data_type) = Self::default_of(argument) {
+                            data_type.infer(argument)?;
+                        }
+
+                        if let Self::Int(_) = argument.data_type {
+                            if symbol_table.get_builtin("print_integer").is_none() {
+                                return Err(TypeError::NotDefined {
+                                    name: "print_integer",
+                                }
+                                .into());
+                            }
+                        }
+
+                        if !matches!(argument.data_type, Self::Int(_) | Self::Bool | Self::Char)
+                            && argument.data_type != string
+                        {
+                            return Err(TypeError::CannotPrint {
+                                found: argument.data_type.clone(),
+                            }
+                            .into());
+                        }
+                    }
+
+                    Self::Void
+                }
+                Intrinsic::Length => {
+                    let [ref mut string] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    Self::Ref(Box::new(Self::Int(IntType::U8))).infer(string)?;
+
+                    Self::Int(IntType::U64)
+                }
+                Intrinsic::SizeOfVal => {
+                    let [ref mut value] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    if let Self::Inferred(_) = value.data_type {
+                        Self::Int(IntType::U64).infer(value)?;
+                    }
+
+                    // Like `[]`, which could be an array of anything
+                    if value.data_type.is_inferred() {
+                        return Err(TypeError::CannotInfer.into());
+                    }
+
+                    Self::Int(IntType::U64)
+                }
+                // Even `[]` has a length, so the elements don't need to be inferred
+                Intrinsic::CountOf => {
+                    let [ref value] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    let Self::Array { .. } = value.data_type else {
+                        return Err(TypeError::NotAnArray.into());
+                    };
+
+                    Self::Int(IntType::U64)
+                }
+                Intrinsic::AlignUp | Intrinsic::AlignDown | Intrinsic::IsAligned => {
+                    let [ref mut pointer, ref mut alignment] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    let Self::Ref(_) = pointer.data_type else {
+                        return Err(TypeError::NotAReference.into());
+                    };
+
+                    Self::Int(IntType::U64).infer(alignment)?;
+
+                    // Folded, so that `8 * 3` is caught as well as `24`
+                    if let Some(value) = alignment.fold() {
+                        if !u64::try_from(value).is_ok_and(u64::is_power_of_two) {
+                            return Err(TypeError::NotAPowerOfTwo.into());
+                        }
+                    }
+
+                    if *intrinsic == Intrinsic::IsAligned {
+                        Self::Bool
+                    } else {
+                        pointer.data_type.clone()
+                    }
+                }
+                Intrinsic::Alloc | Intrinsic::Null => {
+                    if !arguments.is_empty() {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    }
+
+                    // Allocation is done by the runtime, which might not provide it
+                    if *intrinsic == Intrinsic::Alloc && symbol_table.get_symbol("malloc").is_none() {
+                        return Err(TypeError::NotDefined { name: "malloc" }.into());
+                    }
+
+                    Self::Inferred(InferredType::Ref)
+                }
+                Intrinsic::Free => {
+                    let [ref pointer] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    if symbol_table.get_symbol("free").is_none() {
+                        return Err(TypeError::NotDefined { name: "free" }.into());
+                    }
+
+                    let Self::Ref(_) = pointer.data_type else {
+                        return Err(TypeError::NotAReference.into());
+                    };
+
+                    Self::Void
+                }
+                Intrinsic::Memcpy => {
+                    let [ref dst, ref src, ref mut size] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    if !dst.data_type.is_reference() || !src.data_type.is_reference() {
+                        return Err(TypeError::NotAReference.into());
+                    }
+
+                    Self::Int(IntType::U64).infer(size)?;
+
+                    Self::Void
+                }
+                Intrinsic::Assume => {
+                    let [ref mut condition] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    Self::Bool.infer(condition)?;
+
+                    Self::Void
+                }
+                // Negating the smallest value gives back the same value, like `-` does
+                Intrinsic::WrappingNeg => {
+                    let [ref mut value] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    // Like `-`, a literal gets its width from wherever the result goes
+                    match value.data_type {
+                        Self::Inferred(InferredType::Int) => Self::Inferred(InferredType::Int),
+                        Self::Int(int_type) if int_type.is_signed() => value.data_type.clone(),
+                        Self::Int(_) => return Err(TypeError::NotSigned.into()),
+                        _ => return Err(TypeError::NotANumber.into()),
+                    }
+                }
+                Intrinsic::ToChar | Intrinsic::ToByte => {
+                    let [ref mut value] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    let (from, to) = match intrinsic {
+                        Intrinsic::ToChar => (Self::Int(IntType::U8), Self::Char),
+                        _ => (Self::Char, Self::Int(IntType::U8)),
+                    };
+
+                    from.infer(value)?;
+
+                    to
+                }
+                // Counted in elements, the count has to be as wide as the reference
+                Intrinsic::Offset => {
+                    let [ref mut pointer, ref mut count] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    let Self::Ref(_) = pointer.data_type else {
+                        return Err(TypeError::NotAReference.into());
+                    };
+
+                    if let Self::Inferred(InferredType::Int) = count.data_type {
+                        Self::Int(IntType::S64).infer(count)?;
+                    }
+
+                    if !matches!(count.data_type, Self::Int(IntType::U64 | IntType::S64)) {
+                        return Err(TypeError::ExpectedType {
+                            expected: Self::Int(IntType::S64),
+                            found: count.data_type.clone(),
+                        }
+                        .into());
+                    }
+
+                    pointer.data_type.clone()
+                }
+                Intrinsic::ByteAt | Intrinsic::SetByteAt => {
+                    let (pointer, index) = match (*intrinsic, &mut arguments[..]) {
+                        (Intrinsic::ByteAt, [pointer, index]) => (pointer, index),
+                        (Intrinsic::SetByteAt, [pointer, index, value]) => {
+                            Self::Int(IntType::U8).infer(value)?;
+
+                            if value.data_type != Self::Int(IntType::U8) {
+                                return Err(TypeError::ExpectedType {
+                                    expected: Self::Int(IntType::U8),
+                                    found: value.data_type.clone(),
+                                }
+                                .into());
+                            }
+
+                            (pointer, index)
+                        }
+                        _ => return Err(TypeError::WrongNumberOfArguments.into()),
+                    };
+
+                    let string = Self::Ref(Box::new(Self::Int(IntType::U8)));
+
+                    if pointer.data_type != string {
+                        return Err(TypeError::ExpectedType {
+                            expected: string,
+                            found: pointer.data_type.clone(),
+                        }
+                        .into());
+                    }
+
+                    Self::Int(IntType::U64).infer(index)?;
+
+                    if index.data_type != Self::Int(IntType::U64) {
+                        return Err(TypeError::ExpectedType {
+                            expected: Self::Int(IntType::U64),
+                            found: index.data_type.clone(),
+                        }
+                        .into());
+                    }
+
+                    match intrinsic {
+                        Intrinsic::ByteAt => Self::Int(IntType::U8),
+                        _ => Self::Void,
+                    }
+                }
+                Intrinsic::IsNull => {
+                    let [ref pointer] = arguments[..] else {
+                        return Err(TypeError::WrongNumberOfArguments.into());
+                    };
+
+                    let Self::Ref(_) = pointer.data_type else {
+                        return Err(TypeError::NotAReference.into());
+                    };
+
+                    Self::Bool
+                }
+            },
         };
 
         Ok(data_type)
     }
 
     pub fn infer(&self, ast: &mut Ast<'src>) -> CompilerResult<'src, ()> {
+        // Its type can't be known, and the error that caused that has already been reported
+        if ast.is_recovered() {
+            return Ok(());
+        }
+
+        // A character literal still stands for its byte where a `U8` is expected, like in strings
+        if let AstKind::Node {
+            token:
+                Token {
+                    kind: TokenKind::Char(_),
+                    ..
+                },
+        } = ast.kind
+        {
+            if *self == Self::Int(IntType::U8) {
+                ast.data_type = self.clone();
+
+                return Ok(());
+            }
+        }
+
         let Self::Inferred(ast_inferred_type) = ast.data_type else {
-            if let Self::Inferred(_) = self {
+            if self.is_inferred() {
                 return Ok(());
             }
 
+            if ast.data_type.is_inferred() {
+                return self.infer_array(ast);
+            }
+
             if self != &ast.data_type {
                 return Err(
                     TypeError::ExpectedType {
@@ -405,7 +1303,10 @@ impl<'src> DataType<'src> {
             return Ok(());
         };
 
-        if ast_inferred_type == InferredType::Int && !self.is_integer() {
+        if (ast_inferred_type == InferredType::Int && !self.is_integer())
+            || (ast_inferred_type == InferredType::Float && !self.is_float())
+            || (ast_inferred_type == InferredType::Ref && !self.is_reference())
+        {
             return Err(TypeError::ExpectedType {
                 expected: self.clone(),
                 found: Self::Inferred(ast_inferred_type),
@@ -414,11 +1315,34 @@ impl<'src> DataType<'src> {
         }
 
         match ast.kind {
+            AstKind::Node {
+                token:
+                    Token {
+                        kind: TokenKind::Number(value, _),
+                        ..
+                    },
+            } => {
+                if let Self::Int(int_type) = *self {
+                    if !int_type.contains(value) {
+                        return Err(TypeError::NumberOutOfRange { value, int_type }.into());
+                    }
+                }
+            }
+            AstKind::Intrinsic {
+                intrinsic: Intrinsic::WrappingNeg,
+                ref mut arguments,
+            } => {
+                if !self.is_signed_integer() {
+                    return Err(TypeError::NotSigned.into());
+                }
+
+                self.infer(&mut arguments[0])?;
+            }
             AstKind::Prefix {
                 ref mut node,
                 ref oper,
             } => match oper.kind {
-                TokenKind::Sub | TokenKind::Not => self.infer(node)?,
+                TokenKind::Sub | TokenKind::Not | TokenKind::BitNot => self.infer(node)?,
                 TokenKind::Hash => {
                     let Self::Ref(ref deref) = self else {
                             return Err(TypeError::ExpectedType {
@@ -441,11 +1365,16 @@ impl<'src> DataType<'src> {
                 | TokenKind::Sub
                 | TokenKind::Mul
                 | TokenKind::Div
-                | TokenKind::Mod => {
+                | TokenKind::Mod
+                | TokenKind::BitAnd
+                | TokenKind::BitOr
+                | TokenKind::BitXor => {
                     self.infer(lhs)?;
                     self.infer(rhs)?;
                 }
-                TokenKind::Equals
+                TokenKind::And
+                | TokenKind::Or
+                | TokenKind::Equals
                 | TokenKind::NotEquals
                 | TokenKind::Less
                 | TokenKind::Greater
@@ -480,6 +1409,17 @@ impl<'src> DataType<'src> {
                 self.infer(if_block)?;
                 self.infer(else_block)?;
             }
+            AstKind::Match {
+                ref mut arms,
+                ref mut default,
+                ..
+            } => {
+                for arm in arms.iter_mut() {
+                    self.infer(&mut arm.body)?;
+                }
+
+                self.infer(default)?;
+            }
             _ => {}
         }
 
@@ -488,10 +1428,120 @@ impl<'src> DataType<'src> {
         Ok(())
     }
 
+    // An array of something still unknown, which is filled in down to the literals it comes from
+    fn infer_array(&self, ast: &mut Ast<'src>) -> CompilerResult<'src, ()> {
+        let mismatch = || TypeError::ExpectedType {
+            expected: self.clone(),
+            found: ast.data_type.clone(),
+        };
+
+        match ast.kind {
+            AstKind::ArrayLiteral { ref mut elements } => {
+                let Self::Array { element, length } = self else {
+                    return Err(mismatch().into());
+                };
+
+                if *length != elements.len() {
+                    return Err(mismatch().into());
+                }
+
+                for other in elements {
+                    element.infer(other)?;
+                }
+            }
+            AstKind::Block {
+                ref mut statements, ..
+            } => {
+                if let Some(statement) = statements.last_mut() {
+                    self.infer(statement)?;
+                }
+            }
+            AstKind::IfStatement {
+                ref mut if_block,
+                else_block: Some(ref mut else_block),
+                ..
+            } => {
+                self.infer(if_block)?;
+                self.infer(else_block)?;
+            }
+            AstKind::Match {
+                ref mut arms,
+                ref mut default,
+                ..
+            } => {
+                for arm in arms.iter_mut() {
+                    self.infer(&mut arm.body)?;
+                }
+
+                self.infer(default)?;
+            }
+            _ => return Err(TypeError::CannotInfer.into()),
+        }
+
+        ast.data_type = self.clone();
+
+        Ok(())
+    }
+
     pub fn is_integer(&self) -> bool {
         matches!(self, Self::Int(_) | Self::Inferred(InferredType::Int))
     }
 
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::Float(_) | Self::Inferred(InferredType::Float))
+    }
+
+    pub fn is_number(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, Self::Ref(_) | Self::Inferred(InferredType::Ref))
+    }
+
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self, Self::Struct(_) | Self::Array { .. })
+    }
+
+    // Whether it is still unknown, or an array of something still unknown
+    pub fn is_inferred(&self) -> bool {
+        matches!(self.innermost_element(), Self::Inferred(_))
+    }
+
+    // What an array is made of, through any number of nested arrays
+    pub fn innermost_element(&self) -> &Self {
+        match self {
+            Self::Array { element, .. } => element.innermost_element(),
+            _ => self,
+        }
+    }
+
+    // What a value of this type is stored as when nothing else decides it, integer literals are
+    // `U64` like the program's value and float literals are doubles. `None` if there's nothing to
+    // go on at all
+    pub fn defaulted(&self) -> Option<Self> {
+        match self {
+            Self::Inferred(InferredType::Int) => Some(Self::Int(IntType::U64)),
+            Self::Inferred(InferredType::Float) => Some(Self::Float(FloatType::F64)),
+            Self::Inferred(_) => None,
+            Self::Array { element, length } => Some(Self::Array {
+                element: Box::new(element.defaulted()?),
+                length: *length,
+            }),
+            _ => Some(self.clone()),
+        }
+    }
+
+    // The default for a value, a negative literal can't be unsigned so it's `S64` instead
+    pub fn default_of(value: &Ast<'src>) -> Option<Self> {
+        match value.data_type {
+            Self::Inferred(InferredType::Int) if value.fold().is_some_and(|n| n < 0) => {
+                Some(Self::Int(IntType::S64))
+            }
+            _ => value.data_type.defaulted(),
+        }
+    }
+
     pub fn is_signed_integer(&self) -> bool {
         match self {
             Self::Int(int_type) if int_type.is_signed() => true,
@@ -503,13 +1553,18 @@ impl<'src> DataType<'src> {
     pub fn size(&self) -> usize {
         match self {
             Self::Void => 0,
-            Self::Bool => 1,
+            Self::Bool | Self::Char => 1,
             Self::Int(int_type) => int_type.size(),
+            Self::Float(float_type) => float_type.size(),
+            Self::Enum(enum_type) => enum_type.int_type().size(),
             Self::Ref(_) | Self::Function { .. } => 8,
-            Self::Struct(ref fields) => fields
+            Self::Struct(ref struct_type) => struct_type
+                .fields()
                 .iter()
                 .map(|(_, data_type)| data_type.size_aligned())
                 .sum(),
+            // Elements are packed, only the array as a whole is aligned
+            Self::Array { element, length } => length * element.size(),
             _ => unreachable!(),
         }
     }
@@ -525,32 +1580,33 @@ impl<'src> fmt::Debug for DataType<'src> {
         match self {
             Self::Void => write!(f, "Void"),
             Self::Bool => write!(f, "Bool"),
+            Self::Char => write!(f, "Char"),
             Self::Inferred(inferred) => write!(f, "{inferred:?}"),
             Self::Int(int) => write!(f, "{int:?}"),
+            Self::Float(float) => write!(f, "{float:?}"),
             Self::Ref(deref) => write!(f, "#{deref:?}"),
-            Self::Struct(fields) => {
-                write!(
-                    f,
-                    "Struct {{ {}}}",
-                    fields
-                        .iter()
-                        .map(|(name, data_type)| format!("let {name}: {data_type:?}; "))
-                        .collect::<String>()
-                )
-            }
+            Self::Struct(struct_type) => write!(f, "{}", struct_type.name),
+            Self::Enum(enum_type) => write!(f, "{}", enum_type.name),
+            Self::Array { element, length } => write!(f, "[{element:?}; {length}]"),
             Self::Function {
                 return_type,
                 argument_types,
             } => {
                 write!(
                     f,
-                    "Fn ({}) -> {return_type:?}",
+                    "fn({})",
                     argument_types
                         .iter()
                         .map(|x| format!("{x:?}"))
                         .collect::<Vec<String>>()
                         .join(", ")
-                )
+                )?;
+
+                if **return_type != Self::Void {
+                    write!(f, ": {return_type:?}")?;
+                }
+
+                Ok(())
             }
         }
     }